@@ -1,26 +1,267 @@
+use std::time::{Duration, Instant};
+
+use futures::channel::oneshot;
 use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::http::AttachmentType;
 use serenity::model::prelude::*;
 use serenity::prelude::Context;
 
-use crate::core::eval;
+use crate::core::eval::{self, error::{Error, ErrorType, Pos}, options::{self, Mode, Options, MAX_PRECISION}};
+use crate::core::plot::render;
+
+/// Time an evaluation gets before it is stopped
+const EVAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Further wait for a thread stuck in one long step, such as a huge `det`, before giving up on it
+const EVAL_GRACE: Duration = Duration::from_secs(1);
+
+/// Run `work` on a thread of its own, so a slow evaluation doesn't hold up the shard
+///
+/// Errors span all `len` characters of the input, as running out of time has no better place.
+async fn off_thread<T, F>(len: usize, work: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    let spawned = std::thread::Builder::new()
+        .name("eval".to_string())
+        .stack_size(options::STACK_SIZE)
+        .spawn(move || {
+            let _ = tx.send(work());
+        });
+    let why = |msg: &str, error_type| Error::new(msg.to_string(), error_type, Pos::new(0, len));
+    if spawned.is_err() {
+        return Err(why("couldn't start evaluating", ErrorType::RuntimeError));
+    }
+
+    // The thread stops itself at its deadline, this only covers a single step running long
+    match tokio::time::timeout(EVAL_TIMEOUT + EVAL_GRACE, rx).await {
+        Ok(Ok(value)) => Ok(value),
+        // The sender is only dropped unsent when the thread panicked
+        Ok(Err(_)) => Err(why("evaluation failed unexpectedly", ErrorType::RuntimeError)),
+        Err(_) => Err(why("evaluation ran out of time", ErrorType::LimitError)),
+    }
+}
+
+/// What `/eval` shows of the input
+#[derive(Clone, Copy)]
+enum Report {
+    Value,
+    Explain, // How it was parsed, `--explain`
+    Steps,   // Each step of evaluating it, `--steps`
+}
+
+/// Room for text on a page, an embed field holds 1024 characters less the code block around it
+const PAGE_LEN: usize = 1016;
+
+/// Most pages sent for one input, the first ones and the last
+const MAX_PAGES: usize = 5;
+
+fn code_block(text: String) -> String {
+    format!("```\n{}\n```", text)
+}
+
+/// Number the steps, splitting them into pages that each fit an embed
+fn pages(steps: &[String]) -> Vec<String> {
+    let mut pages: Vec<String> = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        let line: String = format!("{}. {}", i + 1, step).chars().take(PAGE_LEN).collect();
+        match pages.last_mut() {
+            Some(page) if page.chars().count() + 1 + line.chars().count() <= PAGE_LEN => {
+                page.push('\n');
+                page.push_str(&line);
+            }
+            _ => pages.push(line),
+        }
+    }
+
+    pages
+}
+
+/// Peel leading `--flag`s off the arguments into evaluation options, or say which flag is wrong
+fn eval_options(args: &mut Args) -> Result<(Options, Report), String> {
+    let mut report = Report::Value;
+    let mut options = Options {
+        deadline: Some(Instant::now() + EVAL_TIMEOUT),
+        ..Options::default()
+    };
+    loop {
+        match args.current() {
+            Some("--lenient") => options.mode = Mode::Lenient,
+            Some("--strict") => options.mode = Mode::Strict,
+            Some("--explain") => report = Report::Explain,
+            Some("--steps") => report = Report::Steps,
+            // `--precision 4`, the flag and its figures
+            Some("--precision") => {
+                args.advance();
+                match args.current().map(|figs| (figs, figs.parse::<usize>().ok())) {
+                    Some((_, Some(figs))) if (1..=MAX_PRECISION).contains(&figs) => {
+                        options.format.precision = Some(figs)
+                    }
+                    found => {
+                        return Err(format!(
+                            "`--precision` takes a number of figures from 1 to {}, found {}",
+                            MAX_PRECISION,
+                            found.map_or("nothing".to_string(), |(figs, _)| format!("`{}`", figs))
+                        ))
+                    }
+                }
+            }
+            // `--hex`, `--oct`, `--bin` and `--sci`
+            Some(flag) if flag.starts_with("--") && options::is_conversion(&flag[2..]) => {
+                options.format.convert(&flag[2..])
+            }
+            _ => break,
+        }
+        args.advance();
+    }
+
+    Ok((options, report))
+}
 
 #[command]
 #[min_args(1)]
 #[description(r#"Evaluates a mathematical expression.
 
-Infix operators available: `+` (addition), `-` (subtraction), `/` (division), `//` (integer division), `*` (multiplication), `**` (exponent), `as` (conversion), `>`, `<`, `>=`, `<=`, `^` (bitwise xor), `|` (bitwise or), `&` (bitwise and), `&&` (logical and), `||` (logical or).
+Infix operators available: `+` (addition), `-` (subtraction), `/` (division, exact for integers: `1/3 + 1/6` is `1/2`), `//` (integer division), `*` (multiplication), `**` (exponent), `as` (conversion), `>`, `<`, `>=`, `<=`, `^` (bitwise xor), `|` (bitwise or), `&` (bitwise and), `&&` (logical and), `||` (logical or), `@` (matrix product).
+
+Prefix operators: `-` (negate), `+` (absolute), `~` (bitwise not), `!` (logical not).
+
+Functions: `sqrt`, `cbrt`, `exp`, `ln`, `log`, `sin`, `cos`, `tan`, `asin`, `acos`, `atan`, `abs`, `min`, `max`, `floor`, `ceil`, `round`, `gcd`, `lcm`, `arg`, `conj`, `re`, `im`, `len`, `sum`, `product`, `sort`, `det`, `inv`, `transpose`, `rank`, `solve`, `mean`, `median`, `mode`, `variance`, `stddev`, `percentile`, `corr`, `linreg` (`[slope, intercept]`), `factorial`, `nCr`, `nPr`.
+
+Numbers: `0xff`, `0b1010`, `0o17`, `1_000_000`, `1e-5`, `.5`.
+
+Complex numbers: `(1+2i)*(3-i)`, `sqrt(-4)`.
+
+Units: `5 km + 300 m`, `60 mph as km/h`, `3 kg * 9.81 m/s**2 as N`.
+
+Lists: `[1, 2, 3] + [4, 5, 6]`, `xs[0]`, `xs[-1]`, `xs[1:3]`.
 
-Prefix operators: `-` (negate), `+` (absolute), `~` (bitwise not), `!` (logical not)."#)]
-async fn eval(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let result = match eval::exec(&args.rest()[..]) {
-        Ok(val) => val,
-        Err(why) => why.to_string()
+Matrices: `[[1, 2], [3, 4]] @ [[5, 6], [7, 8]]` (`*` also multiplies two matrices), `det([[1, 2], [3, 4]])`, `solve([[2, 1], [1, 3]], [4, 7])`.
+
+Dates and durations: `2026-12-25 - today`, `now + 3h30m`, `now + 30 min`, `1.5h`, `(2026-01-01 - 2025-06-01) as days` (times are in UTC). A lone `m` is always metres, write `30 min` for minutes.
+
+Calculus: `d/dx (x**3 + sin(x))`, `diff(x**2*y, x)`, `simplify(x + x)` (names without a value stay as variables).
+
+Equations: `solve(x**2 - 4 = 0, x)` lists the solutions, exactly for linear and quadratic equations, otherwise numerically between -100 and 100.
+
+Conditionals: `if x < 0 then -x else x`, only the chosen branch is evaluated (`&&` and `||` also skip their right side once the left decides).
+
+Text: `"ab" + "cd"`, `len(s)`, `upper(s)`, `lower(s)`, `repeat("ab", 3)`, `split("a,b", ",")`, `join(xs, ", ")`, `"42" as int` (escape with `\"`, `\n`, `\t` and `\\`).
+
+Parsing: `--explain` shows the input with every operation in brackets, as in `((1 + (2 * 3)) - 4)`, and the type of each part.
+
+Steps: `--steps` shows each step of working it out, `2 + 3 * 4 ** 2` becomes `2 + 3 * 16`, then `2 + 48` and `50`.
+
+Output: `255 as hex`, `as oct`, `as bin`, `2**100 as sci`, `pi round to 3 sig figs` (these go last), or the flags `--hex`, `--oct`, `--bin`, `--sci` and `--precision 4`.
+
+Variables: `let r = 2.5; pi * r ** 2.0` (statements are separated by `;`).
+
+User functions: `f(x) = x ** 2 + 1; f(3)` or `sq = |x| x * x; sq(7)`.
+
+Limits: 5 seconds, 1,000,000 steps, nesting 500 deep (function calls included), 1000 digits and 1016 characters of output, past which you get a `LimitError`."#)]
+async fn eval(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (options, report) = match eval_options(&mut args) {
+        Ok(parsed) => parsed,
+        Err(why) => {
+            let _ = msg.channel_id.say(&ctx.http, why).await;
+            return Ok(());
+        }
+    };
+    let src = args.rest().to_string();
+    let work = move || {
+        let result = match report {
+            Report::Value => eval::run_with(&src, options),
+            Report::Explain => eval::explain(&src, options),
+            // `**` would turn bold outside of a code block
+            Report::Steps => {
+                let steps = eval::trace(&src, options)?;
+                return Ok(pages(&steps).into_iter().map(code_block).collect());
+            }
+        };
+        // Matrices are laid out over several lines, keep their columns aligned
+        result.map(|val| vec![if val.contains('\n') { code_block(val) } else { val }])
+    };
+    let pages = match off_thread(src.len(), work).await {
+        Ok(Ok(pages)) => pages,
+        // The underline only lines up in a monospace font
+        Ok(Err(errors)) => vec![code_block(eval::error::render_all(&errors, args.rest()))],
+        Err(why) => vec![code_block(eval::error::render_all(&[why], args.rest()))],
     };
 
+    let total = pages.len();
+    for (i, page) in pages.into_iter().enumerate() {
+        // Skip to the last page of a long trace, it ends in the result
+        if total > MAX_PAGES && i >= MAX_PAGES - 1 && i != total - 1 {
+            continue;
+        }
+
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.field(args.rest(), page, true);
+                    if total > 1 {
+                        e.footer(|f| f.text(format!("Page {}/{}", i + 1, total)));
+                    }
+                    e
+                })
+            })
+            .await;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[min_args(1)]
+#[description(r#"Plots expressions of `x`, separated by `,`.
+
+Usage: `/plot sin(x), x**2 from -5 to 5`, the range is -10 to 10 when left out and its bounds can be expressions such as `-2*pi`.
+
+Up to 8 curves are drawn, with a break wherever a curve has no value or jumps, as `tan(x)` does."#)]
+async fn plot(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let src = args.rest().to_string();
+    let filename = format!("plot{}.png", msg.id.0);
+    let path = filename.clone();
+    let options = Options {
+        deadline: Some(Instant::now() + EVAL_TIMEOUT),
+        ..Options::default()
+    };
+    let plotted = off_thread(src.len(), move || {
+        eval::plot::plot_src(&src, options).map(|plot| render(&plot, &path))
+    })
+    .await;
+
+    match plotted.and_then(|plotted| plotted) {
+        Ok(Ok(())) => {}
+        Ok(Err(why)) => {
+            let _ = msg.channel_id.say(&ctx.http, why).await;
+            return Ok(());
+        }
+        // Underlined like `/eval` errors, which only lines up in a monospace font
+        Err(why) => {
+            let rendered = code_block(eval::error::render_all(&[why], args.rest()));
+            let _ = msg
+                .channel_id
+                .send_message(&ctx.http, |m| m.embed(|e| e.field(args.rest(), rendered, true)))
+                .await;
+            return Ok(());
+        }
+    }
+
+    let file = tokio::fs::File::open(&filename).await?;
     let _ = msg
         .channel_id
-        .send_message(&ctx.http, |m| m.embed(|e| e.field(args.rest(), result, true)))
+        .send_message(&ctx.http, |m| {
+            m.add_file(AttachmentType::File {
+                file: &file,
+                filename: "plot.png".to_string(),
+            })
+        })
         .await;
+    tokio::fs::remove_file(&filename).await?;
 
     Ok(())
 }
@@ -1,11 +1,13 @@
 use crate::core::eval::error::*;
 use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
 pub(crate) enum Operator {
     Add,
     Sub,
     Mul,
+    MatMul, // Matrix product
     Mod,
 
     Div,
@@ -34,45 +36,52 @@ pub(crate) enum Operator {
     BitShiftL, // Left bit shift
 
     As, // Casting
+
+    Equation, // `=` inside an expression, as in `solve(x**2 = 4, x)`
+}
+
+impl Operator {
+    /// How the operator is written, `+` for `Add`
+    pub(crate) fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::MatMul => "@",
+            Operator::Mod => "%",
+            Operator::Div => "/",
+            Operator::IntDiv => "//",
+            Operator::Pow => "**",
+
+            Operator::Eql => "==",
+            Operator::NEql => "!=",
+
+            Operator::GT => ">",
+            Operator::LT => "<",
+            Operator::GE => ">=",
+            Operator::LE => "<=",
+
+            Operator::BAnd => "&",
+            Operator::BOr => "|",
+            Operator::BNot => "~",
+            Operator::BXor => "^",
+
+            Operator::LAnd => "&&",
+            Operator::LOr => "||",
+            Operator::LNot => "!",
+
+            Operator::As => "as",
+            Operator::Equation => "=",
+
+            Operator::BitShiftL => "<<",
+            Operator::BitShiftR => ">>",
+        }
+    }
 }
 
 impl fmt::Display for Operator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "operator {}",
-            match self {
-                Operator::Add => "+",
-                Operator::Sub => "-",
-                Operator::Mul => "*",
-                Operator::Mod => "%",
-                Operator::Div => "/",
-                Operator::IntDiv => "//",
-                Operator::Pow => "**",
-
-                Operator::Eql => "==",
-                Operator::NEql => "!=",
-
-                Operator::GT => ">",
-                Operator::LT => "<",
-                Operator::GE => ">=",
-                Operator::LE => "<=",
-
-                Operator::BAnd => "&",
-                Operator::BOr => "|",
-                Operator::BNot => "~",
-                Operator::BXor => "^",
-
-                Operator::LAnd => "&&",
-                Operator::LOr => "||",
-                Operator::LNot => "!",
-
-                Operator::As => "as",
-
-                Operator::BitShiftL => "<<",
-                Operator::BitShiftR => ">>",
-            }
-        )
+        write!(f, "operator {}", self.symbol())
     }
 }
 
@@ -80,8 +89,18 @@ impl fmt::Display for Operator {
 pub(crate) enum ExpressionKind<'a> {
     PrefixOp(PrefixOp<'a>),
     InfixOp(InfixOp<'a>),
+    Call(Call<'a>),
+    If(If<'a>), // if cond then a else b
+    Lambda(Lambda<'a>),
+    List(Vec<Expression<'a>>),
+    Index(Index<'a>),
     Integer(&'a str),
     Float(&'a str),
+    Imaginary(&'a str), // Without the trailing `i`
+    Date(&'a str),
+    DateTime(&'a str),
+    Duration(&'a str),
+    Str(&'a str), // Escapes are still in it
     Ident(&'a str),
     True,
     False
@@ -95,8 +114,18 @@ impl fmt::Display for ExpressionKind<'_> {
             match self {
                 ExpressionKind::PrefixOp(prefix) => prefix.op.to_string(),
                 ExpressionKind::InfixOp(infix) => infix.op.to_string(),
+                ExpressionKind::Call(_) => "function call".to_string(),
+                ExpressionKind::If(_) => "`if` expression".to_string(),
+                ExpressionKind::Lambda(_) => "function".to_string(),
+                ExpressionKind::List(_) => "list".to_string(),
+                ExpressionKind::Index(_) => "index".to_string(),
                 ExpressionKind::Integer(_) => "integer".to_string(),
                 ExpressionKind::Float(_) => "float".to_string(),
+                ExpressionKind::Imaginary(_) => "imaginary".to_string(),
+                ExpressionKind::Date(_) => "date".to_string(),
+                ExpressionKind::DateTime(_) => "date and time".to_string(),
+                ExpressionKind::Duration(_) => "duration".to_string(),
+                ExpressionKind::Str(_) => "string".to_string(),
                 ExpressionKind::Ident(_) => "identifier".to_string(),
                 ExpressionKind::True => "true".to_string(),
                 ExpressionKind::False => "false".to_string()
@@ -111,6 +140,63 @@ pub(crate) struct Expression<'a> {
     pub(crate) pos: Pos,
 }
 
+/// Written back out with every operation in brackets, so `1 + 2 * 3` is `(1 + (2 * 3))`
+impl fmt::Display for Expression<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expr {
+            ExpressionKind::PrefixOp(prefix) => write!(f, "({}{})", prefix.op.symbol(), prefix.value),
+            ExpressionKind::InfixOp(infix) => {
+                write!(f, "({} {} {})", infix.left, infix.op.symbol(), infix.right)
+            }
+            ExpressionKind::Call(call) => write!(f, "{}({})", call.func, Joined(&call.args)),
+            ExpressionKind::If(cond) => {
+                write!(f, "(if {} then {} else {})", cond.cond, cond.then, cond.otherwise)
+            }
+            ExpressionKind::Lambda(lambda) => write!(f, "(|{}| {})", lambda.params.join(", "), lambda.body),
+            ExpressionKind::List(items) => write!(f, "[{}]", Joined(items)),
+            ExpressionKind::Index(index) => match &index.subscript {
+                Subscript::Item(item) => write!(f, "{}[{}]", index.value, item),
+                Subscript::Slice(start, end) => {
+                    write!(f, "{}[", index.value)?;
+                    if let Some(start) = start {
+                        write!(f, "{}", start)?;
+                    }
+                    write!(f, ":")?;
+                    if let Some(end) = end {
+                        write!(f, "{}", end)?;
+                    }
+                    write!(f, "]")
+                }
+            },
+            ExpressionKind::Imaginary(val) => write!(f, "{}i", val),
+            ExpressionKind::Str(val) => write!(f, "\"{}\"", val),
+            ExpressionKind::Integer(val)
+            | ExpressionKind::Float(val)
+            | ExpressionKind::Date(val)
+            | ExpressionKind::DateTime(val)
+            | ExpressionKind::Duration(val)
+            | ExpressionKind::Ident(val) => write!(f, "{}", val),
+            ExpressionKind::True => write!(f, "true"),
+            ExpressionKind::False => write!(f, "false"),
+        }
+    }
+}
+
+/// Expressions separated by `, `
+struct Joined<'e, 'a>(&'e [Expression<'a>]);
+
+impl fmt::Display for Joined<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, expr) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", expr)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct InfixOp<'a> {
     pub(crate) op: Operator,
@@ -124,13 +210,89 @@ pub(crate) struct PrefixOp<'a> {
     pub(crate) value: Box<Expression<'a>>,
 }
 
+#[derive(Debug, PartialEq)]
+pub(crate) struct Call<'a> {
+    pub(crate) func: Box<Expression<'a>>,
+    pub(crate) args: Vec<Expression<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Subscript<'a> {
+    Item(Box<Expression<'a>>), // xs[i]
+    Slice(Option<Box<Expression<'a>>>, Option<Box<Expression<'a>>>), // xs[a:b], either end optional
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Index<'a> {
+    pub(crate) value: Box<Expression<'a>>,
+    pub(crate) subscript: Subscript<'a>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct If<'a> {
+    pub(crate) cond: Box<Expression<'a>>,
+    pub(crate) then: Box<Expression<'a>>,
+    pub(crate) otherwise: Box<Expression<'a>>, // Only the chosen branch is evaluated
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Lambda<'a> {
+    pub(crate) params: Vec<&'a str>,
+    pub(crate) body: Rc<Expression<'a>>, // Shared with every closure made from it
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Assign<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) name_pos: Pos,
+    pub(crate) value: Box<Expression<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum StatementKind<'a> {
+    Let(Assign<'a>),    // let x = 1
+    Assign(Assign<'a>), // x = 1, or f(x) = x with a lambda value
+    Expr(Expression<'a>),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Statement<'a> {
+    pub(crate) stmt: StatementKind<'a>,
+    pub(crate) pos: Pos,
+}
+
+impl fmt::Display for Statement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.stmt {
+            StatementKind::Let(assign) => write!(f, "let {} = {}", assign.name, assign.value),
+            StatementKind::Assign(assign) => write!(f, "{} = {}", assign.name, assign.value),
+            StatementKind::Expr(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum TokenType {
     Identifier,
     Integer,
     Float,
+    Imaginary, // 2i
+    Date, // 2026-12-25
+    DateTime, // 2026-12-25T18:30
+    Duration, // 3h30m
+    Str, // "text", holding what is between the quotes
     RP, // )
     LP, // (
+    RB, // ]
+    LB, // [
+    Colon, // :
+    Comma, // ,
+    Assign, // =
+    Semicolon, // ;
+    Let,
+    If,
+    Then,
+    Else,
     EOF,
     TRUE,
     FALSE,
@@ -146,9 +308,24 @@ impl fmt::Display for TokenType {
                 // XXX: Get rid of these sad allocations
                 TokenType::Integer => "integer".to_string(),
                 TokenType::Float => "float".to_string(),
+                TokenType::Imaginary => "imaginary".to_string(),
+                TokenType::Date => "date".to_string(),
+                TokenType::DateTime => "date and time".to_string(),
+                TokenType::Duration => "duration".to_string(),
+                TokenType::Str => "string".to_string(),
                 TokenType::Identifier => "identifier".to_string(),
                 TokenType::RP => "`)`".to_string(),
                 TokenType::LP => "`(`".to_string(),
+                TokenType::RB => "`]`".to_string(),
+                TokenType::LB => "`[`".to_string(),
+                TokenType::Colon => "`:`".to_string(),
+                TokenType::Comma => "`,`".to_string(),
+                TokenType::Assign => "`=`".to_string(),
+                TokenType::Semicolon => "`;`".to_string(),
+                TokenType::Let => "`let`".to_string(),
+                TokenType::If => "`if`".to_string(),
+                TokenType::Then => "`then`".to_string(),
+                TokenType::Else => "`else`".to_string(),
                 TokenType::EOF => "end of file".to_string(),
 
                 TokenType::TRUE => "true".to_string(),
@@ -168,10 +345,14 @@ pub(crate) struct Token<'a> {
 }
 
 impl<'a> Token<'a> {
-    pub(crate) fn unwrap_op(&self) -> Operator {
+    pub(crate) fn op(&self) -> Result<Operator, Error> {
         match self.tok_type {
-            TokenType::Operator(op) => op,
-            _ => panic!("Tried to unwrap non operator")
+            TokenType::Operator(op) => Ok(op),
+            _ => Err(Error::new(
+                format!("expected an operator, found {}", self),
+                ErrorType::SyntaxError,
+                self.pos,
+            )),
         }
     }
 }
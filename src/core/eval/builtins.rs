@@ -0,0 +1,521 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use num::bigint::BigInt;
+use num::complex::Complex64;
+use num::traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+use num::Integer as _;
+
+use crate::core::eval::error::*;
+use crate::core::eval::exec::{
+    big_to_f64, text, too_long_err, ExecutionExpr, ExecutionExpr::*, EE, MAX_STRING_LEN,
+};
+use crate::core::eval::matrix;
+use crate::core::eval::options::{Mode, Options};
+use crate::core::eval::stats;
+use crate::core::eval::units;
+
+type NativeFn = fn(Pos, &[EE]) -> Result<ExecutionExpr, Error>;
+
+/// A native that keeps to the limits in the options, such as the digit limit
+type LimitedFn = fn(Pos, &[EE], &Options) -> Result<ExecutionExpr, Error>;
+
+#[derive(Clone, Copy)]
+enum Func {
+    Plain(NativeFn),
+    Limited(LimitedFn),
+}
+
+/// A function implemented in Rust and callable from an expression
+#[derive(Clone, Copy)]
+pub(crate) struct Native {
+    min_args: usize,
+    max_args: Option<usize>, // None for variadic functions
+    func: Func,
+    widens: bool, // Compares or adds up its numbers, so lenient mode widens them first
+    floats: bool, // Works in floats, so its numbers are widened to one type in strict mode too
+}
+
+impl Native {
+    fn new(min_args: usize, max_args: Option<usize>, func: NativeFn) -> Self {
+        Native {
+            min_args,
+            max_args,
+            func: Func::Plain(func),
+            widens: false,
+            floats: false,
+        }
+    }
+
+    /// A native given the options, to keep to their limits
+    fn limited(min_args: usize, max_args: Option<usize>, func: LimitedFn) -> Self {
+        Native {
+            min_args,
+            max_args,
+            func: Func::Limited(func),
+            widens: false,
+            floats: false,
+        }
+    }
+
+    /// Widen the numbers in the arguments to one type in lenient mode, as operators do
+    fn widening(self) -> Self {
+        Native {
+            widens: true,
+            ..self
+        }
+    }
+
+    /// Widen the numbers in the arguments to one type whatever the mode, as in `log(8, 2.0)`
+    fn on_floats(self) -> Self {
+        Native {
+            floats: true,
+            ..self
+        }
+    }
+
+    fn check_arity(&self, name: &str, found: usize, pos: Pos) -> Result<(), Error> {
+        let expected = match self.max_args {
+            Some(max) if max == self.min_args => self.min_args.to_string(),
+            Some(max) => format!("{} to {}", self.min_args, max),
+            None => format!("at least {}", self.min_args),
+        };
+
+        if found < self.min_args || matches!(self.max_args, Some(max) if found > max) {
+            return Err(Error::new(
+                format!(
+                    "function `{}` takes {} argument(s) but {} were supplied",
+                    name, expected, found
+                ),
+                ErrorType::TypeError,
+                pos,
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn call(&self, name: &str, pos: Pos, args: &[EE], options: &Options) -> Result<EE, Error> {
+        self.check_arity(name, args.len(), pos)?;
+        let widened;
+        let args = if self.floats || (self.widens && options.mode == Mode::Lenient) {
+            widened = EE::widen_all(args);
+            &widened
+        } else {
+            args
+        };
+
+        let value = match self.func {
+            Func::Plain(func) => func(pos, args)?,
+            Func::Limited(func) => func(pos, args, options)?,
+        };
+        Ok(EE::new(value, pos))
+    }
+}
+
+/// Every native function, keyed by the name it is called with
+pub(crate) fn registry() -> HashMap<&'static str, Native> {
+    let mut natives = HashMap::new();
+
+    natives.insert("sqrt", Native::new(1, Some(1), sqrt).on_floats());
+    natives.insert("cbrt", Native::new(1, Some(1), cbrt).on_floats());
+    natives.insert("exp", Native::new(1, Some(1), exp).on_floats());
+    natives.insert("ln", Native::new(1, Some(1), ln).on_floats());
+    natives.insert("log", Native::new(1, Some(2), log).on_floats());
+
+    natives.insert("sin", Native::new(1, Some(1), sin).on_floats());
+    natives.insert("cos", Native::new(1, Some(1), cos).on_floats());
+    natives.insert("tan", Native::new(1, Some(1), tan).on_floats());
+    natives.insert("asin", Native::new(1, Some(1), asin).on_floats());
+    natives.insert("acos", Native::new(1, Some(1), acos).on_floats());
+    natives.insert("atan", Native::new(1, Some(1), atan).on_floats());
+
+    natives.insert("abs", Native::new(1, Some(1), abs));
+    natives.insert("arg", Native::new(1, Some(1), arg));
+    natives.insert("conj", Native::new(1, Some(1), conj));
+    natives.insert("re", Native::new(1, Some(1), re));
+    natives.insert("im", Native::new(1, Some(1), im));
+    natives.insert("min", Native::new(1, None, min).widening());
+    natives.insert("max", Native::new(1, None, max).widening());
+
+    natives.insert("floor", Native::new(1, Some(1), floor));
+    natives.insert("ceil", Native::new(1, Some(1), ceil));
+    natives.insert("round", Native::new(1, Some(1), round));
+
+    natives.insert("gcd", Native::new(2, Some(2), gcd));
+    natives.insert("lcm", Native::new(2, Some(2), lcm));
+
+    natives.insert("len", Native::new(1, Some(1), len));
+    natives.insert("sum", Native::new(1, Some(1), sum).widening());
+    natives.insert("product", Native::new(1, Some(1), product).widening());
+    natives.insert("sort", Native::new(1, Some(1), sort).widening());
+
+    natives.insert("upper", Native::new(1, Some(1), upper));
+    natives.insert("lower", Native::new(1, Some(1), lower));
+    natives.insert("repeat", Native::new(2, Some(2), repeat));
+    natives.insert("split", Native::new(1, Some(2), split));
+    natives.insert("join", Native::new(1, Some(2), join));
+
+    natives.insert("det", Native::limited(1, Some(1), matrix::det));
+    natives.insert("inv", Native::limited(1, Some(1), matrix::inv));
+    natives.insert("transpose", Native::new(1, Some(1), matrix::transpose));
+    natives.insert("rank", Native::limited(1, Some(1), matrix::rank));
+    natives.insert("solve", Native::limited(2, Some(2), matrix::solve));
+
+    natives.insert("mean", Native::new(1, Some(1), stats::mean).widening());
+    natives.insert("median", Native::new(1, Some(1), stats::median).widening());
+    natives.insert("mode", Native::new(1, Some(1), stats::mode).widening());
+    natives.insert("variance", Native::new(1, Some(1), stats::variance).widening());
+    natives.insert("stddev", Native::new(1, Some(1), stats::stddev).widening());
+    natives.insert("percentile", Native::new(2, Some(2), stats::percentile).widening());
+    natives.insert("corr", Native::new(2, Some(2), stats::corr).widening());
+    natives.insert("linreg", Native::new(2, Some(2), stats::linreg).widening());
+
+    natives.insert("factorial", Native::limited(1, Some(1), stats::factorial));
+    natives.insert("nCr", Native::limited(2, Some(2), stats::ncr));
+    natives.insert("nPr", Native::limited(2, Some(2), stats::npr));
+
+    natives
+}
+
+pub(crate) fn type_err(arg: &EE, expected: &'static str) -> Error {
+    Error::new(
+        format!(
+            "expected {} argument, found {}",
+            expected,
+            arg.value.display_type()
+        ),
+        ErrorType::TypeError,
+        arg.pos,
+    )
+}
+
+/// Read an argument as a float, widening integers and rationals
+pub(crate) fn number(arg: &EE) -> Result<f64, Error> {
+    match &arg.value {
+        Integer(val) => Ok(big_to_f64(val)),
+        Rational(val) => Ok(val.to_f64().unwrap_or(f64::NAN)),
+        Float(val) => Ok(*val),
+        _ => Err(type_err(arg, "a real number")),
+    }
+}
+
+/// The elements of a list argument
+pub(crate) fn list(arg: &EE) -> Result<Vec<EE>, Error> {
+    match &arg.value {
+        List(items) => Ok(items.iter().map(|item| EE::new(item.clone(), arg.pos)).collect()),
+        _ => Err(type_err(arg, "a `list`")),
+    }
+}
+
+/// The arguments, or the elements of a list when that is the only argument
+fn values(args: &[EE]) -> Result<Vec<EE>, Error> {
+    match args {
+        [arg] if matches!(arg.value, List(_)) => list(arg),
+        _ => Ok(args.to_vec()),
+    }
+}
+
+pub(crate) fn empty_err(name: &str, pos: Pos) -> Error {
+    Error::new(
+        format!("cannot take the {} of an empty list", name),
+        ErrorType::RuntimeError,
+        pos,
+    )
+}
+
+fn string(arg: &EE) -> Result<&str, Error> {
+    match &arg.value {
+        Str(val) => Ok(val),
+        _ => Err(type_err(arg, "a `string`")),
+    }
+}
+
+pub(crate) fn integer(arg: &EE) -> Result<&BigInt, Error> {
+    match &arg.value {
+        Integer(val) => Ok(val),
+        _ => Err(type_err(arg, "an `integer`")),
+    }
+}
+
+macro_rules! float_fn {
+    ($($name: ident),*) => {
+        $(
+            fn $name(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+                match &args[0].value {
+                    Complex(val) => Ok(Complex(val.$name())),
+                    _ => Ok(Float(number(&args[0])?.$name())),
+                }
+            }
+        )*
+    };
+}
+
+float_fn!(cbrt, exp, ln, sin, cos, tan, asin, acos, atan);
+
+fn sqrt(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    match &args[0].value {
+        Complex(val) => Ok(Complex(val.sqrt())),
+        // Only whole powers of units survive, so `sqrt(4 m**2)` is `2 m` but `sqrt(4 m)` fails
+        Quantity(val) if val.dim.iter().all(|exp| exp % 2 == 0) => {
+            let mut dim = val.dim;
+            dim.iter_mut().for_each(|exp| *exp /= 2);
+            Ok(Quantity(units::Quantity {
+                value: val.value.sqrt(),
+                dim,
+                unit: None,
+            }))
+        }
+        _ => {
+            let val = number(&args[0])?;
+            if val < 0.0 {
+                Ok(Complex(Complex64::new(0.0, (-val).sqrt())))
+            } else {
+                Ok(Float(val.sqrt()))
+            }
+        }
+    }
+}
+
+fn arg(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    match &args[0].value {
+        Complex(val) => Ok(Float(val.arg())),
+        _ => Ok(Float(0f64.atan2(number(&args[0])?))),
+    }
+}
+
+fn conj(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    match &args[0].value {
+        Complex(val) => Ok(Complex(val.conj())),
+        _ => number(&args[0]).map(|_| args[0].value.clone()),
+    }
+}
+
+fn re(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    match &args[0].value {
+        Complex(val) => Ok(Float(val.re)),
+        _ => Ok(Float(number(&args[0])?)),
+    }
+}
+
+fn im(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    match &args[0].value {
+        Complex(val) => Ok(Float(val.im)),
+        _ => number(&args[0]).map(|_| Float(0.0)),
+    }
+}
+
+fn log(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let val = number(&args[0])?;
+    Ok(Float(match args.get(1) {
+        Some(base) => val.log(number(base)?),
+        None => val.log10(),
+    }))
+}
+
+fn abs(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    match &args[0].value {
+        Integer(val) => Ok(Integer(val.abs())),
+        Rational(val) => Ok(Rational(val.abs())),
+        Float(val) => Ok(Float(val.abs())),
+        Complex(val) => Ok(Float(val.norm())),
+        Quantity(val) => Ok(Quantity(val.with_value(val.value.abs()))),
+        _ => Err(type_err(&args[0], "a number")),
+    }
+}
+
+fn min(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let values = values(args)?;
+    let (mut best, rest) = values.split_first().ok_or_else(|| empty_err("min", pos))?;
+    for arg in rest {
+        // `best` came first, so the operands of an error stay in the order they were written
+        if best.gt(arg)?.value == Bool(true) {
+            best = arg;
+        }
+    }
+    Ok(best.value.clone())
+}
+
+fn max(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let values = values(args)?;
+    let (mut best, rest) = values.split_first().ok_or_else(|| empty_err("max", pos))?;
+    for arg in rest {
+        if best.lt(arg)?.value == Bool(true) {
+            best = arg;
+        }
+    }
+    Ok(best.value.clone())
+}
+
+macro_rules! rounding_fn {
+    ($($name: ident),*) => {
+        $(
+            fn $name(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+                match &args[0].value {
+                    Integer(val) => Ok(Integer(val.clone())),
+                    Rational(val) => Ok(Integer(val.$name().to_integer())),
+                    Float(val) => match BigInt::from_f64(val.$name()) {
+                        Some(val) => Ok(Integer(val)),
+                        None => Err(Error::new(
+                            format!("failed to {} `{}` to an integer", stringify!($name), val),
+                            ErrorType::RuntimeError,
+                            pos,
+                        )),
+                    },
+                    _ => Err(type_err(&args[0], "a number")),
+                }
+            }
+        )*
+    };
+}
+
+rounding_fn!(floor, ceil, round);
+
+fn gcd(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    Ok(Integer(integer(&args[0])?.gcd(integer(&args[1])?)))
+}
+
+fn lcm(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let (left, right) = (integer(&args[0])?, integer(&args[1])?);
+    if left.is_zero() || right.is_zero() {
+        return Ok(Integer(BigInt::zero()));
+    }
+
+    Ok(Integer(left.lcm(right)))
+}
+
+fn len(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    match &args[0].value {
+        Str(val) => Ok(Integer(BigInt::from(val.chars().count()))),
+        List(items) => Ok(Integer(BigInt::from(items.len()))),
+        _ => Err(type_err(&args[0], "a `list` or `string`")),
+    }
+}
+
+fn sum(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let items = list(&args[0])?;
+    let (first, rest) = match items.split_first() {
+        Some(split) => split,
+        None => return Ok(Integer(BigInt::zero())),
+    };
+
+    let mut total = first.clone();
+    for item in rest {
+        total = total.add(item)?;
+    }
+    Ok(total.value)
+}
+
+fn product(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let items = list(&args[0])?;
+    let (first, rest) = match items.split_first() {
+        Some(split) => split,
+        None => return Ok(Integer(BigInt::one())),
+    };
+
+    let mut total = first.clone();
+    for item in rest {
+        total = total.mul(item)?;
+    }
+    Ok(total.value)
+}
+
+fn compare(left: &EE, right: &EE) -> Result<Ordering, Error> {
+    if left.lt(right)?.value == Bool(true) {
+        Ok(Ordering::Less)
+    } else if left.gt(right)?.value == Bool(true) {
+        Ok(Ordering::Greater)
+    } else {
+        Ok(Ordering::Equal)
+    }
+}
+
+/// Order floats with NaN after every number, as `partial_cmp` leaves it unordered
+fn total(left: f64, right: f64) -> Ordering {
+    left.partial_cmp(&right)
+        .unwrap_or_else(|| left.is_nan().cmp(&right.is_nan()))
+}
+
+/// Order two values already known to be comparable, without failing
+fn order(left: &EE, right: &EE) -> Ordering {
+    match (&left.value, &right.value) {
+        (Float(left), Float(right)) => total(*left, *right),
+        (Quantity(left), Quantity(right)) => total(left.value, right.value),
+        _ => compare(left, right).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// Sort values in ascending order, NaN last
+pub(crate) fn sorted(mut items: Vec<EE>) -> Result<Vec<EE>, Error> {
+    // Sorting needs a total order, so refuse values that don't compare, such as a number
+    // against a boolean, before starting
+    if let Some(first) = items.first() {
+        for item in &items[1..] {
+            compare(first, item)?;
+            compare(item, first)?;
+        }
+    }
+
+    items.sort_by(order);
+    Ok(items)
+}
+
+fn sort(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let items = sorted(list(&args[0])?)?;
+    Ok(List(items.into_iter().map(|item| item.value).collect()))
+}
+
+fn upper(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    Ok(Str(string(&args[0])?.to_uppercase()))
+}
+
+fn lower(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    Ok(Str(string(&args[0])?.to_lowercase()))
+}
+
+/// `repeat("ab", 3)` is `"ababab"`
+fn repeat(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let val = string(&args[0])?;
+    let times = match integer(&args[1])?.to_usize() {
+        Some(times) => times,
+        None => {
+            return Err(Error::new(
+                format!("cannot repeat a string {} times", args[1].value),
+                ErrorType::RuntimeError,
+                args[1].pos,
+            ))
+        }
+    };
+
+    // Checked before building it, so `repeat("a", 10**12)` fails without allocating
+    if val.chars().count().saturating_mul(times) > MAX_STRING_LEN {
+        return Err(too_long_err(pos));
+    }
+    Ok(Str(val.repeat(times)))
+}
+
+/// Split on a separator, on whitespace without one, or into characters on `""`
+fn split(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let val = string(&args[0])?;
+    let parts: Vec<String> = match args.get(1).map(string).transpose()? {
+        Some("") => val.chars().map(String::from).collect(),
+        Some(sep) => val.split(sep).map(String::from).collect(),
+        None => val.split_whitespace().map(String::from).collect(),
+    };
+
+    Ok(List(parts.into_iter().map(Str).collect()))
+}
+
+/// Join a list into one string, writing anything but strings as it would be shown
+fn join(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let sep = args.get(1).map(string).transpose()?.unwrap_or("");
+    let parts: Vec<String> = list(&args[0])?
+        .iter()
+        .map(|item| match &item.value {
+            Str(val) => val.clone(),
+            val => val.to_string(),
+        })
+        .collect();
+
+    text(parts.join(sep), pos)
+}
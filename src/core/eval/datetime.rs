@@ -0,0 +1,105 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// `2026-12-25`
+pub(crate) fn parse_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()
+}
+
+/// `2026-12-25T18:30` or `2026-12-25T18:30:15`
+pub(crate) fn parse_datetime(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M"))
+        .ok()
+}
+
+/// Seconds in one of the suffixes of a duration literal
+fn suffix_secs(suffix: char) -> Option<i64> {
+    Some(match suffix {
+        'w' => 604_800,
+        'd' => 86_400,
+        'h' => 3_600,
+        'm' => 60,
+        's' => 1,
+        _ => return None,
+    })
+}
+
+/// `3h30m`, `2d`, `1.5h` or `1w2d`, None if it doesn't fit in a duration
+pub(crate) fn parse_duration(text: &str) -> Option<Duration> {
+    let mut secs = 0.0;
+    let mut digits = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            digits.push(c);
+            continue;
+        }
+
+        let amount: f64 = digits.parse().ok()?;
+        secs += amount * suffix_secs(c)? as f64;
+        digits.clear();
+    }
+
+    from_secs(secs)
+}
+
+/// Whether `suffix` can end a component of a duration literal
+pub(crate) fn is_duration_suffix(suffix: char) -> bool {
+    suffix_secs(suffix).is_some()
+}
+
+/// The duration closest to `secs`, None if it's out of range
+pub(crate) fn from_secs(secs: f64) -> Option<Duration> {
+    let micros = (secs * MICROS_PER_SECOND as f64).round();
+    if !micros.is_finite() || micros.abs() >= i64::MAX as f64 {
+        return None;
+    }
+    Some(Duration::microseconds(micros as i64))
+}
+
+pub(crate) fn to_secs(duration: &Duration) -> f64 {
+    match duration.num_microseconds() {
+        Some(micros) => micros as f64 / MICROS_PER_SECOND as f64,
+        None => duration.num_milliseconds() as f64 / 1000.0,
+    }
+}
+
+pub(crate) fn is_whole_days(duration: &Duration) -> bool {
+    matches!(duration.num_microseconds(), Some(micros) if micros % MICROS_PER_DAY == 0)
+}
+
+pub(crate) fn fmt_datetime(datetime: &NaiveDateTime) -> String {
+    datetime.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+/// Format as `2d 3h 30m 12.5s`, skipping parts that are zero
+pub(crate) fn fmt_duration(duration: &Duration) -> String {
+    let micros = match duration.num_microseconds() {
+        Some(micros) => micros,
+        None => return format!("{}d", duration.num_days()),
+    };
+
+    let sign = if micros < 0 { "-" } else { "" };
+    let micros = micros.unsigned_abs();
+    let second = MICROS_PER_SECOND as u64;
+
+    let mut parts = Vec::new();
+    for (amount, suffix) in &[
+        (micros / MICROS_PER_DAY as u64, "d"),
+        (micros / (3600 * second) % 24, "h"),
+        (micros / (60 * second) % 60, "m"),
+    ] {
+        if *amount > 0 {
+            parts.push(format!("{}{}", amount, suffix));
+        }
+    }
+
+    let secs = micros % (60 * second);
+    if secs > 0 || parts.is_empty() {
+        parts.push(format!("{}s", secs as f64 / second as f64));
+    }
+
+    format!("{}{}", sign, parts.join(" "))
+}
@@ -0,0 +1,384 @@
+use num::complex::Complex64;
+use num::traits::ToPrimitive;
+
+use crate::core::eval::ast::{self, ExpressionKind};
+use crate::core::eval::error::*;
+use crate::core::eval::exec::{rational, ExecutionExpr, ExecutionExpr::*};
+use crate::core::eval::symbolic::{self, add, apply, div, mul, neg, num, pow, sub, Expr};
+
+/// Highest power expanded when looking for a polynomial, `(x + 1)**20` is left alone
+const MAX_DEGREE: usize = 16;
+
+/// Roots are searched for in `-SEARCH_RANGE..=SEARCH_RANGE`
+const SEARCH_RANGE: f64 = 100.0;
+
+/// Points sampled across the search range, looking for sign changes
+const SAMPLES: usize = 20_000;
+
+/// How close successive guesses must get before a root is accepted
+const TOLERANCE: f64 = 1e-12;
+
+/// Newton and bisection steps before giving up on a bracket
+const MAX_ITERATIONS: usize = 100;
+
+/// Largest `|f(x)|` at an accepted root, so poles such as `tan(pi/2)` are left out
+const MAX_RESIDUAL: f64 = 1e-6;
+
+/// Most roots returned, nearest to zero first
+const MAX_ROOTS: usize = 10;
+
+/// `solve(u = v, x)`, as opposed to `solve` on a matrix
+pub(crate) fn is_solve(call: &ast::Call<'_>) -> bool {
+    call.func.expr == ExpressionKind::Ident("solve")
+        && matches!(
+            call.args.first(),
+            Some(ast::Expression { expr: ExpressionKind::InfixOp(infix), .. })
+                if infix.op == ast::Operator::Equation
+        )
+}
+
+/// Solve an equation for a variable, giving the list of its solutions and a note when the list
+/// may be missing some of them
+pub(crate) fn solve(
+    call: &ast::Call<'_>,
+    pos: Pos,
+    resolve: &symbolic::Resolve<'_>,
+    budget: &mut symbolic::Budget,
+) -> Result<(ExecutionExpr, Option<String>), Error> {
+    if call.args.len() != 2 {
+        return Err(symbolic::arity_err("solve", 2, call.args.len(), pos));
+    }
+
+    let var = match call.args[1].expr {
+        ExpressionKind::Ident(var) => var,
+        _ => {
+            return Err(Error::new(
+                format!("expected a variable to solve for, found {}", call.args[1].expr),
+                ErrorType::TypeError,
+                call.args[1].pos,
+            ))
+        }
+    };
+
+    let (left, right) = match &call.args[0].expr {
+        ExpressionKind::InfixOp(infix) if infix.op == ast::Operator::Equation => (&infix.left, &infix.right),
+        _ => {
+            return Err(Error::new(
+                format!("expected an equation, found {}", call.args[0].expr),
+                ErrorType::TypeError,
+                call.args[0].pos,
+            ))
+        }
+    };
+
+    // Solve `left - right = 0`
+    let expr = sub(
+        symbolic::from_ast(left, resolve, &[var], budget)?,
+        symbolic::from_ast(right, resolve, &[var], budget)?,
+    );
+
+    // Whether the roots were found numerically, and whether all of those found are kept
+    let (roots, numerically, every) = match polynomial(&expr, var) {
+        Some(mut coefficients) => {
+            while coefficients.len() > 1 && coefficients[coefficients.len() - 1].is(0) {
+                coefficients.pop();
+            }
+
+            match coefficients.len() {
+                1 => (constant(&coefficients[0], var, call.args[0].pos)?, false, true),
+                2 => (vec![to_value(neg(div(coefficients[0].clone(), coefficients[1].clone())))], false, true),
+                3 => (quadratic(&coefficients[0], &coefficients[1], &coefficients[2]), false, true),
+                _ => {
+                    let (roots, every) = numeric(&expr, var, call.args[0].pos, budget)?;
+                    (roots, true, every)
+                }
+            }
+        }
+        None => {
+            let (roots, every) = numeric(&expr, var, call.args[0].pos, budget)?;
+            (roots, true, every)
+        }
+    };
+
+    let note = match (numerically, every) {
+        (false, _) => None,
+        (true, false) => Some(format!(
+            "solved numerically, these are the {} roots nearest 0 in {}..{}",
+            MAX_ROOTS, -SEARCH_RANGE, SEARCH_RANGE
+        )),
+        (true, true) => Some(format!(
+            "solved numerically, only looking for `{}` in {}..{}",
+            var, -SEARCH_RANGE, SEARCH_RANGE
+        )),
+    };
+    Ok((List(roots), note))
+}
+
+/// Coefficients of `expr` as a polynomial in `var`, lowest power first
+fn polynomial(expr: &Expr, var: &str) -> Option<Vec<Expr>> {
+    if !expr.depends_on(var) {
+        return Some(vec![expr.clone()]);
+    }
+
+    match expr {
+        Expr::Symbol(_) => Some(vec![num(0), num(1)]),
+        Expr::Neg(val) => Some(polynomial(val, var)?.into_iter().map(neg).collect()),
+        Expr::Add(left, right) => Some(poly_add(polynomial(left, var)?, polynomial(right, var)?)),
+        Expr::Sub(left, right) => Some(poly_add(
+            polynomial(left, var)?,
+            polynomial(right, var)?.into_iter().map(neg).collect(),
+        )),
+        Expr::Mul(left, right) => poly_mul(&polynomial(left, var)?, &polynomial(right, var)?),
+        Expr::Div(left, right) if !right.depends_on(var) => Some(
+            polynomial(left, var)?
+                .into_iter()
+                .map(|coefficient| div(coefficient, (**right).clone()))
+                .collect(),
+        ),
+        Expr::Pow(base, exp) if !exp.depends_on(var) => {
+            let exp = match &**exp {
+                Expr::Number(val) if val.is_integer() => val.to_integer().to_usize()?,
+                _ => return None,
+            };
+            if exp > MAX_DEGREE {
+                return None;
+            }
+
+            let base = polynomial(base, var)?;
+            let mut result = vec![num(1)];
+            for _ in 0..exp {
+                result = poly_mul(&result, &base)?;
+            }
+            Some(result)
+        }
+        _ => None,
+    }
+}
+
+fn poly_add(mut left: Vec<Expr>, right: Vec<Expr>) -> Vec<Expr> {
+    if left.len() < right.len() {
+        left.resize(right.len(), num(0));
+    }
+    for (i, coefficient) in right.into_iter().enumerate() {
+        left[i] = add(left[i].clone(), coefficient);
+    }
+    left
+}
+
+fn poly_mul(left: &[Expr], right: &[Expr]) -> Option<Vec<Expr>> {
+    let degree = left.len() + right.len() - 2;
+    if degree > MAX_DEGREE {
+        return None;
+    }
+
+    let mut result = vec![num(0); degree + 1];
+    for (i, a) in left.iter().enumerate() {
+        for (j, b) in right.iter().enumerate() {
+            result[i + j] = add(result[i + j].clone(), mul(a.clone(), b.clone()));
+        }
+    }
+    Some(result)
+}
+
+fn to_value(expr: Expr) -> ExecutionExpr {
+    match expr {
+        Expr::Number(val) => rational(val),
+        Expr::Float(val) => Float(val),
+        expr => Symbolic(expr),
+    }
+}
+
+/// An equation without the variable in it is always or never true
+fn constant(value: &Expr, var: &str, pos: Pos) -> Result<Vec<ExecutionExpr>, Error> {
+    if value.is(0) {
+        return Err(Error::new(
+            format!("every value of `{}` solves the equation", var),
+            ErrorType::RuntimeError,
+            pos,
+        ));
+    }
+
+    if value.is_number() {
+        Ok(Vec::new())
+    } else {
+        Err(Error::new(
+            format!("`{}` does not appear in the equation", var),
+            ErrorType::RuntimeError,
+            pos,
+        ))
+    }
+}
+
+/// `c + b*x + a*x**2 = 0`, complex roots included
+fn quadratic(c: &Expr, b: &Expr, a: &Expr) -> Vec<ExecutionExpr> {
+    let discriminant = sub(pow(b.clone(), num(2)), mul(num(4), mul(a.clone(), c.clone())));
+    let vertex = neg(div(b.clone(), mul(num(2), a.clone())));
+
+    if discriminant.is(0) {
+        return vec![to_value(vertex)];
+    }
+
+    // Two complex roots when every coefficient is known
+    if discriminant.is_negative() && a.is_number() && b.is_number() {
+        let re = vertex.to_f64();
+        let im = ((-discriminant.to_f64()).sqrt() / (2.0 * a.to_f64())).abs();
+        return vec![
+            Complex(Complex64::new(re, -im)),
+            Complex(Complex64::new(re, im)),
+        ];
+    }
+
+    let offset = div(apply("sqrt", vec![discriminant]), mul(num(2), a.clone()));
+    let mut roots = vec![
+        sub(vertex.clone(), offset.clone()),
+        add(vertex, offset),
+    ];
+    if roots.iter().all(Expr::is_number) && roots[0].to_f64() > roots[1].to_f64() {
+        roots.swap(0, 1);
+    }
+
+    roots.into_iter().map(to_value).collect()
+}
+
+/// A variable other than `var`, which root finding can't put a number to
+fn free_variable<'e>(expr: &'e Expr, var: &str) -> Option<&'e str> {
+    match expr {
+        Expr::Number(_) | Expr::Float(_) => None,
+        Expr::Symbol(name) if name == var => None,
+        Expr::Symbol(name) => Some(name),
+        Expr::Neg(val) => free_variable(val, var),
+        Expr::Add(left, right)
+        | Expr::Sub(left, right)
+        | Expr::Mul(left, right)
+        | Expr::Div(left, right)
+        | Expr::Pow(left, right) => free_variable(left, var).or_else(|| free_variable(right, var)),
+        Expr::Call(_, args) => args.iter().find_map(|arg| free_variable(arg, var)),
+    }
+}
+
+/// Find real roots by scanning for sign changes, then closing in on each, and whether all of
+/// those found in the search range are kept
+fn numeric(
+    expr: &Expr,
+    var: &str,
+    pos: Pos,
+    budget: &mut symbolic::Budget,
+) -> Result<(Vec<ExecutionExpr>, bool), Error> {
+    if let Some(name) = free_variable(expr, var) {
+        return Err(Error::new(
+            format!("cannot solve for `{}` numerically while `{}` has no value", var, name),
+            ErrorType::RuntimeError,
+            pos,
+        ));
+    }
+
+    let f = |x: f64| expr.value_at(var, x);
+    // Without a derivative, such as for `abs`, every step bisects
+    let slope = match symbolic::derivative(expr, var, pos, budget) {
+        Err(why) if why.error_type() == ErrorType::LimitError => return Err(why),
+        slope => slope.ok(),
+    };
+    let df = |x: f64| slope.as_ref().map_or(f64::NAN, |slope| slope.value_at(var, x));
+    // Each sample evaluates the expression, and each Newton step its derivative as well
+    let cost = expr.size();
+    let newton_cost = cost + slope.as_ref().map_or(0, Expr::size);
+
+    let step = 2.0 * SEARCH_RANGE / SAMPLES as f64;
+    let mut roots: Vec<f64> = Vec::new();
+    let mut defined = false;
+    let (mut prev_x, mut prev_y) = (-SEARCH_RANGE, f(-SEARCH_RANGE));
+    for i in 0..=SAMPLES {
+        budget.charge(cost, pos)?;
+        let x = -SEARCH_RANGE + i as f64 * step;
+        let y = if i == 0 { prev_y } else { f(x) };
+        defined |= y.is_finite();
+
+        let root = if y == 0.0 {
+            Some(x)
+        } else if prev_y.is_finite() && y.is_finite() && prev_y != 0.0 && (prev_y < 0.0) != (y < 0.0) {
+            refine(&f, &df, prev_x, x, &mut || budget.charge(newton_cost, pos))?
+        } else {
+            None
+        };
+
+        if let Some(root) = root {
+            let repeated = match roots.last() {
+                Some(last) => (root - last).abs() <= step / 2.0,
+                None => false,
+            };
+            if !repeated {
+                roots.push(root);
+            }
+        }
+
+        prev_x = x;
+        prev_y = y;
+    }
+
+    if !defined {
+        return Err(Error::new(
+            format!("cannot evaluate the equation for any `{}` in {}..{}", var, -SEARCH_RANGE, SEARCH_RANGE),
+            ErrorType::RuntimeError,
+            pos,
+        ));
+    }
+
+    let every = roots.len() <= MAX_ROOTS;
+    if !every {
+        roots.sort_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap_or(std::cmp::Ordering::Equal));
+        roots.truncate(MAX_ROOTS);
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    Ok((roots.into_iter().map(Float).collect(), every))
+}
+
+/// Newton's method kept inside a bracket `lo..hi` where `f` changes sign,
+/// bisecting whenever a Newton step would leave it, and paying for each step with `charge`
+fn refine<F, D, C>(f: &F, df: &D, lo: f64, hi: f64, charge: &mut C) -> Result<Option<f64>, Error>
+where
+    F: Fn(f64) -> f64,
+    D: Fn(f64) -> f64,
+    C: FnMut() -> Result<(), Error>,
+{
+    // Keep `f(below) < 0 < f(above)`
+    let (mut below, mut above) = if f(lo) < 0.0 { (lo, hi) } else { (hi, lo) };
+    let mut x = (lo + hi) / 2.0;
+
+    for _ in 0..MAX_ITERATIONS {
+        charge()?;
+        let y = f(x);
+        if y == 0.0 {
+            break;
+        }
+        if y < 0.0 {
+            below = x;
+        } else {
+            above = x;
+        }
+
+        let newton = x - y / df(x);
+        let next = if newton.is_finite() && newton >= below.min(above) && newton <= below.max(above) {
+            newton
+        } else {
+            (below + above) / 2.0
+        };
+
+        let done = (next - x).abs() <= TOLERANCE * (1.0 + x.abs());
+        x = next;
+        if done {
+            break;
+        }
+    }
+
+    // Land exactly on whole numbers the search only got near
+    if f(x.round()).abs() <= f(x).abs() {
+        x = x.round();
+    }
+
+    if f(x).abs() <= MAX_RESIDUAL {
+        Ok(Some(x))
+    } else {
+        Ok(None)
+    }
+}
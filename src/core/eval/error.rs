@@ -12,11 +12,21 @@ impl Pos {
     }
 }
 
+/// A span pointed out alongside the one the error is about, such as an operand
+#[derive(Debug, PartialEq)]
+pub(crate) struct Label {
+    pub(crate) position: Pos,
+    pub(crate) message: String,
+}
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct Error {
     message: String,
     position: Pos,
     error_type: ErrorType,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+    help: Option<String>,
 }
 
 impl Error {
@@ -24,17 +34,195 @@ impl Error {
         Error {
             message,
             error_type,
-            position
+            position,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub(crate) fn position(&self) -> Pos {
+        self.position
+    }
+
+    pub(crate) fn error_type(&self) -> ErrorType {
+        self.error_type
+    }
+
+    /// Point out another span, such as the operands of a mismatched operator
+    pub(crate) fn with_label(mut self, position: Pos, message: String) -> Self {
+        self.labels.push(Label { position, message });
+        self
+    }
+
+    pub(crate) fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    /// Move every span `by` characters along, for an error in a part of a longer input
+    pub(crate) fn shifted(mut self, by: usize) -> Self {
+        let shift = |pos: Pos| Pos::new(pos.start + by, pos.end + by);
+        self.position = shift(self.position);
+        for label in &mut self.labels {
+            label.position = shift(label.position);
         }
+        self
+    }
+
+    /// Suggest a fix, replacing any suggestion made before
+    pub(crate) fn with_help(mut self, help: String) -> Self {
+        self.help = Some(help);
+        self
+    }
+
+    /// Show the error under the line of `src` it points at, the way rustc does:
+    ///
+    /// ```text
+    /// TypeError: cannot apply operator `add` on types `float` and `integer`
+    ///   |
+    /// 1 | 10.1 + 1
+    ///   | ----^^^- `integer`
+    ///   | |
+    ///   | `float`
+    ///   = help: use `1.0` instead of `1` to add to a float
+    /// ```
+    pub(crate) fn render(&self, src: &str) -> String {
+        let primary = Span::locate(src, self.position);
+        let labels: Vec<(Span, &str)> = self
+            .labels
+            .iter()
+            .map(|label| (Span::locate(src, label.position), label.message.as_str()))
+            .collect();
+
+        let mut lines: Vec<usize> = labels.iter().map(|(span, _)| span.line).collect();
+        lines.push(primary.line);
+        lines.sort_unstable();
+        lines.dedup();
+
+        let gutter = (lines[lines.len() - 1] + 1).to_string().len();
+        let pad = " ".repeat(gutter);
+        let mut out = format!("{}: {}\n{} |\n", self.error_type, self.message, pad);
+
+        for line in lines {
+            let text = src.split('\n').nth(line).unwrap_or_default();
+            out += &format!("{:>width$} | {}\n", line + 1, text, width = gutter);
+
+            let mut labels: Vec<&(Span, &str)> = labels.iter().filter(|(span, _)| span.line == line).collect();
+            labels.sort_by_key(|(span, _)| span.start);
+
+            // Operands are underlined with `-`, the rest of the span with `^`
+            let width = labels.iter().map(|(span, _)| span.end).chain(Some(primary.end)).max().unwrap_or(0);
+            let mut markers = vec![' '; width];
+            for (span, _) in &labels {
+                markers[span.start..span.end].iter_mut().for_each(|marker| *marker = '-');
+            }
+            if primary.line == line {
+                let mut free = markers[primary.start..primary.end].iter_mut().filter(|marker| **marker == ' ').peekable();
+                if free.peek().is_some() {
+                    free.for_each(|marker| *marker = '^');
+                } else {
+                    markers[primary.start..primary.end].iter_mut().for_each(|marker| *marker = '^');
+                }
+            }
+
+            let mut underline: String = markers.iter().collect();
+            let mut messages: Vec<&(Span, &str)> = labels.into_iter().filter(|(_, message)| !message.is_empty()).collect();
+
+            // The last message fits after the underline when nothing is marked past its span
+            if let Some((span, message)) = messages.last() {
+                if span.end == width {
+                    underline = format!("{} {}", underline, message);
+                    messages.pop();
+                }
+            }
+            out += &format!("{} | {}\n", pad, underline.trim_end());
+
+            // The others hang below their spans, rightmost first
+            while let Some((span, message)) = messages.pop() {
+                let mut row = vec![' '; span.start];
+                for (other, _) in &messages {
+                    row[other.start] = '|';
+                }
+                let row: String = row.into_iter().collect();
+                out += &format!("{} | {}|\n", pad, row);
+                out += &format!("{} | {}{}\n", pad, row, message);
+            }
+        }
+
+        for note in &self.notes {
+            out += &format!("{} = note: {}\n", pad, note);
+        }
+        if let Some(help) = &self.help {
+            out += &format!("{} = help: {}\n", pad, help);
+        }
+
+        out.trim_end().to_string()
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Most of the text taken up by `render_all`, leaving room in a Discord embed field
+const MAX_RENDERED: usize = 900;
+
+/// Render each error in turn, summarising those that don't fit
+pub(crate) fn render_all(errors: &[Error], src: &str) -> String {
+    let mut out = String::new();
+    for (i, why) in errors.iter().enumerate() {
+        let rendered = why.render(src);
+        if i > 0 && out.len() + rendered.len() > MAX_RENDERED {
+            let more = errors.len() - i;
+            out += &format!("\n\n... and {} more error{}", more, if more == 1 { "" } else { "s" });
+            break;
+        }
+
+        if i > 0 {
+            out += "\n\n";
+        }
+        out += &rendered;
+    }
+    out
+}
+
+/// Where a `Pos` falls on screen, in characters from the start of its line
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    line: usize,
+    start: usize,
+    end: usize, // Past the last character, at least one past `start`
+}
+
+impl Span {
+    fn locate(src: &str, position: Pos) -> Self {
+        let boundary = |mut offset: usize| {
+            offset = offset.min(src.len());
+            while !src.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            offset
+        };
+        let (start, end) = (boundary(position.start), boundary(position.end.max(position.start)));
+
+        let line_start = src[..start].rfind('\n').map_or(0, |at| at + 1);
+        // A span running onto the next lines is cut off at the end of its first
+        let line_end = src[start..].find('\n').map_or(src.len(), |at| start + at);
+
+        let column = src[line_start..start].chars().count();
+        let width = src[start..end.min(line_end)].chars().count().max(1);
+        Span {
+            line: src[..start].matches('\n').count(),
+            start: column,
+            end: column + width,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum ErrorType {
     SyntaxError,
     LexError,
     TypeError,
     RuntimeError,
+    LimitError, // Ran out of depth, steps, time or room for the result
 }
 
 impl fmt::Display for ErrorType {
@@ -46,7 +234,8 @@ impl fmt::Display for ErrorType {
                 ErrorType::SyntaxError => "SyntaxError",
                 ErrorType::LexError => "LexError",
                 ErrorType::TypeError => "TypeError",
-                ErrorType::RuntimeError => "RuntimeError"
+                ErrorType::RuntimeError => "RuntimeError",
+                ErrorType::LimitError => "LimitError"
             }
         )
     }
@@ -1,50 +1,214 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::time::Instant;
 
+use crate::core::eval::builtins::{self, Native};
+use crate::core::eval::datetime;
+use crate::core::eval::equation;
 use crate::core::eval::error::*;
+use crate::core::eval::lexer;
+use crate::core::eval::matrix;
+use crate::core::eval::format;
+use crate::core::eval::options::{self, Format, Mode, Options};
+use crate::core::eval::symbolic;
+use crate::core::eval::trace::{self, Reduction};
+use crate::core::eval::units;
 use crate::core::eval::{ast, ast::ExpressionKind};
 
-use std::convert::TryFrom;
+use std::rc::Rc;
 
 use std::ops::{Add, Div, Mul, Sub};
 
+use num::bigint::{BigInt, BigUint};
+use num::complex::Complex64;
+use num::rational::BigRational;
+use num::traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
 use ExecutionExpr::*;
 
-#[derive(Clone, Copy, PartialEq)]
-enum ExecutionExpr {
-    Integer(i128),
+/// Largest integer result allowed, so a result can't flood an embed
+pub(crate) const DEFAULT_MAX_DIGITS: usize = 1000;
+
+/// Expressions evaluated before giving up, so `f(x) = f(x - 1) + f(x - 2)` can't run forever
+pub(crate) const DEFAULT_MAX_STEPS: usize = 1_000_000;
+
+/// Deepest nesting of expressions, parsed or evaluated and so of function calls too, well
+/// within `options::STACK_SIZE`
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 500;
+
+/// Longest result written out, an embed field holds 1024 characters less the code block around a matrix
+pub(crate) const DEFAULT_MAX_OUTPUT: usize = 1016;
+
+/// Steps between looks at the clock, a power of two
+const CLOCK_STEPS: usize = 1024;
+
+/// Upper bound on the bits of an integer with `max_digits` decimal digits
+pub(crate) fn max_bits(max_digits: usize) -> u64 {
+    (max_digits as f64 * std::f64::consts::LOG2_10).ceil() as u64
+}
+
+pub(crate) fn too_big_err(max_digits: usize, pos: Pos) -> Error {
+    Error::new(
+        format!("result is larger than the limit of {} digits", max_digits),
+        ErrorType::LimitError,
+        pos,
+    )
+}
+
+pub(crate) fn too_many_steps_err(max_steps: usize, pos: Pos) -> Error {
+    Error::new(
+        format!("evaluation took more than the limit of {} steps", max_steps),
+        ErrorType::LimitError,
+        pos,
+    )
+}
+
+pub(crate) fn out_of_time_err(pos: Pos) -> Error {
+    Error::new("evaluation ran out of time".to_string(), ErrorType::LimitError, pos)
+}
+
+/// Longest string a result can hold, in characters
+pub(crate) const MAX_STRING_LEN: usize = 10_000;
+
+pub(crate) fn too_long_err(pos: Pos) -> Error {
+    Error::new(
+        format!("string is longer than the limit of {} characters", MAX_STRING_LEN),
+        ErrorType::LimitError,
+        pos,
+    )
+}
+
+/// Build a string, refusing one longer than `MAX_STRING_LEN`
+pub(crate) fn text(val: String, pos: Pos) -> Result<ExecutionExpr, Error> {
+    if val.chars().count() > MAX_STRING_LEN {
+        return Err(too_long_err(pos));
+    }
+
+    Ok(Str(val))
+}
+
+/// Build a rational, collapsing whole numbers back to an integer
+pub(crate) fn rational(val: BigRational) -> ExecutionExpr {
+    if val.is_integer() {
+        Integer(val.to_integer())
+    } else {
+        Rational(val)
+    }
+}
+
+/// Convert to the nearest float, saturating to infinity
+pub(crate) fn big_to_f64(val: &BigInt) -> f64 {
+    val.to_f64().unwrap_or_else(|| {
+        if val.is_negative() {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        }
+    })
+}
+
+/// Build a quantity, collapsing dimensionless ones back to a float
+pub(crate) fn quantity(val: units::Quantity) -> ExecutionExpr {
+    if val.dim == units::DIMENSIONLESS {
+        Float(val.value)
+    } else {
+        Quantity(val)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub(crate) enum ExecutionExpr {
+    Integer(BigInt),
+    Rational(BigRational),
     Float(f64),
+    Complex(Complex64),
+    Quantity(units::Quantity),
+    Date(chrono::NaiveDate),
+    DateTime(chrono::NaiveDateTime), // In UTC
+    Duration(chrono::Duration),
     Bool(bool),
+    Str(String),
+    List(Vec<ExecutionExpr>),
+    Function(usize), // Index into `Executer::functions`
+    Symbolic(symbolic::Expr), // Result of `diff` or `simplify`
 }
 
 impl ExecutionExpr {
-    fn display_type(&self) -> &'static str {
+    /// Widen any number to a complex number
+    pub(crate) fn to_complex(&self) -> Option<Complex64> {
+        match self {
+            Integer(val) => Some(Complex64::new(big_to_f64(val), 0.0)),
+            Rational(val) => Some(Complex64::new(val.to_f64()?, 0.0)),
+            Float(val) => Some(Complex64::new(*val, 0.0)),
+            Complex(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    /// Treat a real number as a quantity without dimension
+    pub(crate) fn to_quantity(&self) -> Option<units::Quantity> {
+        match self {
+            Integer(val) => Some(units::Quantity::scalar(big_to_f64(val))),
+            Rational(val) => Some(units::Quantity::scalar(val.to_f64()?)),
+            Float(val) => Some(units::Quantity::scalar(*val)),
+            Quantity(val) => Some(val.clone()),
+            Duration(val) => Some(units::Quantity {
+                value: datetime::to_secs(val),
+                dim: units::TIME,
+                unit: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Read a duration, or a quantity of time such as `3 days`
+    pub(crate) fn to_duration(&self) -> Option<chrono::Duration> {
+        match self {
+            Duration(val) => Some(*val),
+            Quantity(val) if val.dim == units::TIME => datetime::from_secs(val.value),
+            _ => None,
+        }
+    }
+
+    /// Read a date as midnight of that day
+    fn to_datetime(&self) -> Option<chrono::NaiveDateTime> {
+        match self {
+            Date(val) => val.and_hms_opt(0, 0, 0),
+            DateTime(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn display_type(&self) -> &'static str {
         match self {
             ExecutionExpr::Integer(_) => "`integer`",
+            ExecutionExpr::Rational(_) => "`rational`",
             ExecutionExpr::Float(_) => "`float`",
+            ExecutionExpr::Complex(_) => "`complex`",
+            ExecutionExpr::Quantity(_) => "`quantity`",
+            ExecutionExpr::Date(_) => "`date`",
+            ExecutionExpr::DateTime(_) => "`datetime`",
+            ExecutionExpr::Duration(_) => "`duration`",
             ExecutionExpr::Bool(_) => "`boolean`",
+            ExecutionExpr::Str(_) => "`string`",
+            ExecutionExpr::List(_) => "`list`",
+            ExecutionExpr::Function(_) => "`function`",
+            ExecutionExpr::Symbolic(_) => "`expression`",
         }
     }
 }
 
 impl fmt::Display for ExecutionExpr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                ExecutionExpr::Integer(val) => val.to_string(),
-                ExecutionExpr::Float(val) => val.to_string(),
-                ExecutionExpr::Bool(val) => val.to_string(),
-            }
-        )
+        write!(f, "{}", format::show(self, &Format::default()))
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct EE {
-    value: ExecutionExpr,
-    pos: Pos,
+    pub(crate) value: ExecutionExpr,
+    pub(crate) pos: Pos,
 }
 
 macro_rules! from_expr {
@@ -57,7 +221,7 @@ macro_rules! from_expr {
 }
 
 impl EE {
-    fn new(expr: ExecutionExpr, pos: Pos) -> Self {
+    pub(crate) fn new(expr: ExecutionExpr, pos: Pos) -> Self {
         EE { value: expr, pos }
     }
 
@@ -76,162 +240,427 @@ impl EE {
             ErrorType::TypeError,
             self.calc_pos(other),
         )
+        .with_label(self.pos, self.value.display_type().to_string())
+        .with_label(other.pos, other.value.display_type().to_string())
+    }
+
+    /// Apply `op` to every element of a list
+    fn each<F>(&self, items: &[ExecutionExpr], op: F) -> Result<ExecutionExpr, Error>
+    where
+        F: Fn(&Self) -> Result<Self, Error>,
+    {
+        items
+            .iter()
+            .map(|item| op(&EE::new(item.clone(), self.pos)).map(|item| item.value))
+            .collect::<Result<_, _>>()
+            .map(List)
+    }
+
+    /// Check two quantities measure the same kind of thing
+    fn check_dims(
+        &self,
+        other: &Self,
+        left: &units::Quantity,
+        right: &units::Quantity,
+        operation: &'static str,
+    ) -> Result<(), Error> {
+        if left.dim == right.dim {
+            return Ok(());
+        }
+
+        Err(Error::new(
+            format!(
+                "cannot {} quantities of mismatched dimensions `{}` and `{}`",
+                operation,
+                units::dim_name(&left.dim),
+                units::dim_name(&right.dim)
+            ),
+            ErrorType::TypeError,
+            self.calc_pos(other),
+        ))
     }
 
-    fn add(&self, other: &Self) -> Result<Self, Error> {
+    /// Where a type sits in `boolean < integer < rational < float`
+    fn rank(&self) -> Option<u8> {
+        match self.value {
+            Bool(_) => Some(0),
+            Integer(_) => Some(1),
+            Rational(_) => Some(2),
+            Float(_) => Some(3),
+            _ => None,
+        }
+    }
+
+    fn widen_to(self, rank: u8) -> Self {
+        let value = match (&self.value, rank) {
+            (Bool(val), 1) | (Bool(val), 2) => Integer(BigInt::from(*val as u8)),
+            (Bool(val), 3) => Float(*val as u8 as f64),
+            (Integer(val), 3) => Float(big_to_f64(val)),
+            (Rational(val), 3) => Float(val.to_f64().unwrap_or(f64::NAN)),
+            _ => return self,
+        };
+
+        EE::new(value, self.pos)
+    }
+
+    /// Lenient mode: widen the narrower operand to the type of the wider one
+    fn widen(self, other: Self) -> (Self, Self) {
+        match (self.rank(), other.rank()) {
+            (Some(left), Some(right)) if left != right => {
+                let rank = left.max(right);
+                (self.widen_to(rank), other.widen_to(rank))
+            }
+            _ => (self, other),
+        }
+    }
+
+    /// Lenient mode: widen every value, and every element of a list, to the widest type of them
+    pub(crate) fn widen_all(values: &[EE]) -> Vec<EE> {
+        let items = |value: &EE| match &value.value {
+            List(items) => items.iter().map(|item| EE::new(item.clone(), value.pos)).collect(),
+            _ => vec![value.clone()],
+        };
+        let rank = match values.iter().flat_map(items).filter_map(|item| item.rank()).max() {
+            Some(rank) => rank,
+            None => return values.to_vec(),
+        };
+
+        let widen = |value: &EE| match &value.value {
+            List(items) => EE::new(
+                List(
+                    items
+                        .iter()
+                        .map(|item| EE::new(item.clone(), value.pos).widen_to(rank).value)
+                        .collect(),
+                ),
+                value.pos,
+            ),
+            _ => value.clone().widen_to(rank),
+        };
+        values.iter().map(widen).collect()
+    }
+
+    /// Promote an integer/rational pair to two rationals
+    fn rationals(&self, other: &Self) -> Option<(BigRational, BigRational)> {
+        let promote = |value: &ExecutionExpr| match value {
+            Integer(val) => Some(BigRational::from_integer(val.clone())),
+            Rational(val) => Some(val.clone()),
+            _ => None,
+        };
+
+        Some((promote(&self.value)?, promote(&other.value)?))
+    }
+
+    /// Promote a number pair to two complex numbers
+    fn complexes(&self, other: &Self) -> Option<(Complex64, Complex64)> {
+        Some((self.value.to_complex()?, other.value.to_complex()?))
+    }
+
+    pub(crate) fn add(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(match left.checked_add(*right) {
-                    Some(val) => val,
-                    None => {
-                        return Err(Error::new(
-                            format!("failed to add `{}` and `{}`: value overflowed", left, right),
-                            ErrorType::RuntimeError,
-                            self.calc_pos(other),
-                        ));
-                    }
-                }),
+                (Integer(left), Integer(right)) => Integer(left + right),
                 (Float(left), Float(right)) => Float(left.add(*right)),
-                _ => return Err(self.gen_type_err(other, "add")),
+                (Str(left), Str(right)) => text(format!("{}{}", left, right), self.calc_pos(other))?,
+                (Quantity(left), Quantity(right)) => {
+                    self.check_dims(other, left, right, "add")?;
+                    Quantity(left.with_value(left.value + right.value))
+                }
+                (Date(_), _) | (DateTime(_), _) | (Duration(_), _) | (_, Date(_)) | (_, DateTime(_))
+                | (_, Duration(_)) => self.time_arith(other, false)?,
+                (Complex(_), _) | (_, Complex(_)) => match self.complexes(other) {
+                    Some((left, right)) => Complex(left + right),
+                    None => return Err(self.gen_type_err(other, "add")),
+                },
+                _ => match self.rationals(other) {
+                    Some((left, right)) => rational(left + right),
+                    None => return Err(self.gen_type_err(other, "add")),
+                },
             },
             self.calc_pos(other)
         )
     }
 
-    fn sub(&self, other: &Self) -> Result<Self, Error> {
+    pub(crate) fn sub(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(match left.checked_sub(*right) {
-                    Some(val) => val,
-                    None => {
-                        return Err(Error::new(
-                            format!(
-                                "failed to subtract `{}` from `{}`: value overflowed",
-                                right, left
-                            ),
-                            ErrorType::RuntimeError,
-                            self.calc_pos(other),
-                        ));
-                    }
-                }),
+                (Integer(left), Integer(right)) => Integer(left - right),
                 (Float(left), Float(right)) => Float(left.sub(*right)),
-                _ => return Err(self.gen_type_err(other, "subtract")),
+                (Quantity(left), Quantity(right)) => {
+                    self.check_dims(other, left, right, "subtract")?;
+                    Quantity(left.with_value(left.value - right.value))
+                }
+                (Date(_), _) | (DateTime(_), _) | (Duration(_), _) | (_, Duration(_)) => {
+                    self.time_arith(other, true)?
+                }
+                (Complex(_), _) | (_, Complex(_)) => match self.complexes(other) {
+                    Some((left, right)) => Complex(left - right),
+                    None => return Err(self.gen_type_err(other, "subtract")),
+                },
+                _ => match self.rationals(other) {
+                    Some((left, right)) => rational(left - right),
+                    None => return Err(self.gen_type_err(other, "subtract")),
+                },
             },
             self.calc_pos(other)
         )
     }
 
-    fn mul(&self, other: &Self) -> Result<Self, Error> {
+    /// `+` or `-` with a date, time or duration on either side
+    fn time_arith(&self, other: &Self, subtract: bool) -> Result<ExecutionExpr, Error> {
+        let operation = if subtract { "subtract" } else { "add" };
+        let out_of_range = || {
+            Error::new(
+                format!("failed to {} `{}` and `{}`: out of range", operation, self.value, other.value),
+                ErrorType::RuntimeError,
+                self.calc_pos(other),
+            )
+        };
+
+        // `3h + today` is `today + 3h`
+        let (left, right) = match (&self.value, &other.value) {
+            (_, Date(_)) | (_, DateTime(_)) if !subtract => (&other.value, &self.value),
+            _ => (&self.value, &other.value),
+        };
+
+        let type_err = || {
+            let why = self.gen_type_err(other, operation);
+            match (left, right) {
+                (Date(_), Quantity(val)) | (DateTime(_), Quantity(val)) if val.dim == units::LENGTH => {
+                    why.with_help("`m` is metres, write `30 min` for minutes".to_string())
+                }
+                _ => why,
+            }
+        };
+
+        let value = match (left, right.to_duration()) {
+            (Date(_), Some(shift)) | (DateTime(_), Some(shift)) => {
+                let shift = if subtract { -shift } else { shift };
+                match left {
+                    // Whole days keep a date a date
+                    Date(date) if datetime::is_whole_days(&shift) => {
+                        date.checked_add_signed(shift).map(Date)
+                    }
+                    _ => left
+                        .to_datetime()
+                        .and_then(|val| val.checked_add_signed(shift))
+                        .map(DateTime),
+                }
+            }
+            (_, Some(right)) => match left.to_duration() {
+                Some(left) if subtract => left.checked_sub(&right).map(Duration),
+                Some(left) => left.checked_add(&right).map(Duration),
+                None => return Err(type_err()),
+            },
+            (Date(_), None) | (DateTime(_), None) if subtract => match right.to_datetime() {
+                Some(right) => left.to_datetime().map(|left| Duration(left.signed_duration_since(right))),
+                None => return Err(type_err()),
+            },
+            _ => return Err(type_err()),
+        };
+
+        value.ok_or_else(out_of_range)
+    }
+
+    /// Turn quantities of time back into durations, after durations were multiplied or divided
+    fn timed(&self, other: &Self, val: units::Quantity) -> Result<ExecutionExpr, Error> {
+        if val.dim != units::TIME {
+            return Ok(quantity(val));
+        }
+
+        datetime::from_secs(val.value).map(Duration).ok_or_else(|| {
+            Error::new(
+                format!("duration `{:.3e} s` is out of range", val.value),
+                ErrorType::RuntimeError,
+                self.calc_pos(other),
+            )
+        })
+    }
+
+    pub(crate) fn mul(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(match left.checked_mul(*right) {
-                    Some(val) => val,
-                    None => {
-                        return Err(Error::new(
-                            format!("failed to multiply `{}` by `{}`: value overflowed", right, left),
-                            ErrorType::RuntimeError,
-                            self.calc_pos(other),
-                        ));
-                    }
-                }),
+                (Integer(left), Integer(right)) => Integer(left * right),
                 (Float(left), Float(right)) => Float(left.mul(*right)),
-                _ => return Err(self.gen_type_err(other, "multiply")),
+                (Duration(_), _) | (_, Duration(_)) => {
+                    match (self.value.to_quantity(), other.value.to_quantity()) {
+                        (Some(left), Some(right)) => self.timed(other, left.product(&right, false))?,
+                        _ => return Err(self.gen_type_err(other, "multiply")),
+                    }
+                }
+                (Quantity(_), _) | (_, Quantity(_)) => {
+                    match (self.value.to_quantity(), other.value.to_quantity()) {
+                        (Some(left), Some(right)) => quantity(left.product(&right, false)),
+                        _ => return Err(self.gen_type_err(other, "multiply")),
+                    }
+                }
+                (Complex(_), _) | (_, Complex(_)) => match self.complexes(other) {
+                    Some((left, right)) => Complex(left * right),
+                    None => return Err(self.gen_type_err(other, "multiply")),
+                },
+                _ => match self.rationals(other) {
+                    Some((left, right)) => rational(left * right),
+                    None => return Err(self.gen_type_err(other, "multiply")),
+                },
             },
             self.calc_pos(other)
         )
     }
 
+    fn zero_div_err(&self, other: &Self, operation: &'static str) -> Error {
+        Error::new(
+            format!("failed to {} `{}` by zero", operation, self.value),
+            ErrorType::RuntimeError,
+            self.calc_pos(other),
+        )
+    }
+
     fn modulo(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(*left % *right),
+                (Integer(_), Integer(right)) if right.is_zero() => {
+                    return Err(self.zero_div_err(other, "modulo"))
+                }
+                (Integer(left), Integer(right)) => Integer(left % right),
                 (Float(left), Float(right)) => Float(*left % *right),
-                _ => return Err(self.gen_type_err(other, "modulo")),
+                _ => match self.rationals(other) {
+                    Some((_, right)) if right.is_zero() => {
+                        return Err(self.zero_div_err(other, "modulo"))
+                    }
+                    Some((left, right)) => rational(left % right),
+                    None => return Err(self.gen_type_err(other, "modulo")),
+                },
             },
             self.calc_pos(other)
         )
     }
 
-    fn div(&self, other: &Self) -> Result<Self, Error> {
+    pub(crate) fn div(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Float((*left as f64).div(*right as f64)),
                 (Float(left), Float(right)) => Float(left.div(*right)),
-                _ => return Err(self.gen_type_err(other, "divide")),
+                (Duration(_), _) | (_, Duration(_)) => {
+                    match (self.value.to_quantity(), other.value.to_quantity()) {
+                        (Some(left), Some(right)) => self.timed(other, left.product(&right, true))?,
+                        _ => return Err(self.gen_type_err(other, "divide")),
+                    }
+                }
+                (Quantity(_), _) | (_, Quantity(_)) => {
+                    match (self.value.to_quantity(), other.value.to_quantity()) {
+                        (Some(left), Some(right)) => quantity(left.product(&right, true)),
+                        _ => return Err(self.gen_type_err(other, "divide")),
+                    }
+                }
+                (Complex(_), _) | (_, Complex(_)) => match self.complexes(other) {
+                    Some((_, right)) if right.is_zero() => {
+                        return Err(self.zero_div_err(other, "divide"))
+                    }
+                    Some((left, right)) => Complex(left / right),
+                    None => return Err(self.gen_type_err(other, "divide")),
+                },
+                _ => match self.rationals(other) {
+                    Some((_, right)) if right.is_zero() => {
+                        return Err(self.zero_div_err(other, "divide"))
+                    }
+                    Some((left, right)) => rational(left / right),
+                    None => return Err(self.gen_type_err(other, "divide")),
+                },
             },
             self.calc_pos(other)
         )
     }
 
     fn int_div(&self, other: &Self) -> Result<Self, Error> {
-        from_expr!(
-            match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(match left.checked_div(*right) {
-                    Some(val) => val,
-                    None => {
+        let (left, right) = match (&self.value, &other.value) {
+            (Integer(left), Integer(right)) => (left.clone(), right.clone()),
+            (Float(left), Float(right)) => {
+                match (BigInt::from_f64(left.trunc()), BigInt::from_f64(right.trunc())) {
+                    (Some(left), Some(right)) => (left, right),
+                    _ => {
                         return Err(Error::new(
-                            format!(
-                                "failed to integer divide `{}` by `{}`: value overflowed",
-                                right, left
-                            ),
+                            format!("failed to integer divide `{}` by `{}`", left, right),
                             ErrorType::RuntimeError,
                             self.calc_pos(other),
-                        ));
+                        ))
                     }
-                }),
-                (Float(left), Float(right)) => {
-                    Integer(match (*left as i128).checked_div(*right as i128) {
-                        Some(val) => val,
-                        None => {
-                            return Err(Error::new(
-                                format!(
-                                    "failed to integer divide `{}` by {}: value overflowed",
-                                    right, left
-                                ),
-                                ErrorType::RuntimeError,
-                                self.calc_pos(other),
-                            ))
-                        }
-                    })
                 }
-                _ => return Err(self.gen_type_err(other, "integer divide")),
+            }
+            _ => match self.rationals(other) {
+                Some((_, right)) if right.is_zero() => {
+                    return Err(self.zero_div_err(other, "integer divide"))
+                }
+                Some((left, right)) => ((left / right).to_integer(), BigInt::one()),
+                None => return Err(self.gen_type_err(other, "integer divide")),
             },
-            self.calc_pos(other)
-        )
+        };
+
+        if right.is_zero() {
+            return Err(self.zero_div_err(other, "integer divide"));
+        }
+
+        from_expr!(Integer(left / right), self.calc_pos(other))
     }
 
-    fn pow(&self, other: &Self) -> Result<Self, Error> {
+    fn pow(&self, other: &Self, max_digits: usize) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(
-                    match left.checked_pow(match u32::try_from(*right) {
-                        Ok(val) => val,
-                        Err(why) => {
-                            return Err(Error::new(
-                                format!(
-                                    "failed to raise `{}` to the power of `{}`: {}",
-                                    left, right, why
-                                ),
-                                ErrorType::RuntimeError,
-                                self.calc_pos(other),
-                            ));
+                (Float(left), Float(right)) => Float(left.powf(*right)),
+                (Quantity(base), Integer(exp)) => match exp.to_i8() {
+                    Some(exp) => quantity(base.powi(exp)),
+                    None => {
+                        return Err(Error::new(
+                            format!(
+                                "failed to raise `{}` to the power of `{}`: exponent must be between {} and {}",
+                                self.value, exp, i8::MIN, i8::MAX
+                            ),
+                            ErrorType::RuntimeError,
+                            self.calc_pos(other),
+                        ))
+                    }
+                },
+                (Complex(_), _) | (_, Complex(_)) => match self.complexes(other) {
+                    // Whole powers by repeated multiplication, so `i ** 2` is exact
+                    Some((left, right)) => Complex(match &other.value {
+                        Integer(exp) => match exp.to_i32() {
+                            Some(exp) => left.powi(exp),
+                            None => left.powc(right),
+                        },
+                        _ => left.powc(right),
+                    }),
+                    None => return Err(self.gen_type_err(other, "power")),
+                },
+                _ => match self.rationals(other) {
+                    Some((base, exp)) if exp.is_integer() => {
+                        let exp = match exp.to_integer().to_i32() {
+                            Some(val) => val,
+                            None => {
+                                return Err(Error::new(
+                                    format!(
+                                        "failed to raise `{}` to the power of `{}`: exponent must be between {} and {}",
+                                        self.value, exp, i32::MIN, i32::MAX
+                                    ),
+                                    ErrorType::RuntimeError,
+                                    self.calc_pos(other),
+                                ));
+                            }
+                        };
+
+                        if base.is_zero() && exp < 0 {
+                            return Err(self.zero_div_err(other, "divide"));
                         }
-                    }) {
-                        Some(val) => val,
-                        None => {
-                            return Err(Error::new(
-                                format!(
-                                    "failed to raise `{}` to the power of `{}`: value overflowed",
-                                    left, right
-                                ),
-                                ErrorType::RuntimeError,
-                                self.calc_pos(other),
-                            ));
+
+                        // Refuse before doing the work if the result can't fit
+                        let bits = base.numer().bits().max(base.denom().bits());
+                        if (base.numer().magnitude() > &BigUint::one() || !base.is_integer())
+                            && (bits - 1) * u64::from(exp.unsigned_abs()) > max_bits(max_digits)
+                        {
+                            return Err(too_big_err(max_digits, self.calc_pos(other)));
                         }
-                    },
-                ),
-                (Float(left), Float(right)) => Float(left.powf(*right)),
-                _ => return Err(self.gen_type_err(other, "power")),
+
+                        rational(base.pow(exp))
+                    }
+                    _ => return Err(self.gen_type_err(other, "power")),
+                },
             },
             self.calc_pos(other)
         )
@@ -275,7 +704,7 @@ impl EE {
     fn band(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(*left & *right),
+                (Integer(left), Integer(right)) => Integer(left & right),
                 _ => return Err(self.gen_type_err(other, "bitwise and")),
             },
             self.pos
@@ -285,7 +714,7 @@ impl EE {
     fn bor(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(*left | *right),
+                (Integer(left), Integer(right)) => Integer(left | right),
                 _ => return Err(self.gen_type_err(other, "bitwise or")),
             },
             self.pos
@@ -295,7 +724,7 @@ impl EE {
     fn bxor(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => Integer(*left ^ *right),
+                (Integer(left), Integer(right)) => Integer(left ^ right),
                 _ => return Err(self.gen_type_err(other, "bitwise xor")),
             },
             self.pos
@@ -317,33 +746,31 @@ impl EE {
         )
     }
 
+    fn shift_amount(&self, other: &Self, operation: &'static str) -> Result<usize, Error> {
+        match &other.value {
+            Integer(right) => right.to_usize().ok_or_else(|| {
+                Error::new(
+                    format!(
+                        "failed to {} `{}` by `{}`: shift must not be negative",
+                        operation, self.value, right
+                    ),
+                    ErrorType::RuntimeError,
+                    self.calc_pos(other),
+                )
+            }),
+            _ => Err(self.gen_type_err(other, operation)),
+        }
+    }
 
-    fn bitshift_l(&self, other: &Self) -> Result<Self, Error> {
+    fn bitshift_l(&self, other: &Self, max_digits: usize) -> Result<Self, Error> {
         from_expr!(
-            match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => {
-                    match left.checked_shl(match u32::try_from(*right) {
-                        Ok(val) => val,
-                        Err(why) => {
-                            return Err(Error::new(
-                                format!("failed to bitshift `{}` left `{}`: {}", left, right, why),
-                                ErrorType::RuntimeError,
-                                self.calc_pos(other),
-                            ));
-                        }
-                    }) {
-                        Some(val) => Integer(val),
-                        None => {
-                            return Err(Error::new(
-                                format!(
-                                    "failed to bitshift `{}` left by `{}`: overflowed",
-                                    left, right
-                                ),
-                                ErrorType::RuntimeError,
-                                self.calc_pos(other),
-                            ))
-                        }
+            match &self.value {
+                Integer(left) => {
+                    let shift = self.shift_amount(other, "left bitshift")?;
+                    if !left.is_zero() && left.bits() + shift as u64 > max_bits(max_digits) + 1 {
+                        return Err(too_big_err(max_digits, self.calc_pos(other)));
                     }
+                    Integer(left << shift)
                 }
                 _ => return Err(self.gen_type_err(other, "left bitshift")),
             },
@@ -353,99 +780,147 @@ impl EE {
 
     fn bitshift_r(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
-            match (&self.value, &other.value) {
-                (Integer(left), Integer(right)) => {
-                    match left.checked_shr(match u32::try_from(*right) {
-                        Ok(val) => val,
-                        Err(why) => {
-                            return Err(Error::new(
-                                format!("failed to bitshift `{}` right `{}`: {}", left, right, why),
-                                ErrorType::RuntimeError,
-                                self.calc_pos(other),
-                            ));
-                        }
-                    }) {
-                        Some(val) => Integer(val),
-                        None => {
-                            return Err(Error::new(
-                                format!(
-                                    "failed to bitshift `{}` right by `{}`: overflowed",
-                                    left, right
-                                ),
-                                ErrorType::RuntimeError,
-                                self.calc_pos(other),
-                            ))
-                        }
-                    }
-                }
-                _ => return Err(self.gen_type_err(other, "right bitshift ")),
+            match &self.value {
+                Integer(left) => Integer(left >> self.shift_amount(other, "right bitshift")?),
+                _ => return Err(self.gen_type_err(other, "right bitshift")),
             },
             self.calc_pos(other)
         )
     }
 
-    fn as_cast<'a>(&self, target_type: ast::Expression<'a>) -> Result<Self, Error> {
+    fn cast_err(&self, target: &str, target_pos: Pos) -> Error {
+        Error::new(
+            format!(
+                "cannot convert type {} to `{}`",
+                self.value.display_type(),
+                target
+            ),
+            ErrorType::TypeError,
+            Pos::new(self.pos.start, target_pos.end),
+        )
+    }
+
+    fn parse_err(&self, text: &str, target: &str) -> Error {
+        Error::new(
+            format!("cannot read {:?} as `{}`", text, target),
+            ErrorType::RuntimeError,
+            self.pos,
+        )
+    }
+
+    fn as_cast(&self, target_type: &ast::Expression<'_>) -> Result<Self, Error> {
+        if let List(items) = &self.value {
+            return from_expr!(self.each(items, |item| item.as_cast(target_type))?, self.pos);
+        }
+
         from_expr!(
             match target_type.expr {
                 ExpressionKind::Ident(tok) => match tok {
-                    "float" => match self.value {
-                        Integer(val) => Float(
-                            match f64::try_from(match i32::try_from(val) {
-                                Ok(val) => val,
-                                Err(why) =>
-                                    return Err(Error::new(
-                                        format!(
-                                            "failed to convert `{}` to `{}`: {}",
-                                            self.value, tok, why
-                                        ),
-                                        ErrorType::RuntimeError,
-                                        self.pos,
-                                    )),
-                            }) {
-                                Ok(val) => val,
-                                Err(why) =>
-                                    return Err(Error::new(
-                                        format!(
-                                            "failed to convert `{}` to `{}`: {}",
-                                            self.value, tok, why
-                                        ),
-                                        ErrorType::RuntimeError,
-                                        self.pos,
-                                    )),
-                            }
-                        ),
-                        Float(_) => self.value,
-                        Bool(val) => Float(val as i8 as f64),
+                    "float" => match &self.value {
+                        Integer(val) => match val.to_f64() {
+                            Some(val) if val.is_finite() => Float(val),
+                            _ =>
+                                return Err(Error::new(
+                                    format!("failed to convert `{}` to `{}`: value too large", self.value, tok),
+                                    ErrorType::RuntimeError,
+                                    self.pos,
+                                )),
+                        },
+                        Rational(val) => match val.to_f64() {
+                            Some(val) if val.is_finite() => Float(val),
+                            _ =>
+                                return Err(Error::new(
+                                    format!("failed to convert `{}` to `{}`: value too large", self.value, tok),
+                                    ErrorType::RuntimeError,
+                                    self.pos,
+                                )),
+                        },
+                        Float(_) => self.value.clone(),
+                        Bool(val) => Float(*val as i8 as f64),
+                        Str(val) => match val.trim().parse::<f64>() {
+                            Ok(val) => Float(val),
+                            Err(_) => return Err(self.parse_err(val, tok)),
+                        },
+                        Complex(_) | Quantity(_) | Date(_) | DateTime(_) | Duration(_) | List(_)
+                        | Function(_) | Symbolic(_) => {
+                            return Err(self.cast_err(tok, target_type.pos))
+                        }
                     },
-                    "int" => match self.value {
-                        Integer(_) => self.value,
-                        Float(val) => Integer(val.round() as i128),
-                        Bool(val) => Integer(val as i128),
+                    "int" => match &self.value {
+                        Integer(_) => self.value.clone(),
+                        Rational(val) => Integer(val.round().to_integer()),
+                        Float(val) => match BigInt::from_f64(val.round()) {
+                            Some(val) => Integer(val),
+                            None =>
+                                return Err(Error::new(
+                                    format!("failed to convert `{}` to `{}`", self.value, tok),
+                                    ErrorType::RuntimeError,
+                                    self.pos,
+                                )),
+                        },
+                        Bool(val) => Integer(BigInt::from(*val as u8)),
+                        Str(val) => match val.trim().parse::<BigInt>() {
+                            Ok(val) => Integer(val),
+                            Err(_) => return Err(self.parse_err(val, tok)),
+                        },
+                        Complex(_) | Quantity(_) | Date(_) | DateTime(_) | Duration(_) | List(_)
+                        | Function(_) | Symbolic(_) => {
+                            return Err(self.cast_err(tok, target_type.pos))
+                        }
                     },
-                    _ =>
+                    tok if options::is_conversion(tok) => {
                         return Err(Error::new(
-                            format!("unknown type `{}`", tok),
+                            format!("`as {}` only changes how the result is written", tok),
                             ErrorType::TypeError,
-                            target_type.pos
-                        )),
+                            target_type.pos,
+                        )
+                        .with_help(format!("put it at the very end, as in `(x + 1) as {}`", tok)))
+                    }
+                    _ => self.convert(target_type)?,
                 },
-                _ => {
-                    return Err(Error::new(
-                        format!("invalid type for `as` type operand `{}`", target_type.expr),
-                        ErrorType::TypeError,
-                        target_type.pos,
-                    ));
-                }
+                _ => self.convert(target_type)?,
             },
             self.pos
         )
     }
 
-    fn neg(&self) -> Result<Self, Error> {
+    /// Show a quantity in another unit of the same dimension
+    fn convert(&self, target: &ast::Expression<'_>) -> Result<ExecutionExpr, Error> {
+        let (unit, dim) = units::target(target)?;
+        let val = match &self.value {
+            Quantity(_) | Duration(_) => self.value.to_quantity(),
+            _ => None,
+        };
+
+        match val {
+            Some(val) if val.dim == dim => Ok(Quantity(units::Quantity {
+                value: val.value,
+                dim,
+                unit: Some(unit),
+            })),
+            Some(val) => Err(Error::new(
+                format!(
+                    "cannot convert `{}` to `{}`: dimensions don't match",
+                    units::dim_name(&val.dim),
+                    unit.name
+                ),
+                ErrorType::TypeError,
+                Pos::new(self.pos.start, target.pos.end),
+            )),
+            _ => Err(self.cast_err(&unit.name, target.pos)),
+        }
+    }
+
+    pub(crate) fn neg(&self) -> Result<Self, Error> {
         from_expr!(
             match &self.value {
                 Integer(val) => Integer(-val),
+                Rational(val) => Rational(-val),
                 Float(val) => Float(-val),
+                Complex(val) => Complex(-val),
+                Quantity(val) => Quantity(val.with_value(-val.value)),
+                Duration(val) => Duration(-*val),
+                List(items) => self.each(items, |item| item.neg())?,
                 _ => {
                     return Err(Error::new(
                         format!("cannot make type {} negative", self.value.display_type()),
@@ -458,11 +933,17 @@ impl EE {
         )
     }
 
-    fn pos(&self) -> Result<Self, Error> {
+    pub(crate) fn pos(&self) -> Result<Self, Error> {
         from_expr!(
             match &self.value {
                 Integer(val) => Integer(val.abs()),
+                Rational(val) => Rational(val.abs()),
                 Float(val) => Float(val.abs()),
+                Complex(val) => Float(val.norm()),
+                Quantity(val) => Quantity(val.with_value(val.value.abs())),
+                Duration(val) if *val < chrono::Duration::zero() => Duration(-*val),
+                Duration(val) => Duration(*val),
+                List(items) => self.each(items, |item| item.pos())?,
                 _ => {
                     return Err(Error::new(
                         format!("cannot make type {} positive", self.value.display_type()),
@@ -475,31 +956,80 @@ impl EE {
         )
     }
 
+    /// Quantities and durations are equal when they measure the same amount
+    fn equals(&self, other: &Self) -> Result<bool, Error> {
+        match (&self.value, &other.value) {
+            (Quantity(left), Quantity(right)) => {
+                self.check_dims(other, left, right, "compare")?;
+                Ok(left == right)
+            }
+            (Duration(_), _) | (_, Duration(_)) => Ok(matches!(
+                (self.value.to_duration(), other.value.to_duration()),
+                (Some(left), Some(right)) if left == right
+            )),
+            _ => Ok(self.value == other.value),
+        }
+    }
+
     fn eql(&self, other: &Self) -> Result<Self, Error> {
-        from_expr!(Bool(self.value == other.value), self.calc_pos(other))
+        from_expr!(Bool(self.equals(other)?), self.calc_pos(other))
     }
 
     fn neql(&self, other: &Self) -> Result<Self, Error> {
-        from_expr!(Bool(self.value != other.value), self.calc_pos(other))
+        from_expr!(Bool(!self.equals(other)?), self.calc_pos(other))
+    }
+
+    /// Order dates and times, or durations
+    fn time_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.value.to_datetime(), other.value.to_datetime()) {
+            (Some(left), Some(right)) => Some(left.cmp(&right)),
+            _ => Some(self.value.to_duration()?.cmp(&other.value.to_duration()?)),
+        }
     }
 
-    fn lt(&self, other: &Self) -> Result<Self, Error> {
+    pub(crate) fn lt(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
                 (Integer(left), Integer(right)) => Bool(left < right),
                 (Float(left), Float(right)) => Bool(left < right),
-                _ => return Err(self.gen_type_err(other, "less than")),
+                (Str(left), Str(right)) => Bool(left < right),
+                (Quantity(left), Quantity(right)) => {
+                    self.check_dims(other, left, right, "compare")?;
+                    Bool(left.value < right.value)
+                }
+                (Date(_), _) | (DateTime(_), _) | (Duration(_), _) | (_, Date(_)) | (_, DateTime(_))
+                | (_, Duration(_)) => match self.time_cmp(other) {
+                    Some(ord) => Bool(ord == Ordering::Less),
+                    None => return Err(self.gen_type_err(other, "less than")),
+                },
+                _ => match self.rationals(other) {
+                    Some((left, right)) => Bool(left < right),
+                    None => return Err(self.gen_type_err(other, "less than")),
+                },
             },
             self.calc_pos(other)
         )
     }
 
-    fn gt(&self, other: &Self) -> Result<Self, Error> {
+    pub(crate) fn gt(&self, other: &Self) -> Result<Self, Error> {
         from_expr!(
             match (&self.value, &other.value) {
                 (Integer(left), Integer(right)) => Bool(left > right),
                 (Float(left), Float(right)) => Bool(left > right),
-                _ => return Err(self.gen_type_err(other, "greater than")),
+                (Str(left), Str(right)) => Bool(left > right),
+                (Quantity(left), Quantity(right)) => {
+                    self.check_dims(other, left, right, "compare")?;
+                    Bool(left.value > right.value)
+                }
+                (Date(_), _) | (DateTime(_), _) | (Duration(_), _) | (_, Date(_)) | (_, DateTime(_))
+                | (_, Duration(_)) => match self.time_cmp(other) {
+                    Some(ord) => Bool(ord == Ordering::Greater),
+                    None => return Err(self.gen_type_err(other, "greater than")),
+                },
+                _ => match self.rationals(other) {
+                    Some((left, right)) => Bool(left > right),
+                    None => return Err(self.gen_type_err(other, "greater than")),
+                },
             },
             self.calc_pos(other)
         )
@@ -510,7 +1040,20 @@ impl EE {
             match (&self.value, &other.value) {
                 (Integer(left), Integer(right)) => Bool(left <= right),
                 (Float(left), Float(right)) => Bool(left <= right),
-                _ => return Err(self.gen_type_err(other, "less than")),
+                (Str(left), Str(right)) => Bool(left <= right),
+                (Quantity(left), Quantity(right)) => {
+                    self.check_dims(other, left, right, "compare")?;
+                    Bool(left.value <= right.value)
+                }
+                (Date(_), _) | (DateTime(_), _) | (Duration(_), _) | (_, Date(_)) | (_, DateTime(_))
+                | (_, Duration(_)) => match self.time_cmp(other) {
+                    Some(ord) => Bool(ord != Ordering::Greater),
+                    None => return Err(self.gen_type_err(other, "less than")),
+                },
+                _ => match self.rationals(other) {
+                    Some((left, right)) => Bool(left <= right),
+                    None => return Err(self.gen_type_err(other, "less than")),
+                },
             },
             self.calc_pos(other)
         )
@@ -521,7 +1064,20 @@ impl EE {
             match (&self.value, &other.value) {
                 (Integer(left), Integer(right)) => Bool(left >= right),
                 (Float(left), Float(right)) => Bool(left >= right),
-                _ => return Err(self.gen_type_err(other, "greater than")),
+                (Str(left), Str(right)) => Bool(left >= right),
+                (Quantity(left), Quantity(right)) => {
+                    self.check_dims(other, left, right, "compare")?;
+                    Bool(left.value >= right.value)
+                }
+                (Date(_), _) | (DateTime(_), _) | (Duration(_), _) | (_, Date(_)) | (_, DateTime(_))
+                | (_, Duration(_)) => match self.time_cmp(other) {
+                    Some(ord) => Bool(ord != Ordering::Less),
+                    None => return Err(self.gen_type_err(other, "greater than")),
+                },
+                _ => match self.rationals(other) {
+                    Some((left, right)) => Bool(left >= right),
+                    None => return Err(self.gen_type_err(other, "greater than")),
+                },
             },
             self.calc_pos(other)
         )
@@ -535,8 +1091,71 @@ impl fmt::Display for EE {
     }
 }
 
+/// Explain how to combine an integer or rational with a float, which strict mode refuses
+fn mixed_help(why: Error, op: ast::Operator, exact: &ast::Expression<'_>) -> Error {
+    let why = why.with_note("integers and floats don't mix, so that exact results stay exact".to_string());
+    match exact.expr {
+        // Only plain digits, `0xff.0` is no float
+        ExpressionKind::Integer(digits) if digits.bytes().all(|c| c.is_ascii_digit() || c == b'_') => {
+            let action = match op {
+                ast::Operator::Add => "add to",
+                ast::Operator::Sub => "subtract with",
+                ast::Operator::Mul => "multiply by",
+                ast::Operator::Div => "divide with",
+                ast::Operator::Eql
+                | ast::Operator::NEql
+                | ast::Operator::GT
+                | ast::Operator::LT
+                | ast::Operator::GE
+                | ast::Operator::LE => "compare with",
+                _ => "combine with",
+            };
+            why.with_help(format!("use `{}.0` instead of `{}` to {} a float", digits, digits, action))
+        }
+        _ => why.with_help("convert with `as float`, or evaluate with `--lenient`".to_string()),
+    }
+}
+
+fn nothing_err() -> Error {
+    Error::new(
+        "nothing to evaluate".to_string(),
+        ErrorType::SyntaxError,
+        Pos::new(0, 0),
+    )
+}
+
+/// Operators applied element by element when either side is a list
+fn is_elementwise(op: ast::Operator) -> bool {
+    matches!(
+        op,
+        ast::Operator::Add
+            | ast::Operator::Sub
+            | ast::Operator::Mul
+            | ast::Operator::Div
+            | ast::Operator::Mod
+            | ast::Operator::IntDiv
+            | ast::Operator::Pow
+    )
+}
+
+/// A user defined function, `f(x) = ...` or `|x| ...`
+struct Closure<'a> {
+    params: Vec<&'a str>,
+    body: Rc<ast::Expression<'a>>,
+    captured: HashMap<&'a str, EE>, // Locals visible where it was created
+}
+
 pub struct Executer<'a> {
     symbtab: HashMap<&'a str, EE>,
+    constants: HashSet<&'a str>,
+    natives: HashMap<&'static str, Native>,
+    options: Options,
+    functions: Vec<Closure<'a>>,
+    scopes: Vec<HashMap<&'a str, EE>>, // Locals of the functions being called
+    steps: usize,
+    depth: usize, // Of `eval` calls in progress
+    trace: Option<Vec<Reduction>>, // Parts reduced so far, while tracing
+    notes: Vec<String>, // Caveats shown after the result, such as roots only searched for nearby
 }
 
 macro_rules! map(
@@ -553,24 +1172,590 @@ macro_rules! map(
 
 use std::f64::consts;
 impl<'a> Executer<'a> {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn with_options(options: Options) -> Self {
+        let mut symbtab = map!(
+            "pi" => consts::PI,
+            "tau" => consts::PI * 2.0,
+            "e" => consts::E,
+            "sqrt2" => consts::SQRT_2
+        );
+        symbtab.insert("i", EE::new(Complex(Complex64::i()), Pos::new(0, 0)));
+
+        let now = chrono::Utc::now().naive_utc();
+        symbtab.insert("now", EE::new(DateTime(now), Pos::new(0, 0)));
+        symbtab.insert("today", EE::new(Date(now.date()), Pos::new(0, 0)));
+        let constants = symbtab.keys().copied().collect();
+
         Executer {
-            symbtab: map!(
-                "pi" => consts::PI,
-                "tau" => consts::PI * 2.0,
-                "e" => consts::E,
-                "sqrt2" => consts::SQRT_2
-            ),
+            symbtab,
+            constants,
+            natives: builtins::registry(),
+            options,
+            functions: Vec::new(),
+            scopes: Vec::new(),
+            steps: 0,
+            depth: 0,
+            trace: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Give a variable a value ahead of evaluating, such as `x` while plotting
+    pub(crate) fn bind(&mut self, name: &'a str, value: ExecutionExpr) {
+        self.symbtab.insert(name, EE::new(value, Pos::new(0, 0)));
+    }
+
+    fn lookup(&self, name: &str) -> Option<&EE> {
+        self.scopes
+            .last()
+            .and_then(|scope| scope.get(name))
+            .or_else(|| self.symbtab.get(name))
+    }
+
+    fn call(&mut self, call: &ast::Call<'a>, pos: Pos) -> Result<EE, Error> {
+        // Natives are looked up by name, unless shadowed by a variable
+        if let ExpressionKind::Ident(name) = call.func.expr {
+            if self.lookup(name).is_none() {
+                let native = match self.natives.get(name) {
+                    Some(native) => *native,
+                    None => {
+                        return Err(Error::new(
+                            format!("no function `{}` found", name),
+                            ErrorType::RuntimeError,
+                            call.func.pos,
+                        ))
+                    }
+                };
+
+                let args = self.eval_args(&call.args)?;
+                return native.call(name, pos, &args, &self.options);
+            }
+        }
+
+        let func = self.eval(&call.func)?;
+        let index = match func.value {
+            Function(index) => index,
+            _ => {
+                return Err(Error::new(
+                    format!("cannot call type {}", func.value.display_type()),
+                    ErrorType::TypeError,
+                    func.pos,
+                ))
+            }
+        };
+
+        let (params, body, mut scope) = {
+            let closure = &self.functions[index];
+            (
+                closure.params.clone(),
+                Rc::clone(&closure.body),
+                closure.captured.clone(),
+            )
+        };
+
+        if params.len() != call.args.len() {
+            return Err(Error::new(
+                format!(
+                    "function takes {} argument(s) but {} were supplied",
+                    params.len(),
+                    call.args.len()
+                ),
+                ErrorType::TypeError,
+                pos,
+            ));
+        }
+
+        let args = self.eval_args(&call.args)?;
+        scope.extend(params.into_iter().zip(args));
+
+        self.scopes.push(scope);
+        let result = self.eval(&body);
+        self.scopes.pop();
+
+        Ok(EE::new(result?.value, pos))
+    }
+
+    fn eval_args(&mut self, args: &[ast::Expression<'a>]) -> Result<Vec<EE>, Error> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.eval(arg)?);
+        }
+        Ok(values)
+    }
+
+    pub(crate) fn assign(&mut self, assign: &ast::Assign<'a>) -> Result<EE, Error> {
+        if self.constants.contains(assign.name) {
+            return Err(Error::new(
+                format!("cannot assign to constant `{}`", assign.name),
+                ErrorType::RuntimeError,
+                assign.name_pos,
+            ));
+        }
+
+        let value = self.eval(&assign.value)?;
+        self.symbtab.insert(assign.name, value.clone());
+        Ok(value)
+    }
+
+    /// Run every statement in order, returning the value of the last one
+    pub(crate) fn run(&mut self, program: Vec<ast::Statement<'a>>) -> Result<EE, Error> {
+        let mut last = None;
+        for (i, statement) in program.iter().enumerate() {
+            last = Some(self.statement(statement, i == program.len() - 1)?);
+        }
+
+        last.ok_or_else(nothing_err)
+    }
+
+    /// Run every statement like `run`, returning the last one rewritten after each step that
+    /// reduced it, innermost first, and ending in the result as shown
+    pub(crate) fn trace(&mut self, program: Vec<ast::Statement<'a>>) -> Result<Vec<String>, Error> {
+        let (last, rest) = program.split_last().ok_or_else(nothing_err)?;
+        for statement in rest {
+            self.statement(statement, false)?;
+        }
+
+        self.trace = Some(Vec::new());
+        let value = self.statement(last, true);
+        let reductions = self.trace.take().unwrap_or_default();
+        let shown = format::inline(&value?.value, &self.options.format);
+        if reductions.len() >= trace::MAX_STEPS {
+            return Err(Error::new(
+                format!("evaluation takes more than the {} steps a trace can show", trace::MAX_STEPS),
+                ErrorType::LimitError,
+                last.pos,
+            ));
+        }
+
+        let ast = match &last.stmt {
+            ast::StatementKind::Let(assign) | ast::StatementKind::Assign(assign) => &assign.value,
+            ast::StatementKind::Expr(expr) => expr,
+        };
+        let mut steps = trace::rewrites(ast, &reductions);
+        // Output conversions such as `as hex` are applied after the last reduction
+        if steps.last() != Some(&shown) {
+            steps.push(shown);
+        }
+        Ok(steps)
+    }
+
+    fn statement(&mut self, statement: &ast::Statement<'a>, last: bool) -> Result<EE, Error> {
+        match &statement.stmt {
+            ast::StatementKind::Let(assign) | ast::StatementKind::Assign(assign) => self.assign(assign),
+            ast::StatementKind::Expr(expr) if last => self.result(expr),
+            ast::StatementKind::Expr(expr) => self.eval(expr),
+        }
+    }
+
+    /// Evaluate the expression whose value is shown, which may end in an output conversion
+    /// such as `as hex`
+    pub(crate) fn result(&mut self, ast: &ast::Expression<'a>) -> Result<EE, Error> {
+        let (value, target) = match &ast.expr {
+            ExpressionKind::InfixOp(infix) if infix.op == ast::Operator::As => match infix.right.expr {
+                ExpressionKind::Ident(target) if options::is_conversion(target) => (&infix.left, target),
+                _ => return self.eval(ast),
+            },
+            _ => return self.eval(ast),
+        };
+
+        let value = self.result(value)?;
+        if !format::supports(&value.value, target) {
+            return Err(value.cast_err(target, ast.pos));
         }
+        self.options.format.convert(target);
+        Ok(value)
     }
 
-    pub(crate) fn eval(&mut self, ast: ast::Expression<'a>) -> Result<EE, Error> {
-        Ok(match ast.expr {
+    /// Write a result the way the options ask, refusing one longer than `max_output`
+    pub(crate) fn show(&self, value: &EE) -> Result<String, Error> {
+        let shown = format::show(&value.value, &self.options.format);
+        if shown.chars().count() > self.options.max_output {
+            return Err(Error::new(
+                format!("result is longer than the limit of {} characters", self.options.max_output),
+                ErrorType::LimitError,
+                value.pos,
+            ));
+        }
+
+        Ok(self.notes.iter().fold(shown, |shown, note| format!("{}\nnote: {}", shown, note)))
+    }
+
+    /// `diff(u, x)`, `simplify(u)`, `solve(u = v, x)` and `d/dx (u)`, unless their names are
+    /// taken by variables
+    fn is_symbolic(&self, ast: &ast::Expression<'a>) -> bool {
+        match &ast.expr {
+            ExpressionKind::Call(call) => match call.func.expr {
+                ExpressionKind::Ident(name) => {
+                    (symbolic::is_form(name) || equation::is_solve(call)) && self.lookup(name).is_none()
+                }
+                _ => false,
+            },
+            ExpressionKind::InfixOp(infix) => match symbolic::leibniz(infix) {
+                Some((var, _)) => {
+                    self.lookup("d").is_none() && self.lookup(&format!("d{}", var)).is_none()
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Work on the expression itself, substituting variables that hold numbers
+    fn symbolic(&mut self, ast: &ast::Expression<'a>) -> Result<EE, Error> {
+        // Building expressions counts against the same steps as evaluating them
+        let mut budget = symbolic::Budget::new(&self.options, self.steps);
+        // Root finding needs numbers, elsewhere `pi` reads better than its digits
+        let solving = matches!(&ast.expr, ExpressionKind::Call(call) if equation::is_solve(call));
+        let resolve = |name: &str| {
+            if self.constants.contains(name) && !solving {
+                return None;
+            }
+
+            match &self.lookup(name)?.value {
+                Integer(val) => Some(symbolic::Expr::Number(BigRational::from_integer(val.clone()))),
+                Rational(val) => Some(symbolic::Expr::Number(val.clone())),
+                Float(val) => Some(symbolic::Expr::Float(*val)),
+                Symbolic(val) => Some(val.clone()),
+                _ => None,
+            }
+        };
+
+        let mut note = None;
+        let value = match &ast.expr {
+            ExpressionKind::Call(call) if solving => {
+                equation::solve(call, ast.pos, &resolve, &mut budget).map(|(roots, caveat)| {
+                    note = caveat;
+                    roots
+                })
+            }
+            _ => symbolic::from_ast(ast, &resolve, &[], &mut budget).map(Symbolic),
+        };
+        self.steps = budget.steps;
+        if let Some(note) = note.filter(|note| !self.notes.contains(note)) {
+            self.notes.push(note);
+        }
+        Ok(EE::new(value?, ast.pos))
+    }
+
+    fn infix(&mut self, infix: &ast::InfixOp<'a>, pos: Pos) -> Result<EE, Error> {
+        let left = self.eval(&infix.left)?;
+        if infix.op == ast::Operator::As {
+            return left.as_cast(&infix.right);
+        }
+
+        // Short-circuit: the right of `&&` and `||` is left alone once the left decides
+        match (infix.op, &left.value) {
+            (ast::Operator::LAnd, Bool(false)) | (ast::Operator::LOr, Bool(true)) => {
+                return Ok(EE::new(left.value, pos))
+            }
+            _ => {}
+        }
+
+        let right = self.eval(&infix.right)?;
+        let exact = match (&left.value, &right.value) {
+            (Float(_), Integer(_)) | (Float(_), Rational(_)) => Some(&infix.right),
+            (Integer(_), Float(_)) | (Rational(_), Float(_)) => Some(&infix.left),
+            _ => None,
+        };
+
+        self.binary(infix.op, left, right, pos).map_err(|why| match exact {
+            Some(exact) => mixed_help(why, infix.op, exact),
+            None => why,
+        })
+    }
+
+    /// Evaluate only the branch the condition picks
+    fn conditional(&mut self, cond: &ast::If<'a>, pos: Pos) -> Result<EE, Error> {
+        let test = self.eval(&cond.cond).map_err(|why| match &cond.cond.expr {
+            ExpressionKind::InfixOp(infix) if infix.op == ast::Operator::Equation => {
+                why.with_help("use `==` to compare two values".to_string())
+            }
+            _ => why,
+        })?;
+        let branch = match test.value {
+            Bool(true) => &cond.then,
+            Bool(false) => &cond.otherwise,
+            _ => {
+                return Err(Error::new(
+                    format!("expected a boolean condition, found type {}", test.value.display_type()),
+                    ErrorType::TypeError,
+                    test.pos,
+                )
+                .with_help("compare it with something, as in `if x > 0 then x else -x`".to_string()))
+            }
+        };
+
+        Ok(EE::new(self.eval(branch)?.value, pos))
+    }
+
+    /// Apply an infix operator to two values, element by element for lists
+    fn binary(&self, op: ast::Operator, left: EE, right: EE, pos: Pos) -> Result<EE, Error> {
+        // `*` is the matrix product when both sides are matrices
+        if op == ast::Operator::MatMul
+            || (op == ast::Operator::Mul
+                && matrix::is_matrix(&left.value)
+                && matrix::is_matrix(&right.value))
+        {
+            return matrix::matmul(&left, &right);
+        }
+
+        match (&left.value, &right.value) {
+            (List(_), _) | (_, List(_)) if is_elementwise(op) => {
+                return self.elementwise(op, &left, &right, pos)
+            }
+            _ => {}
+        }
+
+        // `true && 1` stays a boolean and an integer, only numbers widen
+        let logical = matches!(op, ast::Operator::LAnd | ast::Operator::LOr);
+        let (left, right) = match self.options.mode {
+            Mode::Lenient if !logical => left.widen(right),
+            _ => (left, right),
+        };
+
+        match op {
+            ast::Operator::Add => left.add(&right),
+            ast::Operator::Sub => left.sub(&right),
+            ast::Operator::Mul => left.mul(&right),
+            ast::Operator::Div => left.div(&right),
+
+            ast::Operator::Mod => left.modulo(&right),
+
+            ast::Operator::IntDiv => left.int_div(&right),
+            ast::Operator::Pow => left.pow(&right, self.options.max_digits),
+
+            ast::Operator::BitShiftL => left.bitshift_l(&right, self.options.max_digits),
+            ast::Operator::BitShiftR => left.bitshift_r(&right),
+
+            ast::Operator::LOr => left.lor(&right),
+            ast::Operator::LAnd => left.land(&right),
+
+            ast::Operator::BOr => left.bor(&right),
+            ast::Operator::BAnd => left.band(&right),
+            ast::Operator::BXor => left.bxor(&right),
+
+            ast::Operator::NEql => left.neql(&right),
+            ast::Operator::Eql => left.eql(&right),
+
+            ast::Operator::LT => left.lt(&right),
+            ast::Operator::LE => left.lte(&right),
+            ast::Operator::GT => left.gt(&right),
+            ast::Operator::GE => left.gte(&right),
+
+            ast::Operator::Equation => Err(Error::new(
+                "an equation has no value, solve it with `solve(x**2 = 4, x)`".to_string(),
+                ErrorType::TypeError,
+                pos,
+            )),
+
+            _ => Err(Error::new(
+                format!("infix {} not implemented yet", op),
+                ErrorType::TypeError,
+                pos,
+            )),
+        }
+    }
+
+    /// Pair up equal length lists, or a list with a single value
+    fn elementwise(&self, op: ast::Operator, left: &EE, right: &EE, pos: Pos) -> Result<EE, Error> {
+        let len = match (&left.value, &right.value) {
+            (List(left_items), List(right_items)) if left_items.len() != right_items.len() => {
+                return Err(Error::new(
+                    format!(
+                        "cannot apply {} on lists of lengths {} and {}",
+                        op,
+                        left_items.len(),
+                        right_items.len()
+                    ),
+                    ErrorType::RuntimeError,
+                    Pos::new(left.pos.start, right.pos.end),
+                ))
+            }
+            (List(items), _) | (_, List(items)) => items.len(),
+            _ => 0,
+        };
+
+        let item = |value: &EE, i: usize| match &value.value {
+            List(items) => EE::new(items[i].clone(), value.pos),
+            _ => value.clone(),
+        };
+
+        let mut items = Vec::with_capacity(len);
+        for i in 0..len {
+            items.push(self.binary(op, item(left, i), item(right, i), pos)?.value);
+        }
+
+        Ok(EE::new(List(items), Pos::new(left.pos.start, right.pos.end)))
+    }
+
+    /// Evaluate a subscript, counting negative ones back from the end
+    fn subscript(&mut self, expr: &ast::Expression<'a>, len: usize) -> Result<isize, Error> {
+        let value = self.eval(expr)?;
+        let index = match &value.value {
+            Integer(val) => val.to_isize().unwrap_or(if val.is_negative() {
+                isize::MIN
+            } else {
+                isize::MAX
+            }),
+            _ => {
+                return Err(Error::new(
+                    format!("cannot index with type {}", value.value.display_type()),
+                    ErrorType::TypeError,
+                    value.pos,
+                ))
+            }
+        };
+
+        Ok(if index < 0 {
+            index.saturating_add(len as isize)
+        } else {
+            index
+        })
+    }
+
+    fn index(&mut self, index: &ast::Index<'a>, pos: Pos) -> Result<EE, Error> {
+        let value = self.eval(&index.value)?;
+        let items = match &value.value {
+            List(items) => items,
+            _ => {
+                return Err(Error::new(
+                    format!("cannot index into type {}", value.value.display_type()),
+                    ErrorType::TypeError,
+                    value.pos,
+                ))
+            }
+        };
+
+        let len = items.len();
+        match &index.subscript {
+            ast::Subscript::Item(item) => {
+                let i = self.subscript(item, len)?;
+                if i < 0 || i as usize >= len {
+                    return Err(Error::new(
+                        format!("index out of range for a list of length {}", len),
+                        ErrorType::RuntimeError,
+                        item.pos,
+                    ));
+                }
+                Ok(EE::new(items[i as usize].clone(), pos))
+            }
+            ast::Subscript::Slice(start, end) => {
+                // Out of range ends are clamped, like Python
+                let clamp = |i: isize| i.max(0).min(len as isize) as usize;
+                let start = match start {
+                    Some(start) => clamp(self.subscript(start, len)?),
+                    None => 0,
+                };
+                let end = match end {
+                    Some(end) => clamp(self.subscript(end, len)?),
+                    None => len,
+                };
+                Ok(EE::new(List(items[start..end.max(start)].to_vec()), pos))
+            }
+        }
+    }
+
+    fn prefix(&mut self, prefix: &ast::PrefixOp<'a>, pos: Pos) -> Result<EE, Error> {
+        // The result spans the operator too, so errors about `-x` point at all of it
+        let value = EE::new(self.eval(&prefix.value)?.value, pos);
+        match prefix.op {
+            ast::Operator::Sub => value.neg(),
+            ast::Operator::Add => value.pos(),
+            ast::Operator::BNot => value.bnot(),
+            ast::Operator::LNot => value.lnot(),
+            _ => Err(Error::new(
+                format!("prefix {} not implemented yet", prefix.op),
+                ErrorType::TypeError,
+                pos,
+            )),
+        }
+    }
+
+    fn too_big(&self, value: &ExecutionExpr) -> bool {
+        let too_big = |val: &BigInt| {
+            // Only stringify when the bit count leaves it in doubt
+            val.bits() > max_bits(self.options.max_digits)
+                || (val.bits() + 4 > max_bits(self.options.max_digits)
+                    && val.magnitude().to_string().len() > self.options.max_digits)
+        };
+
+        match value {
+            Integer(val) => too_big(val),
+            Rational(val) => too_big(val.numer()) || too_big(val.denom()),
+            List(items) => items.iter().any(|item| self.too_big(item)),
+            _ => false,
+        }
+    }
+
+    /// Refuse integers with more than `max_digits` digits
+    fn limit(&self, value: EE) -> Result<EE, Error> {
+        if self.too_big(&value.value) {
+            return Err(too_big_err(self.options.max_digits, value.pos));
+        }
+
+        Ok(value)
+    }
+
+    /// Whether the deadline has passed, only looking at the clock every `CLOCK_STEPS` steps
+    fn out_of_time(&self) -> bool {
+        match self.options.deadline {
+            Some(end) if self.steps & (CLOCK_STEPS - 1) == 0 => Instant::now() >= end,
+            _ => false,
+        }
+    }
+
+    /// Count a step against the budget, failing once steps, depth or time run out
+    fn step(&mut self, pos: Pos) -> Result<(), Error> {
+        self.steps += 1;
+        if self.steps > self.options.max_steps {
+            Err(too_many_steps_err(self.options.max_steps, pos))
+        } else if self.depth >= self.options.max_depth {
+            Err(Error::new(
+                format!("evaluation is nested deeper than the limit of {}", self.options.max_depth),
+                ErrorType::LimitError,
+                pos,
+            ))
+        } else if self.out_of_time() {
+            Err(out_of_time_err(pos))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn eval(&mut self, ast: &ast::Expression<'a>) -> Result<EE, Error> {
+        self.step(ast.pos)?;
+        self.depth += 1;
+        let value = self.eval_inner(ast);
+        self.depth -= 1;
+
+        // Only reductions of the traced expression itself, not inside the functions it calls
+        if let (Some(trace), Ok(value)) = (&mut self.trace, &value) {
+            let reduces = matches!(
+                ast.expr,
+                ExpressionKind::PrefixOp(_)
+                    | ExpressionKind::InfixOp(_)
+                    | ExpressionKind::Call(_)
+                    | ExpressionKind::If(_)
+                    | ExpressionKind::Index(_)
+            );
+            if reduces && self.scopes.is_empty() {
+                trace.push((trace::node(ast), format::inline(&value.value, &self.options.format)));
+            }
+        }
+        value
+    }
+
+    fn eval_inner(&mut self, ast: &ast::Expression<'a>) -> Result<EE, Error> {
+        if self.is_symbolic(ast) {
+            return self.symbolic(ast);
+        }
+
+        let value = match &ast.expr {
             ExpressionKind::True => EE::new(Bool(true), ast.pos),
             ExpressionKind::False => EE::new(Bool(false), ast.pos),
 
             ExpressionKind::Integer(val) => EE::new(
-                ExecutionExpr::Integer(match val.parse::<i128>() {
+                ExecutionExpr::Integer(match lexer::parse_integer(val) {
                     Ok(val) => val,
                     Err(why) => {
                         return Err(Error::new(
@@ -584,7 +1769,7 @@ impl<'a> Executer<'a> {
             ),
 
             ExpressionKind::Float(val) => EE::new(
-                ExecutionExpr::Float(match val.parse::<f64>() {
+                ExecutionExpr::Float(match lexer::parse_float(val) {
                     Ok(val) => val,
                     Err(why) => {
                         return Err(Error::new(
@@ -597,76 +1782,97 @@ impl<'a> Executer<'a> {
                 ast.pos,
             ),
 
-            // Where all the magic happens
-            ExpressionKind::InfixOp(val) => match val.op {
-                ast::Operator::Add => self.eval(*val.left)?.add(&self.eval(*val.right)?)?,
-                ast::Operator::Sub => self.eval(*val.left)?.sub(&self.eval(*val.right)?)?,
-                ast::Operator::Mul => self.eval(*val.left)?.mul(&self.eval(*val.right)?)?,
-                ast::Operator::Div => self.eval(*val.left)?.div(&self.eval(*val.right)?)?,
-
-                ast::Operator::Mod => self.eval(*val.left)?.modulo(&self.eval(*val.right)?)?,
-
-                ast::Operator::IntDiv => self.eval(*val.left)?.int_div(&self.eval(*val.right)?)?,
-                ast::Operator::Pow => self.eval(*val.left)?.pow(&self.eval(*val.right)?)?,
-
-                ast::Operator::As => self.eval(*val.left)?.as_cast(*val.right)?,
-
-                ast::Operator::BitShiftL => {
-                    self.eval(*val.left)?.bitshift_l(&self.eval(*val.right)?)?
-                }
-                ast::Operator::BitShiftR => {
-                    self.eval(*val.left)?.bitshift_r(&self.eval(*val.right)?)?
-                }
-
-                ast::Operator::LNot => self.eval(*val.left)?.lnot()?,
-                ast::Operator::LOr => self.eval(*val.left)?.lor(&self.eval(*val.right)?)?,
-                ast::Operator::LAnd => self.eval(*val.left)?.land(&self.eval(*val.right)?)?,
-                
-                ast::Operator::BOr => self.eval(*val.left)?.bor(&self.eval(*val.right)?)?,
-                ast::Operator::BAnd => self.eval(*val.left)?.band(&self.eval(*val.right)?)?,
-                ast::Operator::BXor => self.eval(*val.left)?.bxor(&self.eval(*val.right)?)?,
-
-                ast::Operator::NEql => self.eval(*val.left)?.neql(&self.eval(*val.right)?)?,
-                ast::Operator::Eql => self.eval(*val.left)?.eql(&self.eval(*val.right)?)?,
-
-                ast::Operator::LT => self.eval(*val.left)?.lt(&self.eval(*val.right)?)?,
-                ast::Operator::LE => self.eval(*val.left)?.lte(&self.eval(*val.right)?)?,
-                ast::Operator::GT => self.eval(*val.left)?.gt(&self.eval(*val.right)?)?,
-                ast::Operator::GE => self.eval(*val.left)?.gte(&self.eval(*val.right)?)?,
+            ExpressionKind::Imaginary(val) => EE::new(
+                Complex(Complex64::new(
+                    0.0,
+                    match lexer::parse_float(val) {
+                        Ok(val) => val,
+                        Err(why) => {
+                            return Err(Error::new(
+                                format!("error converting `{}i` to complex: {}", val, why),
+                                ErrorType::RuntimeError,
+                                ast.pos,
+                            ))
+                        }
+                    },
+                )),
+                ast.pos,
+            ),
 
-                _ => {
+            ExpressionKind::Date(val) => match datetime::parse_date(val) {
+                Some(val) => EE::new(Date(val), ast.pos),
+                None => {
                     return Err(Error::new(
-                        format!("infix {} not implemented yet", val.op),
-                        ErrorType::TypeError,
+                        format!("`{}` is not a valid date", val),
+                        ErrorType::RuntimeError,
                         ast.pos,
                     ))
                 }
             },
 
-            ExpressionKind::PrefixOp(val) => match val.op {
-                ast::Operator::Sub => self.eval(*val.value)?.neg()?,
-                ast::Operator::Add => self.eval(*val.value)?.pos()?,
-                ast::Operator::BNot => self.eval(*val.value)?.bnot()?,
-                ast::Operator::LNot => self.eval(*val.value)?.lnot()?,
-                _ => {
+            ExpressionKind::DateTime(val) => match datetime::parse_datetime(val) {
+                Some(val) => EE::new(DateTime(val), ast.pos),
+                None => {
                     return Err(Error::new(
-                        format!("prefix {} not implemented yet", val.op),
-                        ErrorType::TypeError,
+                        format!("`{}` is not a valid date and time", val),
+                        ErrorType::RuntimeError,
                         ast.pos,
                     ))
                 }
             },
 
-            ExpressionKind::Ident(val) => match self.symbtab.get(val) {
-                Some(val) => *val,
+            ExpressionKind::Duration(val) => match datetime::parse_duration(val) {
+                Some(val) => EE::new(Duration(val), ast.pos),
                 None => {
                     return Err(Error::new(
-                        format!("no variable `{}` found", val),
+                        format!("duration `{}` is out of range", val),
                         ErrorType::RuntimeError,
                         ast.pos,
                     ))
                 }
             },
-        })
+
+            ExpressionKind::Str(val) => EE::new(text(lexer::unescape(val), ast.pos)?, ast.pos),
+
+            // Where all the magic happens
+            ExpressionKind::InfixOp(infix) => self.infix(infix, ast.pos)?,
+            ExpressionKind::PrefixOp(prefix) => self.prefix(prefix, ast.pos)?,
+
+            ExpressionKind::Call(call) => self.call(call, ast.pos)?,
+            ExpressionKind::If(cond) => self.conditional(cond, ast.pos)?,
+
+            ExpressionKind::List(elements) => EE::new(
+                List(self.eval_args(elements)?.into_iter().map(|item| item.value).collect()),
+                ast.pos,
+            ),
+            ExpressionKind::Index(index) => self.index(index, ast.pos)?,
+
+            ExpressionKind::Lambda(lambda) => {
+                let captured = self.scopes.last().cloned().unwrap_or_default();
+                self.functions.push(Closure {
+                    params: lambda.params.clone(),
+                    body: Rc::clone(&lambda.body),
+                    captured,
+                });
+                EE::new(Function(self.functions.len() - 1), ast.pos)
+            }
+
+            ExpressionKind::Ident(val) => match self.lookup(val) {
+                Some(val) => EE::new(val.value.clone(), ast.pos),
+                // Variables shadow units, so `m` is a metre unless assigned
+                None => match units::Quantity::of_unit(val) {
+                    Some(unit) => EE::new(Quantity(unit), ast.pos),
+                    None => {
+                        return Err(Error::new(
+                            format!("no variable `{}` found", val),
+                            ErrorType::RuntimeError,
+                            ast.pos,
+                        ))
+                    }
+                },
+            },
+        };
+
+        self.limit(value)
     }
 }
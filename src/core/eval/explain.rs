@@ -0,0 +1,91 @@
+use crate::core::eval::ast::{self, ExpressionKind, StatementKind, Subscript};
+use crate::core::eval::error::*;
+use crate::core::eval::exec::Executer;
+use crate::core::eval::options::Options;
+
+/// Indentation for each level of the tree
+const INDENT: &str = "  ";
+
+/// Every statement fully bracketed, followed by a tree of its parts and the type each
+/// evaluates to, `?` where it has none, as for `x` in `diff(x**2, x)`
+pub(crate) fn explain(program: &[ast::Statement<'_>], options: Options) -> Result<String, Error> {
+    let max_output = options.max_output;
+    let mut executer = Executer::with_options(options);
+    let mut lines = Vec::new();
+    for statement in program {
+        match &statement.stmt {
+            StatementKind::Let(assign) | StatementKind::Assign(assign) => {
+                lines.push(statement.to_string());
+                annotate(&mut executer, &assign.value, 1, &mut lines)?;
+                // Leave the variable unset when its value fails, the rest is still worth showing
+                match executer.assign(assign) {
+                    Err(why) if why.error_type() == ErrorType::LimitError => return Err(why),
+                    _ => {}
+                }
+            }
+            StatementKind::Expr(expr) => annotate(&mut executer, expr, 0, &mut lines)?,
+        }
+    }
+
+    let explained = lines.join("\n");
+    if explained.chars().count() > max_output {
+        let end = program.last().map_or(0, |statement| statement.pos.end);
+        return Err(Error::new(
+            format!("explanation is longer than the limit of {} characters", max_output),
+            ErrorType::LimitError,
+            Pos::new(0, end),
+        ));
+    }
+
+    Ok(explained)
+}
+
+/// Add a line for `ast` and, indented under it, one for each of its parts
+fn annotate<'a>(
+    executer: &mut Executer<'a>,
+    ast: &ast::Expression<'a>,
+    depth: usize,
+    lines: &mut Vec<String>,
+) -> Result<(), Error> {
+    // Only the whole expression can end in an output conversion such as `as hex`
+    let value = if depth == 0 { executer.result(ast) } else { executer.eval(ast) };
+    let kind = match value {
+        Ok(value) => value.value.display_type().trim_matches('`'),
+        // Running out of steps or time stops the whole explanation
+        Err(why) if why.error_type() == ErrorType::LimitError => return Err(why),
+        Err(_) => "?",
+    };
+    lines.push(format!("{}{}: {}", INDENT.repeat(depth), ast, kind));
+
+    for part in parts(ast) {
+        annotate(executer, part, depth + 1, lines)?;
+    }
+    Ok(())
+}
+
+/// The expressions `ast` is made of, leaving out names that aren't values: the function
+/// of `sin(x)`, the target of `as km` and the body of a lambda, whose parameters have no value
+fn parts<'e, 'a>(ast: &'e ast::Expression<'a>) -> Vec<&'e ast::Expression<'a>> {
+    match &ast.expr {
+        ExpressionKind::PrefixOp(prefix) => vec![&prefix.value],
+        ExpressionKind::InfixOp(infix) if infix.op == ast::Operator::As => vec![&infix.left],
+        ExpressionKind::InfixOp(infix) => vec![&infix.left, &infix.right],
+        ExpressionKind::Call(call) => {
+            let func = match call.func.expr {
+                ExpressionKind::Ident(_) => None,
+                _ => Some(&*call.func),
+            };
+            func.into_iter().chain(&call.args).collect()
+        }
+        ExpressionKind::If(cond) => vec![&cond.cond, &cond.then, &cond.otherwise],
+        ExpressionKind::List(items) => items.iter().collect(),
+        ExpressionKind::Index(index) => match &index.subscript {
+            Subscript::Item(item) => vec![&index.value, item],
+            Subscript::Slice(start, end) => std::iter::once(&*index.value)
+                .chain(start.as_deref())
+                .chain(end.as_deref())
+                .collect(),
+        },
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,194 @@
+use num::bigint::{BigInt, BigUint};
+use num::complex::Complex64;
+use num::traits::{Signed, ToPrimitive};
+
+use crate::core::eval::datetime;
+use crate::core::eval::error::*;
+use crate::core::eval::exec::{ExecutionExpr, ExecutionExpr::*};
+use crate::core::eval::matrix;
+use crate::core::eval::options::{Format, Notation, Radix, MAX_PRECISION};
+
+/// Write a value the way `format` asks
+pub(crate) fn show(value: &ExecutionExpr, format: &Format) -> String {
+    write(value, format, true)
+}
+
+/// Write a value on one line, matrices as lists of rows, to sit inside an expression
+pub(crate) fn inline(value: &ExecutionExpr, format: &Format) -> String {
+    write(value, format, false)
+}
+
+fn write(value: &ExecutionExpr, format: &Format, grid: bool) -> String {
+    match value {
+        Integer(val) => integer(val, format),
+        // Asking for figures or an exponent asks for a decimal
+        Rational(val) if format.notation == Notation::Scientific || format.precision.is_some() => {
+            float(val.to_f64().unwrap_or(f64::NAN), format)
+        }
+        Rational(val) => val.to_string(),
+        Float(val) => float(*val, format),
+        Complex(val) => complex(val, format),
+        Quantity(val) => {
+            let (value, unit) = val.shown();
+            format!("{} {}", float(value, format), unit)
+        }
+        Date(val) => val.to_string(),
+        DateTime(val) => datetime::fmt_datetime(val),
+        Duration(val) => datetime::fmt_duration(val),
+        Bool(val) => val.to_string(),
+        Str(val) => format!("{:?}", val),
+        List(_) if grid && matrix::is_matrix(value) => {
+            matrix::fmt_grid(value, |cell| show(cell, format)).unwrap_or_default()
+        }
+        List(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(|item| write(item, format, grid))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Function(_) => "<function>".to_string(),
+        Symbolic(val) => val.to_string(),
+    }
+}
+
+/// Whether an output conversion such as `as hex` has anything to write in `value`
+pub(crate) fn supports(value: &ExecutionExpr, target: &str) -> bool {
+    match value {
+        List(items) => items.iter().all(|item| supports(item, target)),
+        Integer(_) => true,
+        Rational(_) | Float(_) | Complex(_) | Quantity(_) => target == "sci",
+        _ => false,
+    }
+}
+
+fn integer(val: &BigInt, format: &Format) -> String {
+    let (prefix, radix) = match format.radix {
+        Radix::Decimal if format.notation == Notation::Scientific => {
+            return scientific(val, format.precision)
+        }
+        Radix::Decimal => {
+            return match format.precision {
+                Some(figs) => round_integer(val, figs).to_string(),
+                None => val.to_string(),
+            }
+        }
+        Radix::Hex => ("0x", 16),
+        Radix::Oct => ("0o", 8),
+        Radix::Bin => ("0b", 2),
+    };
+
+    let sign = if val.is_negative() { "-" } else { "" };
+    format!("{}{}{}", sign, prefix, val.magnitude().to_str_radix(radix))
+}
+
+/// Round to `figs` significant figures, `123456` to 2 is `120000`
+fn round_integer(val: &BigInt, figs: usize) -> BigInt {
+    let len = val.magnitude().to_string().len();
+    if figs >= len {
+        return val.clone();
+    }
+
+    let scale = BigUint::from(10u8).pow((len - figs) as u32);
+    let rounded = (val.magnitude() + &scale / 2u8) / &scale * &scale;
+    BigInt::from_biguint(val.sign(), rounded)
+}
+
+/// `12345` as `1.2345e4`, from the digits so integers past the range of a float still fit
+fn scientific(val: &BigInt, precision: Option<usize>) -> String {
+    let mut digits = val.magnitude().to_string();
+    let mut exp = digits.len() - 1;
+    match precision {
+        Some(figs) if figs < digits.len() => {
+            let scale = BigUint::from(10u8).pow((digits.len() - figs) as u32);
+            let rounded = (val.magnitude() + &scale / 2u8) / scale;
+            digits = rounded.to_string();
+            // Rounding `999` up carries into a new digit
+            if digits.len() > figs {
+                digits.truncate(figs);
+                exp += 1;
+            }
+        }
+        Some(figs) => digits.push_str(&"0".repeat(figs - digits.len())),
+        None => {
+            let len = digits.trim_end_matches('0').len().max(1);
+            digits.truncate(len);
+        }
+    }
+
+    let sign = if val.is_negative() { "-" } else { "" };
+    match digits.split_at(1) {
+        (first, "") => format!("{}{}e{}", sign, first, exp),
+        (first, rest) => format!("{}{}.{}e{}", sign, first, rest, exp),
+    }
+}
+
+fn float(val: f64, format: &Format) -> String {
+    match (format.notation, format.precision) {
+        (Notation::Scientific, Some(figs)) => format!("{:.*e}", figs - 1, val),
+        (Notation::Scientific, None) => format!("{:e}", val),
+        (Notation::Plain, Some(figs)) => round(val, figs).to_string(),
+        (Notation::Plain, None) => val.to_string(),
+    }
+}
+
+/// Round to `figs` significant figures
+fn round(val: f64, figs: usize) -> f64 {
+    if !val.is_finite() || val == 0.0 {
+        return val;
+    }
+
+    format!("{:.*e}", figs - 1, val).parse().unwrap_or(val)
+}
+
+/// Format as `a+bi`, dropping whichever part is zero
+fn complex(val: &Complex64, format: &Format) -> String {
+    if val.im == 0.0 {
+        float(val.re, format)
+    } else if val.re == 0.0 {
+        format!("{}i", float(val.im, format))
+    } else if val.im < 0.0 {
+        format!("{}-{}i", float(val.re, format), float(-val.im, format))
+    } else {
+        format!("{}+{}i", float(val.re, format), float(val.im, format))
+    }
+}
+
+/// Split a trailing `round to 3 sig figs` off the input, returning the rest and the figures
+pub(crate) fn sig_figs(src: &str) -> Result<(&str, Option<usize>), Error> {
+    // Lowercasing ASCII keeps every byte where it was
+    let lower = src.trim_end().to_ascii_lowercase();
+    let rest = ["sig figs", "sig fig", "significant figures", "significant figure"]
+        .iter()
+        .find_map(|suffix| lower.strip_suffix(suffix))
+        .map(str::trim_end);
+    let rest = match rest {
+        Some(rest) => rest,
+        None => return Ok((src, None)),
+    };
+
+    let start = rest.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let number = &rest[start..];
+    let before = rest[..start]
+        .strip_suffix(char::is_whitespace)
+        .map(str::trim_end)
+        .and_then(|rest| rest.strip_suffix("to"))
+        .and_then(|rest| rest.strip_suffix(char::is_whitespace))
+        .map(str::trim_end)
+        .and_then(|rest| rest.strip_suffix("round"))
+        .filter(|rest| rest.is_empty() || rest.ends_with(|c: char| c.is_whitespace() || c == ';'));
+    let before = match before {
+        Some(before) if !number.is_empty() => before,
+        _ => return Ok((src, None)),
+    };
+
+    match number.parse() {
+        Ok(figs) if (1..=MAX_PRECISION).contains(&figs) => Ok((&src[..before.len()], Some(figs))),
+        _ => Err(Error::new(
+            format!("can only round to between 1 and {} significant figures", MAX_PRECISION),
+            ErrorType::RuntimeError,
+            Pos::new(start, start + number.len()),
+        )),
+    }
+}
@@ -1,6 +1,12 @@
 use crate::core::eval::ast;
+use crate::core::eval::datetime;
 use crate::core::eval::error::*;
 
+use std::num::ParseFloatError;
+
+use num::bigint::{BigInt, ParseBigIntError};
+use num::Num;
+
 pub(crate) struct Lexer<'a> {
     chars_peek: std::iter::Peekable<std::str::Chars<'a>>,
     file_contents: &'a str,
@@ -34,6 +40,41 @@ pub fn is_whitespace(c: char) -> bool {
     }
 }
 
+/// The text of a string token, with its escapes replaced
+pub(crate) fn unescape(raw: &str) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        text.push(match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(c) => c,
+                None => break,
+            },
+            c => c,
+        });
+    }
+    text
+}
+
+/// The value of an integer token, such as `1_000` or `0xff`
+pub(crate) fn parse_integer(raw: &str) -> Result<BigInt, ParseBigIntError> {
+    let digits = raw.replace('_', "");
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") | Some("0X") => (16, &digits[2..]),
+        Some("0o") | Some("0O") => (8, &digits[2..]),
+        Some("0b") | Some("0B") => (2, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+    BigInt::from_str_radix(digits, radix)
+}
+
+/// The value of a float token, such as `1_000.5` or `.5e-3`
+pub(crate) fn parse_float(raw: &str) -> Result<f64, ParseFloatError> {
+    raw.replace('_', "").parse()
+}
+
 /// Check if ID is continue
 fn is_id_continue(c: char) -> bool {
     ('a' <= c && c <= 'z') || ('A' <= c && c <= 'Z') || ('0' <= c && c <= '9') || c == '_'
@@ -45,14 +86,14 @@ fn is_id_start(c: char) -> bool {
 }
 
 macro_rules! double_match {
-    ($tokens: ident, $self: ident, $first: expr, $($second: expr => $op_type: expr),*) => {
+    ($tokens: ident, $errors: ident, $self: ident, $first: expr, $($second: expr => $op_type: expr),*) => {
         match $self.peek_char() {
             $(
                 $second => {
                     $tokens.push($self.double_op($op_type));
                 }
              )*
-            _ => { $tokens.push($self.new_literal($first)); }
+            _ => { $tokens.extend($self.new_literal($first, &mut $errors)); }
         }
     }
 }
@@ -68,8 +109,10 @@ impl<'a> Lexer<'a> {
 
     /// Advances in character stream
     fn bump_char(&mut self) -> char {
-        self.pos += 1;
-        self.chars_peek.next().unwrap_or(EOF_CHAR)
+        let c = self.chars_peek.next().unwrap_or(EOF_CHAR);
+        // Positions are byte offsets, so tokens can be sliced straight out of the input
+        self.pos += c.len_utf8();
+        c
     }
 
     /// Doesn't advance
@@ -86,49 +129,275 @@ impl<'a> Lexer<'a> {
         )
     }
 
-    fn number_err(&mut self, next_len: &mut usize, err_val: &'static str) -> Result<(), Error> {
-        let inc = self.len_eat_while(|c| '0' <= c && c <= '9') + 1;
-        *next_len += inc;
-        if inc == 1 {
-            Err(Error::new(
-                format!("expected number after `{}`", err_val),
+    /// Skip the rest of a malformed literal, so it isn't lexed again as more tokens
+    fn malformed(&mut self, why: Error) -> Error {
+        self.len_eat_while(is_id_continue);
+        why
+    }
+
+    /// Digits of `radix`, with `_` allowed between two of them as in `1_000`, returning
+    /// how many digits were eaten
+    fn digits(&mut self, radix: u32) -> Result<usize, Error> {
+        let mut count = 0;
+        loop {
+            match self.peek_char() {
+                c if c.is_digit(radix) => {
+                    self.bump_char();
+                    count += 1;
+                }
+                '_' => {
+                    let after_digit = matches!(
+                        self.file_contents[..self.pos].chars().next_back(),
+                        Some(c) if c.is_digit(radix)
+                    );
+                    let mut ahead = self.chars_peek.clone();
+                    ahead.next();
+                    let before_digit = matches!(ahead.next(), Some(c) if c.is_digit(radix));
+
+                    if !after_digit || !before_digit {
+                        let why = Error::new(
+                            "a digit separator `_` must be between two digits".to_string(),
+                            ErrorType::LexError,
+                            Pos::new(self.pos, self.pos + 1),
+                        );
+                        return Err(self.malformed(why));
+                    }
+                    self.bump_char();
+                }
+                _ => return Ok(count),
+            }
+        }
+    }
+
+    /// Fail unless digits were found after `after`, as in `1e` or `1.`
+    fn expect_digits(&mut self, count: usize, after: &str) -> Result<(), Error> {
+        if count > 0 {
+            return Ok(());
+        }
+
+        let why = Error::new(
+            format!("expected number after `{}`", after),
+            ErrorType::LexError,
+            Pos::new(self.pos, self.pos + 1),
+        );
+        Err(self.malformed(why))
+    }
+
+    /// `0x1f`, `0b1010` or `0o17`, with the prefix already eaten
+    fn radix_number(&mut self, start: usize, radix: u32, name: &str) -> Result<ast::Token<'a>, Error> {
+        let count = self.digits(radix)?;
+        let next = self.peek_char();
+        if next == '.' {
+            let why = Error::new(
+                format!("{} literals have no fractional part", name),
                 ErrorType::LexError,
                 Pos::new(self.pos, self.pos + 1),
-            ))
-        } else {
-            Ok(())
+            );
+            self.bump_char();
+            return Err(self.malformed(why));
         }
+        // A digit outside the base, as the `2` of `0b102`
+        if next != EOF_CHAR && is_id_continue(next) {
+            let why = Error::new(
+                format!("invalid digit `{}` in {} literal", next, name),
+                ErrorType::LexError,
+                Pos::new(self.pos, self.pos + 1),
+            );
+            return Err(self.malformed(why));
+        }
+        if count == 0 {
+            return Err(Error::new(
+                format!("expected {} digits after `{}`", name, &self.file_contents[start..self.pos]),
+                ErrorType::LexError,
+                Pos::new(start, self.pos),
+            ));
+        }
+
+        Ok(self.crate_tok(ast::TokenType::Integer, self.pos - start))
     }
 
-    fn number(&mut self) -> Result<ast::Token<'a>, Error> {
-        let mut next_len = self.len_eat_while(|c| '0' <= c && c <= '9') + 1;
-        match self.peek_char() {
-            '.' => {
+    /// A number starting with `first`, which is a digit or the `.` of `.5`
+    fn number(&mut self, first: char) -> Result<ast::Token<'a>, Error> {
+        let start = self.pos - 1;
+        if first == '0' {
+            let radix = match self.peek_char() {
+                'x' | 'X' => Some((16, "hexadecimal")),
+                'o' | 'O' => Some((8, "octal")),
+                'b' | 'B' => Some((2, "binary")),
+                _ => None,
+            };
+            if let Some((radix, name)) = radix {
                 self.bump_char();
-                self.number_err(&mut next_len, ".")?;
-
-                // Decimal
-                let next_tok = self.peek_char();
-                match next_tok {
-                    'e' | 'E' => {
-                        // 10.124E2193
-                        self.bump_char();
-                        self.number_err(&mut next_len, if next_tok == 'e' { "e" } else { "E" })?;
+                return self.radix_number(start, radix, name);
+            }
+        }
 
-                        Ok(self.crate_tok(ast::TokenType::Float, next_len))
-                    }
-                    _ => Ok(self.crate_tok(ast::TokenType::Float, next_len)),
-                }
+        let mut tok_type = ast::TokenType::Integer;
+        if first != '.' {
+            self.digits(10)?;
+        }
+        if first == '.' || self.peek_char() == '.' {
+            if first != '.' {
+                self.bump_char();
             }
-            'e' | 'E' => {
-                // 10E100
+            let count = self.digits(10)?;
+            self.expect_digits(count, ".")?;
+            tok_type = ast::TokenType::Float;
+        }
+
+        if matches!(self.peek_char(), 'e' | 'E') {
+            // 10.124E2193, 1e-5
+            let exp_start = self.pos;
+            self.bump_char();
+            if matches!(self.peek_char(), '+' | '-') {
                 self.bump_char();
-                self.number_err(&mut next_len, ".")?;
+            }
+            let count = self.digits(10)?;
+            let contents = self.file_contents;
+            self.expect_digits(count, &contents[exp_start..self.pos])?;
+            tok_type = ast::TokenType::Float;
+        }
+
+        Ok(self.crate_tok(tok_type, self.pos - start))
+    }
+
+    /// `2i` and `1.5i` are imaginary literals, but `2in` is not
+    fn imaginary(&mut self, tok: ast::Token<'a>) -> ast::Token<'a> {
+        let mut ahead = self.chars_peek.clone();
+        if ahead.next() != Some('i') || matches!(ahead.next(), Some(c) if is_id_continue(c)) {
+            return tok;
+        }
+
+        self.bump_char();
+        ast::Token::new(ast::TokenType::Imaginary, tok.value, tok.pos.start, self.pos)
+    }
+
+    /// `2026-12-25`, optionally followed by a time such as `T18:30` or `T18:30:15`
+    fn date(&mut self) -> Option<ast::Token<'a>> {
+        let start = self.pos - 1;
+        let rest = &self.file_contents.as_bytes()[start..];
+        let fits = |pattern: &str| {
+            pattern.len() <= rest.len()
+                && pattern.bytes().zip(rest).all(|(expected, c)| match expected {
+                    b'0' => c.is_ascii_digit(),
+                    _ => expected == *c,
+                })
+        };
+
+        let (len, tok_type) = if fits("0000-00-00T00:00:00") {
+            (19, ast::TokenType::DateTime)
+        } else if fits("0000-00-00T00:00") {
+            (16, ast::TokenType::DateTime)
+        } else if fits("0000-00-00") {
+            (10, ast::TokenType::Date)
+        } else {
+            return None;
+        };
+
+        if matches!(rest.get(len), Some(c) if is_id_continue(*c as char)) {
+            return None;
+        }
+
+        for _ in 1..len {
+            self.bump_char();
+        }
+        Some(self.crate_tok(tok_type, len))
+    }
+
+    /// `3h30m`, `1.5h` and `2d` are durations, but `2m` alone is left to mean metres
+    /// and `5ms` to be a unit
+    fn duration(&mut self, tok: ast::Token<'a>) -> ast::Token<'a> {
+        // Only plain digits, `1_000h` and `1e3h` stay quantities in hours
+        let plain = tok.value.bytes().all(|c| c.is_ascii_digit() || c == b'.');
+        if !matches!(tok.tok_type, ast::TokenType::Integer | ast::TokenType::Float) || !plain {
+            return tok;
+        }
 
-                Ok(self.crate_tok(ast::TokenType::Float, next_len))
+        let rest = &self.file_contents.as_bytes()[self.pos..];
+        let (mut len, mut parts, mut end) = (0, 0, 0);
+        while matches!(rest.get(len), Some(c) if datetime::is_duration_suffix(*c as char)) {
+            len += 1;
+            parts += 1;
+            end = len;
+
+            let digits = rest[len..].iter().take_while(|c| c.is_ascii_digit()).count();
+            if digits == 0 {
+                break;
             }
-            _ => Ok(self.crate_tok(ast::TokenType::Integer, next_len)),
+            len += digits;
+        }
+
+        if parts == 0
+            || (parts == 1 && rest[0] == b'm')
+            || matches!(rest.get(end), Some(c) if is_id_continue(*c as char))
+        {
+            return tok;
+        }
+
+        for _ in 0..end {
+            self.bump_char();
         }
+        ast::Token::new(
+            ast::TokenType::Duration,
+            &self.file_contents[tok.pos.start..self.pos],
+            tok.pos.start,
+            self.pos,
+        )
+    }
+
+    /// `"..."` with `\"`, `\\`, `\n` and `\t` escapes, recording any bad escapes or a missing
+    /// closing quote
+    fn string(&mut self, errors: &mut Vec<Error>) -> Option<ast::Token<'a>> {
+        let start = self.pos - 1;
+        loop {
+            match self.peek_char() {
+                EOF_CHAR => {
+                    errors.push(
+                        Error::new(
+                            "unterminated string".to_string(),
+                            ErrorType::LexError,
+                            Pos::new(start, self.pos),
+                        )
+                        .with_help("close it with `\"`".to_string()),
+                    );
+                    return None;
+                }
+                '"' => {
+                    self.bump_char();
+                    break;
+                }
+                '\\' => {
+                    self.bump_char();
+                    match self.peek_char() {
+                        EOF_CHAR => {}
+                        '"' | '\\' | 'n' | 't' => {
+                            self.bump_char();
+                        }
+                        c => {
+                            self.bump_char();
+                            errors.push(
+                                Error::new(
+                                    format!("unknown escape `\\{}`", c),
+                                    ErrorType::LexError,
+                                    Pos::new(self.pos - c.len_utf8() - 1, self.pos),
+                                )
+                                .with_help("write `\\\\` for a backslash".to_string()),
+                            );
+                        }
+                    }
+                }
+                _ => {
+                    self.bump_char();
+                }
+            }
+        }
+
+        Some(ast::Token::new(
+            ast::TokenType::Str,
+            &self.file_contents[start + 1..self.pos - 1],
+            start,
+            self.pos,
+        ))
     }
 
     fn identifier(&mut self) -> ast::Token<'a> {
@@ -137,6 +406,10 @@ impl<'a> Lexer<'a> {
         ast::Token::new(
             match ident {
                 "as" => ast::TokenType::Operator(ast::Operator::As),
+                "let" => ast::TokenType::Let,
+                "if" => ast::TokenType::If,
+                "then" => ast::TokenType::Then,
+                "else" => ast::TokenType::Else,
                 "true" => ast::TokenType::TRUE,
                 "false" => ast::TokenType::FALSE,
                 _ => ast::TokenType::Identifier,
@@ -163,17 +436,25 @@ impl<'a> Lexer<'a> {
     }
 
     /// One character literals
-    fn new_literal(&mut self, c: char) -> ast::Token<'a> {
-        ast::Token::new(
+    fn new_literal(&mut self, c: char, errors: &mut Vec<Error>) -> Option<ast::Token<'a>> {
+        let start = self.pos - c.len_utf8();
+        Some(ast::Token::new(
             match c {
                 '(' => ast::TokenType::LP,
                 ')' => ast::TokenType::RP,
+                '[' => ast::TokenType::LB,
+                ']' => ast::TokenType::RB,
+                ':' => ast::TokenType::Colon,
+                ',' => ast::TokenType::Comma,
+                '=' => ast::TokenType::Assign,
+                ';' => ast::TokenType::Semicolon,
 
                 _ => ast::TokenType::Operator(match c {
                     '+' => ast::Operator::Add,
                     '-' => ast::Operator::Sub,
                     '/' => ast::Operator::Div,
                     '*' => ast::Operator::Mul,
+                    '@' => ast::Operator::MatMul,
                     '%' => ast::Operator::Mod,
 
                     '&' => ast::Operator::BAnd,
@@ -186,13 +467,20 @@ impl<'a> Lexer<'a> {
 
                     '^' => ast::Operator::BXor,
 
-                    _ => panic!("Bad operator given!"),
+                    _ => {
+                        errors.push(Error::new(
+                            format!("unexpected character `{}`", c),
+                            ErrorType::LexError,
+                            Pos::new(start, self.pos),
+                        ));
+                        return None;
+                    }
                 }),
             },
             "",
-            self.pos - 1,
+            start,
             self.pos,
-        )
+        ))
     }
 
     fn double_op(&mut self, tok_type: ast::Operator) -> ast::Token<'a> {
@@ -206,20 +494,62 @@ impl<'a> Lexer<'a> {
         tok
     }
 
-    fn e(&self, current: char) -> Result<(), Error> {
-        Err(Error::new(
-            format!("unrecognized character {}", current),
-            ErrorType::LexError,
-            Pos::new(self.pos, self.pos + 1),
-        ))
+    /// One error for each run of characters that start no token, such as `×` or `“”`
+    fn unrecognized(&self, runs: Vec<Pos>) -> Vec<Error> {
+        runs.into_iter()
+            .map(|run| {
+                let chars = &self.file_contents[run.start..run.end];
+                Error::new(
+                    if chars.chars().count() == 1 {
+                        format!("unrecognized character `{}`", chars)
+                    } else {
+                        format!("unrecognized characters `{}`", chars)
+                    },
+                    ErrorType::LexError,
+                    run,
+                )
+            })
+            .collect()
     }
 
+    /// Split the input into tokens, failing on the first error
     pub(crate) fn tokenize(&mut self) -> Result<Vec<ast::Token<'a>>, Error> {
+        let (tokens, mut errors) = self.tokenize_all();
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Split the input into tokens, skipping past errors so that all of them are found
+    pub(crate) fn tokenize_all(&mut self) -> (Vec<ast::Token<'a>>, Vec<Error>) {
         let mut tokens: Vec<ast::Token<'a>> = Vec::new();
+        let mut errors = Vec::new();
+        let mut runs: Vec<Pos> = Vec::new();
         let mut current = self.bump_char();
         while current != EOF_CHAR {
             match current {
-                '0'..='9' => tokens.push(self.number()?),
+                '.' if self.peek_char().is_ascii_digit() => match self.number(current) {
+                    Ok(tok) => {
+                        let tok = self.duration(tok);
+                        tokens.push(self.imaginary(tok));
+                    }
+                    Err(why) => errors.push(why),
+                },
+
+                '0'..='9' => match self.date() {
+                    Some(tok) => tokens.push(tok),
+                    None => match self.number(current) {
+                        Ok(tok) => {
+                            let tok = self.duration(tok);
+                            tokens.push(self.imaginary(tok));
+                        }
+                        Err(why) => errors.push(why),
+                    },
+                },
+
+                '"' => tokens.extend(self.string(&mut errors)),
 
                 c if is_whitespace(c) => {
                     // Character is whitespace
@@ -231,36 +561,36 @@ impl<'a> Lexer<'a> {
                     tokens.push(self.identifier());
                 }
 
-                '+' | '-' | '~' | '^' | '%' | '(' | ')' => {
-                    tokens.push(self.new_literal(current));
+                '+' | '-' | '~' | '^' | '%' | '@' | '(' | ')' | '[' | ']' | ':' | ',' | ';' => {
+                    tokens.extend(self.new_literal(current, &mut errors));
                 }
 
                 '!' => double_match! {
-                    tokens, self,
+                    tokens, errors, self,
                     '!',
                     '=' => ast::Operator::NEql
                 },
 
                 '|' => double_match! {
-                    tokens, self,
+                    tokens, errors, self,
                     '|',
                     '|' => ast::Operator::LOr
                 },
 
                 '&' => double_match! {
-                    tokens, self,
+                    tokens, errors, self,
                     '&',
                     '&' => ast::Operator::LAnd
                 },
 
                 '*' => double_match! {
-                    tokens, self,
+                    tokens, errors, self,
                     '*',
                     '*' => ast::Operator::Pow
                 },
 
                 '/' => double_match! {
-                    tokens, self,
+                    tokens, errors, self,
                     '/',
                     '/' => ast::Operator::IntDiv
                 },
@@ -269,31 +599,39 @@ impl<'a> Lexer<'a> {
                     '=' => {
                         tokens.push(self.double_op(ast::Operator::Eql));
                     }
-                    _ => self.e(current)?,
+                    _ => tokens.extend(self.new_literal(current, &mut errors)),
                 },
 
                 '>' => double_match! {
-                    tokens, self,
+                    tokens, errors, self,
                     '>',
                     '=' => ast::Operator::GE,
                     '>' => ast::Operator::BitShiftR
                 },
 
                 '<' => double_match! {
-                    tokens, self,
+                    tokens, errors, self,
                     '<',
                     '=' => ast::Operator::LE,
                     '<' => ast::Operator::BitShiftL
                 },
 
-                _ => self.e(current)?,
+                _ => {
+                    let start = self.pos - current.len_utf8();
+                    match runs.last_mut() {
+                        Some(run) if run.end == start => run.end = self.pos,
+                        _ => runs.push(Pos::new(start, self.pos)),
+                    }
+                }
             }
             current = self.bump_char();
         }
 
         tokens.push(ast::Token::new(ast::TokenType::EOF, "", self.pos, self.pos));
 
-        Ok(tokens)
+        errors.extend(self.unrecognized(runs));
+        errors.sort_by_key(|why| why.position().start);
+        (tokens, errors)
     }
 }
 
@@ -485,3 +823,151 @@ fn integer_op() {
     assert_eq!(tokens[21].tok_type, ast::TokenType::LP);
     assert_eq!(tokens[22].tok_type, ast::TokenType::RP);
 }
+
+#[test]
+fn assignment() {
+    let tokens = Lexer::new("let x = 10; x == 10")
+        .tokenize()
+        .expect("Failed to parse");
+
+    assert_eq!(tokens[0].tok_type, ast::TokenType::Let);
+    assert_eq!(tokens[1].tok_type, ast::TokenType::Identifier);
+    assert_eq!(tokens[2].tok_type, ast::TokenType::Assign);
+    assert_eq!(tokens[3].value, "10");
+    assert_eq!(tokens[4].tok_type, ast::TokenType::Semicolon);
+    assert_eq!(tokens[5].value, "x");
+    assert_eq!(
+        tokens[6].tok_type,
+        ast::TokenType::Operator(ast::Operator::Eql)
+    );
+}
+
+#[test]
+fn call() {
+    let tokens = Lexer::new("log(8, 2)")
+        .tokenize()
+        .expect("Failed to parse");
+
+    assert_eq!(tokens[0].value, "log");
+    assert_eq!(tokens[1].tok_type, ast::TokenType::LP);
+    assert_eq!(tokens[2].value, "8");
+    assert_eq!(tokens[3].tok_type, ast::TokenType::Comma);
+    assert_eq!(tokens[4].value, "2");
+    assert_eq!(tokens[5].tok_type, ast::TokenType::RP);
+}
+
+#[test]
+fn imaginary() {
+    let tokens = Lexer::new("2i 1.5i 2 i 2in")
+        .tokenize()
+        .expect("Failed to parse");
+
+    assert_eq!(tokens[0].tok_type, ast::TokenType::Imaginary);
+    assert_eq!(tokens[0].value, "2");
+    assert_eq!(tokens[1].tok_type, ast::TokenType::Imaginary);
+    assert_eq!(tokens[1].value, "1.5");
+    assert_eq!(tokens[2].tok_type, ast::TokenType::Integer);
+    assert_eq!(tokens[3].tok_type, ast::TokenType::Identifier);
+    assert_eq!(tokens[4].tok_type, ast::TokenType::Integer);
+    assert_eq!(tokens[5].value, "in");
+}
+
+#[test]
+fn date() {
+    let tokens = Lexer::new("2026-12-25 - 2026-12-25T18:30")
+        .tokenize()
+        .expect("Failed to parse");
+
+    assert_eq!(tokens[0].tok_type, ast::TokenType::Date);
+    assert_eq!(tokens[0].value, "2026-12-25");
+    assert_eq!(tokens[2].tok_type, ast::TokenType::DateTime);
+    assert_eq!(tokens[2].value, "2026-12-25T18:30");
+}
+
+#[test]
+fn duration() {
+    let tokens = Lexer::new("3h30m 2m 5ms")
+        .tokenize()
+        .expect("Failed to parse");
+
+    assert_eq!(tokens[0].tok_type, ast::TokenType::Duration);
+    assert_eq!(tokens[0].value, "3h30m");
+    assert_eq!(tokens[1].tok_type, ast::TokenType::Integer);
+    assert_eq!(tokens[2].tok_type, ast::TokenType::Identifier);
+    assert_eq!(tokens[3].tok_type, ast::TokenType::Integer);
+    assert_eq!(tokens[4].value, "ms");
+
+    let tokens = Lexer::new("now + 30m 1.5h").tokenize().expect("Failed to parse");
+    assert_eq!(tokens[2].tok_type, ast::TokenType::Integer);
+    assert_eq!(tokens[3].value, "m");
+    assert_eq!(tokens[4].tok_type, ast::TokenType::Duration);
+    assert_eq!(tokens[4].value, "1.5h");
+}
+
+#[test]
+fn list() {
+    let tokens = Lexer::new("[1, 2][0:1]")
+        .tokenize()
+        .expect("Failed to parse");
+
+    assert_eq!(tokens[0].tok_type, ast::TokenType::LB);
+    assert_eq!(tokens[2].tok_type, ast::TokenType::Comma);
+    assert_eq!(tokens[4].tok_type, ast::TokenType::RB);
+    assert_eq!(tokens[7].tok_type, ast::TokenType::Colon);
+    assert_eq!(tokens[9].tok_type, ast::TokenType::RB);
+}
+
+#[test]
+fn recover() {
+    let (tokens, errors) = Lexer::new("2 × 3 $$ 1e").tokenize_all();
+
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[1].value, "3");
+    assert_eq!(tokens[1].pos, Pos::new(5, 6));
+    assert_eq!(
+        errors.iter().map(|why| why.position()).collect::<Vec<_>>(),
+        vec![Pos::new(2, 4), Pos::new(7, 9), Pos::new(12, 13)]
+    );
+}
+
+#[test]
+fn string() {
+    let (tokens, errors) = Lexer::new(r#""a \"b\"" + "\q" + "c"#).tokenize_all();
+
+    assert_eq!(tokens[0].tok_type, ast::TokenType::Str);
+    assert_eq!(tokens[0].value, r#"a \"b\""#);
+    assert_eq!(tokens[0].pos, Pos::new(0, 9));
+    assert_eq!(unescape(tokens[0].value), r#"a "b""#);
+    assert_eq!(tokens.len(), 5);
+    assert_eq!(
+        errors.iter().map(|why| why.position()).collect::<Vec<_>>(),
+        vec![Pos::new(13, 15), Pos::new(19, 21)]
+    );
+}
+
+#[test]
+fn number() {
+    let tokens = Lexer::new("0xff 0b1010 1_000 1e-5 .5 2.5E+3")
+        .tokenize()
+        .expect("Failed to parse");
+
+    assert_eq!(tokens[0].tok_type, ast::TokenType::Integer);
+    assert_eq!(parse_integer(tokens[0].value), Ok(BigInt::from(255)));
+    assert_eq!(parse_integer(tokens[1].value), Ok(BigInt::from(10)));
+    assert_eq!(parse_integer(tokens[2].value), Ok(BigInt::from(1000)));
+    assert_eq!(tokens[3].tok_type, ast::TokenType::Float);
+    assert_eq!(tokens[3].value, "1e-5");
+    assert_eq!(tokens[4].value, ".5");
+    assert_eq!(parse_float(tokens[5].value), Ok(2500.0));
+}
+
+#[test]
+fn malformed_number() {
+    let (tokens, errors) = Lexer::new("0x 0b102 1__0 1e+ 0o1.5").tokenize_all();
+
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(
+        errors.iter().map(|why| why.position()).collect::<Vec<_>>(),
+        vec![Pos::new(0, 2), Pos::new(7, 8), Pos::new(10, 11), Pos::new(17, 18), Pos::new(21, 22)]
+    );
+}
@@ -0,0 +1,441 @@
+use std::time::Instant;
+
+use num::bigint::BigInt;
+use num::traits::Zero;
+
+use crate::core::eval::error::*;
+use crate::core::eval::exec::{out_of_time_err, ExecutionExpr, ExecutionExpr::*, EE};
+use crate::core::eval::options::Options;
+
+/// Share of the largest entry below which floats count as zero when eliminating
+const EPSILON: f64 = 1e-10;
+
+type Rows = Vec<Vec<EE>>;
+
+/// A list of equal length lists of scalars, such as `[[1, 2], [3, 4]]`
+pub(crate) fn is_matrix(value: &ExecutionExpr) -> bool {
+    let rows = match value {
+        List(rows) if !rows.is_empty() => rows,
+        _ => return false,
+    };
+
+    let width = match &rows[0] {
+        List(row) if !row.is_empty() => row.len(),
+        _ => return false,
+    };
+
+    rows.iter().all(|row| match row {
+        List(row) => row.len() == width && row.iter().all(|item| !matches!(item, List(_))),
+        _ => false,
+    })
+}
+
+/// Lay a matrix out as a grid with right aligned columns, writing each cell with `cell`
+pub(crate) fn fmt_grid<F: Fn(&ExecutionExpr) -> String>(value: &ExecutionExpr, cell: F) -> Option<String> {
+    if !is_matrix(value) {
+        return None;
+    }
+
+    let cells: Vec<Vec<String>> = match value {
+        List(rows) => rows
+            .iter()
+            .map(|row| match row {
+                List(row) => row.iter().map(&cell).collect(),
+                _ => Vec::new(),
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    let mut widths = vec![0; cells[0].len()];
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let lines: Vec<String> = cells
+        .iter()
+        .map(|row| {
+            let row: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+                .collect();
+            format!("[{}]", row.join("  "))
+        })
+        .collect();
+
+    Some(lines.join("\n"))
+}
+
+fn matrix_err(arg: &EE) -> Error {
+    Error::new(
+        format!(
+            "expected a matrix argument, such as `[[1, 2], [3, 4]]`, found {}",
+            arg.value.display_type()
+        ),
+        ErrorType::TypeError,
+        arg.pos,
+    )
+}
+
+/// Read the rows of a matrix argument
+fn rows(arg: &EE) -> Result<Rows, Error> {
+    match &arg.value {
+        List(rows) if is_matrix(&arg.value) => Ok(rows
+            .iter()
+            .map(|row| match row {
+                List(row) => row.iter().map(|item| EE::new(item.clone(), arg.pos)).collect(),
+                _ => Vec::new(),
+            })
+            .collect()),
+        _ => Err(matrix_err(arg)),
+    }
+}
+
+fn square(arg: &EE) -> Result<Rows, Error> {
+    let rows = rows(arg)?;
+    if rows.len() != rows[0].len() {
+        return Err(Error::new(
+            format!("expected a square matrix, found {}x{}", rows.len(), rows[0].len()),
+            ErrorType::TypeError,
+            arg.pos,
+        ));
+    }
+    Ok(rows)
+}
+
+fn to_value(rows: Rows) -> ExecutionExpr {
+    List(
+        rows.into_iter()
+            .map(|row| List(row.into_iter().map(|item| item.value).collect()))
+            .collect(),
+    )
+}
+
+/// How wide a number type is: exact, float or complex
+fn kind(rows: &[Vec<EE>]) -> Result<u8, Error> {
+    let mut kind = 0;
+    for item in rows.iter().flatten() {
+        kind = kind.max(match item.value {
+            Integer(_) | Rational(_) => 0,
+            Float(_) => 1,
+            Complex(_) => 2,
+            _ => {
+                return Err(Error::new(
+                    format!(
+                        "matrix entries must be numbers, found {}",
+                        item.value.display_type()
+                    ),
+                    ErrorType::TypeError,
+                    item.pos,
+                ))
+            }
+        });
+    }
+    Ok(kind)
+}
+
+/// Widen every entry to `kind`, so elimination never mixes types
+fn promote(rows: &mut Rows, kind: u8) {
+    if kind == 0 {
+        return;
+    }
+
+    for item in rows.iter_mut().flatten() {
+        if let Some(val) = item.value.to_complex() {
+            item.value = if kind == 2 { Complex(val) } else { Float(val.re) };
+        }
+    }
+}
+
+/// Exact zeros, or floats no larger than `tolerance`
+fn is_zero(value: &ExecutionExpr, tolerance: f64) -> bool {
+    match value {
+        Integer(val) => val.is_zero(),
+        Rational(val) => val.is_zero(),
+        Float(val) => val.abs() <= tolerance,
+        Complex(val) => val.norm() <= tolerance,
+        _ => false,
+    }
+}
+
+/// Size of an entry, to scale the tolerance to the matrix
+fn magnitude(value: &ExecutionExpr) -> f64 {
+    value.to_complex().map_or(0.0, |val| val.norm())
+}
+
+fn identity(size: usize, pos: Pos) -> Rows {
+    (0..size)
+        .map(|i| {
+            (0..size)
+                .map(|j| EE::new(Integer(BigInt::from((i == j) as u8)), pos))
+                .collect()
+        })
+        .collect()
+}
+
+/// Row reduce the first `cols` columns with partial pivoting, clearing above
+/// each pivot too and scaling it to one when `jordan` is set.
+/// Returns the pivot columns and whether the rows were swapped an odd number of times.
+/// Row reduce the first `cols` columns, giving up once the deadline in `options` passes
+fn eliminate(
+    rows: &mut Rows,
+    cols: usize,
+    jordan: bool,
+    pos: Pos,
+    options: &Options,
+) -> Result<(Vec<usize>, bool), Error> {
+    let mut pivots = Vec::new();
+    let mut odd_swaps = false;
+    // Relative to the largest entry, so `[[1e-11, 0.0], [0.0, 1e-11]]` isn't singular
+    let largest = rows
+        .iter()
+        .flat_map(|row| &row[..cols])
+        .fold(0.0, |largest: f64, item| largest.max(magnitude(&item.value)));
+    let tolerance = EPSILON * largest;
+
+    for col in 0..cols {
+        let row = pivots.len();
+        if row == rows.len() {
+            break;
+        }
+        if matches!(options.deadline, Some(end) if Instant::now() >= end) {
+            return Err(out_of_time_err(pos));
+        }
+
+        let mut best = row;
+        for i in row + 1..rows.len() {
+            if rows[i][col].pos()?.gt(&rows[best][col].pos()?)?.value == Bool(true) {
+                best = i;
+            }
+        }
+        if is_zero(&rows[best][col].value, tolerance) {
+            continue;
+        }
+        if best != row {
+            rows.swap(best, row);
+            odd_swaps = !odd_swaps;
+        }
+
+        if jordan {
+            let pivot = rows[row][col].clone();
+            for cell in rows[row][col..].iter_mut() {
+                *cell = cell.div(&pivot)?;
+            }
+        }
+
+        let pivot_row = rows[row].clone();
+        for (i, current) in rows.iter_mut().enumerate() {
+            if i == row || (i < row && !jordan) {
+                continue;
+            }
+
+            let factor = current[col].div(&pivot_row[col])?;
+            if is_zero(&factor.value, EPSILON) {
+                continue;
+            }
+            for (cell, pivot) in current[col..].iter_mut().zip(&pivot_row[col..]) {
+                *cell = cell.sub(&factor.mul(pivot)?)?;
+            }
+        }
+
+        pivots.push(col);
+    }
+
+    Ok((pivots, odd_swaps))
+}
+
+fn singular_err(pos: Pos) -> Error {
+    Error::new(
+        "matrix is singular".to_string(),
+        ErrorType::RuntimeError,
+        pos,
+    )
+}
+
+pub(crate) fn det(pos: Pos, args: &[EE], options: &Options) -> Result<ExecutionExpr, Error> {
+    let mut rows = square(&args[0])?;
+    let kind = kind(&rows)?;
+    promote(&mut rows, kind);
+
+    let size = rows.len();
+    let (pivots, odd_swaps) = eliminate(&mut rows, size, false, pos, options)?;
+    if pivots.len() < size {
+        return Ok(Integer(BigInt::zero()));
+    }
+
+    let mut det = rows[0][0].clone();
+    for (i, row) in rows.iter().enumerate().skip(1) {
+        det = det.mul(&row[i])?;
+    }
+    if odd_swaps {
+        det = det.neg()?;
+    }
+    Ok(det.value)
+}
+
+pub(crate) fn inv(pos: Pos, args: &[EE], options: &Options) -> Result<ExecutionExpr, Error> {
+    let rows = square(&args[0])?;
+    let size = rows.len();
+
+    let mut augmented: Rows = rows
+        .into_iter()
+        .zip(identity(size, args[0].pos))
+        .map(|(mut row, unit)| {
+            row.extend(unit);
+            row
+        })
+        .collect();
+    let kind = kind(&augmented)?;
+    promote(&mut augmented, kind);
+
+    let (pivots, _) = eliminate(&mut augmented, size, true, pos, options)?;
+    if pivots.len() < size {
+        return Err(singular_err(pos));
+    }
+
+    Ok(to_value(
+        augmented.into_iter().map(|row| row[size..].to_vec()).collect(),
+    ))
+}
+
+pub(crate) fn transpose(_: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let rows = rows(&args[0])?;
+    Ok(to_value(
+        (0..rows[0].len())
+            .map(|j| rows.iter().map(|row| row[j].clone()).collect())
+            .collect(),
+    ))
+}
+
+pub(crate) fn rank(pos: Pos, args: &[EE], options: &Options) -> Result<ExecutionExpr, Error> {
+    let mut rows = rows(&args[0])?;
+    let kind = kind(&rows)?;
+    promote(&mut rows, kind);
+
+    let width = rows[0].len();
+    let (pivots, _) = eliminate(&mut rows, width, false, pos, options)?;
+    Ok(Integer(BigInt::from(pivots.len())))
+}
+
+/// `solve(A, b)` finds `x` such that `A @ x == b`, for a list or matrix `b`
+pub(crate) fn solve(pos: Pos, args: &[EE], options: &Options) -> Result<ExecutionExpr, Error> {
+    let rows = square(&args[0])?;
+    let size = rows.len();
+
+    let (rhs, is_vector) = match &args[1].value {
+        List(items) if !is_matrix(&args[1].value) => (
+            items
+                .iter()
+                .map(|item| vec![EE::new(item.clone(), args[1].pos)])
+                .collect(),
+            true,
+        ),
+        _ => (self::rows(&args[1])?, false),
+    };
+
+    if rhs.len() != size {
+        return Err(Error::new(
+            format!(
+                "cannot solve a {}x{} system with {} right hand side rows",
+                size,
+                size,
+                rhs.len()
+            ),
+            ErrorType::TypeError,
+            args[1].pos,
+        ));
+    }
+
+    let mut augmented: Rows = rows
+        .into_iter()
+        .zip(rhs)
+        .map(|(mut row, rhs)| {
+            row.extend(rhs);
+            row
+        })
+        .collect();
+    let kind = kind(&augmented)?;
+    promote(&mut augmented, kind);
+
+    let (pivots, _) = eliminate(&mut augmented, size, true, pos, options)?;
+    if pivots.len() < size {
+        return Err(singular_err(pos));
+    }
+
+    let solution: Rows = augmented.into_iter().map(|row| row[size..].to_vec()).collect();
+    Ok(if is_vector {
+        List(solution.into_iter().map(|mut row| row.remove(0).value).collect())
+    } else {
+        to_value(solution)
+    })
+}
+
+/// Matrix product, treating a plain list as a row on the left or a column on the right
+pub(crate) fn matmul(left: &EE, right: &EE) -> Result<EE, Error> {
+    let pos = Pos::new(left.pos.start, right.pos.end);
+    let vector = |arg: &EE, column: bool| -> Result<Option<Rows>, Error> {
+        match &arg.value {
+            List(items) if !items.is_empty() && !is_matrix(&arg.value) => {
+                let items = items.iter().map(|item| EE::new(item.clone(), arg.pos));
+                Ok(Some(if column {
+                    items.map(|item| vec![item]).collect()
+                } else {
+                    vec![items.collect()]
+                }))
+            }
+            _ => Ok(None),
+        }
+    };
+
+    let (mut lhs, left_vector) = match vector(left, false)? {
+        Some(rows) => (rows, true),
+        None => (rows(left)?, false),
+    };
+    let (mut rhs, right_vector) = match vector(right, true)? {
+        Some(rows) => (rows, true),
+        None => (rows(right)?, false),
+    };
+
+    if lhs[0].len() != rhs.len() {
+        return Err(Error::new(
+            format!(
+                "cannot multiply a {}x{} matrix by a {}x{} matrix",
+                lhs.len(),
+                lhs[0].len(),
+                rhs.len(),
+                rhs[0].len()
+            ),
+            ErrorType::RuntimeError,
+            pos,
+        ));
+    }
+
+    let kind = kind(&lhs)?.max(kind(&rhs)?);
+    promote(&mut lhs, kind);
+    promote(&mut rhs, kind);
+
+    let mut product = Vec::with_capacity(lhs.len());
+    for row in &lhs {
+        let mut cells = Vec::with_capacity(rhs[0].len());
+        for j in 0..rhs[0].len() {
+            let mut cell = row[0].mul(&rhs[0][j])?;
+            for k in 1..rhs.len() {
+                cell = cell.add(&row[k].mul(&rhs[k][j])?)?;
+            }
+            cells.push(cell);
+        }
+        product.push(cells);
+    }
+
+    let value = match (left_vector, right_vector) {
+        (true, true) => product.remove(0).remove(0).value,
+        (false, true) => List(product.into_iter().map(|mut row| row.remove(0).value).collect()),
+        (true, false) => List(product.remove(0).into_iter().map(|item| item.value).collect()),
+        (false, false) => to_value(product),
+    };
+
+    Ok(EE::new(value, pos))
+}
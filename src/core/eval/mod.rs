@@ -1,18 +1,106 @@
 pub(crate) mod ast;
 pub(crate) mod error;
+mod builtins;
+mod datetime;
+mod equation;
+mod explain;
+mod format;
 mod lexer;
+mod matrix;
 mod parser;
+pub(crate) mod plot;
+mod stats;
+mod symbolic;
+mod trace;
 mod exec;
+mod units;
+pub(crate) mod options;
 
 use error::Error;
+use options::Options;
 
 /// Run some math expr
 pub(crate) fn exec<'a>(value: &'a str) -> Result<String, Error> {
+    exec_with(value, Options::default())
+}
+
+pub(crate) fn exec_with(value: &str, mut options: Options) -> Result<String, Error> {
+    let (value, precision) = format::sig_figs(value)?;
+    options.format.precision = precision.or(options.format.precision);
+
     let tokens = lexer::Lexer::new(value).tokenize()?;
     let mut parser = parser::Parser::new(tokens);
     parser.config();
+    parser.limit_depth(options.max_depth);
     let ast = parser.expr(0)?;
-    exec::Executer::new().eval(ast).map(|expr| expr.to_string())
+    let mut executer = exec::Executer::with_options(options);
+    let result = executer.result(&ast)?;
+    executer.show(&result)
+}
+
+/// Run `;` separated statements, such as `let r = 2.5; pi * r ** 2.0`
+pub(crate) fn run(value: &str) -> Result<String, Vec<Error>> {
+    run_with(value, Options::default())
+}
+
+/// Every lexing and syntax error is reported at once, evaluation stops at the first error
+///
+/// A trailing `round to 3 sig figs` rounds numbers in the result, as `--precision 3` does
+pub(crate) fn run_with(value: &str, mut options: Options) -> Result<String, Vec<Error>> {
+    let (value, precision) = format::sig_figs(value).map_err(|why| vec![why])?;
+    options.format.precision = precision.or(options.format.precision);
+
+    let program = program(value, &options)?;
+    let mut executer = exec::Executer::with_options(options);
+    let result = executer.run(program).map_err(|why| vec![why])?;
+    executer.show(&result).map_err(|why| vec![why])
+}
+
+/// Lex and parse `;` separated statements, with every error found in either
+fn program<'a>(value: &'a str, options: &Options) -> Result<Vec<ast::Statement<'a>>, Vec<Error>> {
+    let (tokens, mut errors) = lexer::Lexer::new(value).tokenize_all();
+    let mut parser = parser::Parser::new(tokens);
+    parser.config();
+    parser.limit_depth(options.max_depth);
+    parser.skipping(&errors);
+
+    match parser.program() {
+        Ok(program) if errors.is_empty() => Ok(program),
+        Ok(_) => Err(errors),
+        Err(syntax) => {
+            errors.extend(syntax);
+            errors.sort_by_key(|why| why.position().start);
+            Err(errors)
+        }
+    }
+}
+
+/// Run like `run_with`, returning each step of evaluating the last statement, as in
+/// `2 + 3 * 16` for `2 + 3 * 4 ** 2`
+pub(crate) fn trace(value: &str, mut options: Options) -> Result<Vec<String>, Vec<Error>> {
+    let (value, precision) = format::sig_figs(value).map_err(|why| vec![why])?;
+    options.format.precision = precision.or(options.format.precision);
+
+    let program = program(value, &options)?;
+    let max_output = options.max_output;
+    let mut executer = exec::Executer::with_options(options);
+    let steps = executer.trace(program).map_err(|why| vec![why])?;
+    // Each step has to fit on a page of its own
+    match steps.iter().find(|step| step.chars().count() > max_output) {
+        Some(_) => Err(vec![Error::new(
+            format!("a step is longer than the limit of {} characters", max_output),
+            error::ErrorType::LimitError,
+            error::Pos::new(0, value.len()),
+        )]),
+        None => Ok(steps),
+    }
+}
+
+/// Show how `value` was parsed, fully bracketed, with the type of every part of it
+pub(crate) fn explain(value: &str, options: Options) -> Result<String, Vec<Error>> {
+    let (value, _) = format::sig_figs(value).map_err(|why| vec![why])?;
+    let program = program(value, &options)?;
+    explain::explain(&program, options).map_err(|why| vec![why])
 }
 
 #[test]
@@ -32,7 +120,7 @@ fn integration_test3() {
 
 #[test]
 fn integration_test4() {
-    assert!(exec("(100/53) as int + (100/53)").is_err());
+    assert_eq!("206/53".to_string(), exec("(100/53) as int + (100/53)").expect("Failed to run"));
 }
 
 #[test]
@@ -202,7 +290,10 @@ fn integration_test37() {
 
 #[test]
 fn integration_test38() {
-    assert!(exec("120391203918441204981*123212319382148102482948").is_err());
+    assert_eq!(
+        "14833679468000297863933321371085439125163988".to_string(),
+        exec("120391203918441204981*123212319382148102482948").expect("Failed to run")
+    );
 }
 
 #[test]
@@ -212,11 +303,1476 @@ fn integration_test39() {
 
 #[test]
 fn integration_test40() {
-    assert!(exec("170141183460469231731687303715884105726+100").is_err());
+    assert_eq!(
+        "170141183460469231731687303715884105826".to_string(),
+        exec("170141183460469231731687303715884105726+100").expect("Failed to run")
+    );
 }
 
 #[test]
 fn integration_test41() {
-    assert!(exec("1230>>123213").is_err());
+    assert_eq!("0".to_string(), exec("1230>>123213").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test42() {
+    assert_eq!("12.5".to_string(), run("let r = 2.5; r * 5.0").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test43() {
+    assert_eq!("7".to_string(), run("x = 3; y = x + 1; x + y").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test44() {
+    assert_eq!("4".to_string(), run("x = 3; x = x + 1;").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test45() {
+    assert!(run("pi = 3").is_err());
+}
+
+#[test]
+fn integration_test46() {
+    assert!(run("let e = 3.0").is_err());
+}
+
+#[test]
+fn integration_test47() {
+    assert!(run("x + 1").is_err());
+}
+
+#[test]
+fn integration_test48() {
+    assert!(run("x = 1 2").is_err());
+}
+
+#[test]
+fn integration_test49() {
+    assert_eq!("1.4142135623730951".to_string(), exec("sqrt(2)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test50() {
+    assert_eq!("1".to_string(), exec("sin(pi / 2.0)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test51() {
+    assert_eq!("3".to_string(), exec("log(8, 2)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test52() {
+    assert_eq!("6".to_string(), exec("gcd(12, 18) + abs(-3) - max(1, 3, 2)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test53() {
+    assert_eq!("4".to_string(), exec("floor(2.7) + ceil(2.1) + min(0, -1) as int").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test54() {
+    assert!(exec("log(8, 2, 1)").is_err());
+}
+
+#[test]
+fn integration_test55() {
+    assert!(exec("gcd(1.5, 2)").is_err());
+}
+
+#[test]
+fn integration_test56() {
+    assert!(exec("min(1, 2.0)").is_err());
+}
+
+#[test]
+fn integration_test57() {
+    assert!(exec("nope(1)").is_err());
+}
+
+#[test]
+fn integration_test58() {
+    assert_eq!("10".to_string(), run("f(x) = x**2 + 1; f(3)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test59() {
+    assert_eq!("49".to_string(), run("sq = |x| x*x; sq(7)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test60() {
+    assert_eq!("15".to_string(), run("adder(n) = |x| x + n; add5 = adder(5); add5(10)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test61() {
+    assert_eq!("7".to_string(), run("let n = 2; f(x, y) = x * n + y; f(2, 3)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test62() {
+    assert_eq!("42".to_string(), run("answer() = 42; (|| answer())()").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test63() {
+    on_eval_stack(|| assert!(run("f(x) = f(x); f(1)").is_err()));
+}
+
+#[test]
+fn integration_test64() {
+    assert!(run("f(x) = x; f(1, 2)").is_err());
+}
+
+#[test]
+fn integration_test65() {
+    assert!(run("x = 1; x(2)").is_err());
+}
+
+#[test]
+fn integration_test66() {
+    assert_eq!(
+        "1606938044258990275541962092341162602522202993782792835301376".to_string(),
+        exec("2**200").expect("Failed to run")
+    );
+}
+
+#[test]
+fn integration_test67() {
+    assert_eq!(
+        "265252859812191058636308480000000".to_string(),
+        exec("1*2*3*4*5*6*7*8*9*10*11*12*13*14*15*16*17*18*19*20*21*22*23*24*25*26*27*28*29*30").expect("Failed to run")
+    );
+}
+
+#[test]
+fn integration_test68() {
+    assert!(exec("10 ** 100000").is_err());
+}
+
+#[test]
+fn integration_test69() {
+    assert!(exec("1 // 0").is_err());
+}
+
+#[test]
+fn integration_test70() {
+    assert_eq!("1".to_string(), exec("1 ** 2000000000").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test71() {
+    let options = Options {
+        max_digits: 5,
+        ..Options::default()
+    };
+    assert!(exec_with("10 ** 5", options).is_err());
+}
+
+#[test]
+fn integration_test72() {
+    assert_eq!("1/2".to_string(), exec("1/3 + 1/6").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test73() {
+    assert_eq!("0.5".to_string(), exec("(1/3 + 1/6) as float").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test74() {
+    assert_eq!("2".to_string(), exec("4/2").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test75() {
+    assert_eq!("-7/4".to_string(), exec("1 - 11/4").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test76() {
+    assert_eq!("8/27".to_string(), exec("(2/3) ** 3").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test77() {
+    assert_eq!("1/8".to_string(), exec("2 ** -3").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test78() {
+    assert_eq!("true".to_string(), exec("1/3 < 1/2 && 3/6 == 1/2").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test79() {
+    assert!(exec("1/0").is_err());
+}
+
+#[test]
+fn integration_test80() {
+    assert!(exec("1/2 + 0.5").is_err());
+}
+
+#[test]
+fn integration_test81() {
+    assert_eq!("5+5i".to_string(), exec("(1+2i)*(3-i)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test82() {
+    assert_eq!("2i".to_string(), exec("sqrt(-4)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test83() {
+    assert_eq!("-1".to_string(), exec("i ** 2").expect("Failed to run"));
+    assert_eq!("0".to_string(), exec("i - i").expect("Failed to run"));
+    assert_eq!("1-2i".to_string(), exec("(1 + 2i) * (1 - 2i) - 4 - 2i").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test84() {
+    assert_eq!("5".to_string(), exec("abs(3+4i)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test85() {
+    assert_eq!("1-2i".to_string(), exec("conj(1+2i)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test86() {
+    assert_eq!("1.5707963267948966".to_string(), exec("arg(2i)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test87() {
+    assert!(exec("1+2i < 3").is_err());
+}
+
+#[test]
+fn integration_test88() {
+    assert!(run("i = 2").is_err());
+}
+
+#[cfg(test)]
+fn lenient() -> Options {
+    Options {
+        mode: options::Mode::Lenient,
+        ..Options::default()
+    }
+}
+
+#[test]
+fn integration_test89() {
+    assert_eq!("11.1".to_string(), exec_with("10.1 + 1", lenient()).expect("Failed to run"));
+}
+
+#[test]
+fn integration_test90() {
+    assert_eq!("0.5".to_string(), exec_with("1/4 + 0.25", lenient()).expect("Failed to run"));
+}
+
+#[test]
+fn integration_test91() {
+    assert_eq!("2".to_string(), exec_with("true + 1", lenient()).expect("Failed to run"));
+}
+
+#[test]
+fn integration_test92() {
+    assert_eq!("true".to_string(), exec_with("1 == 1.0 && 2 > 1.5", lenient()).expect("Failed to run"));
+}
+
+#[test]
+fn integration_test93() {
+    assert!(exec_with("10.1 << 1", lenient()).is_err());
+}
+
+#[test]
+fn integration_test94() {
+    assert!(exec_with("true + false", lenient()).is_err());
+}
+
+#[test]
+fn integration_test95() {
+    assert_eq!("5.3 km".to_string(), exec("5 km + 300 m").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test96() {
+    assert_eq!("96.56063999999999 km/h".to_string(), exec("60 mph as km/h").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test97() {
+    assert_eq!("29.43 N".to_string(), exec("3 kg * 9.81 m/s**2 as N").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test98() {
+    assert_eq!("29.43 m*kg/s**2".to_string(), exec("3 kg * 9.81 m/s**2").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test99() {
+    assert!(exec("5 km + 3 s").is_err());
+}
+
+#[test]
+fn integration_test100() {
+    assert!(exec("1 km < 1 h").is_err());
+}
+
+#[test]
+fn integration_test101() {
+    assert!(exec("60 mph as kg").is_err());
+}
+
+#[test]
+fn integration_test102() {
+    assert_eq!("true".to_string(), exec("1 km == 1000 m && 2 ft < 1 m").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test103() {
+    assert_eq!("1000".to_string(), exec("km / m").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test104() {
+    assert_eq!("2 m".to_string(), exec("sqrt(4 m**2)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test105() {
+    assert_eq!("7".to_string(), run("m = 3; m + 4").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test106() {
+    assert_eq!("2".to_string(), exec("3 as float as int - 1").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test107() {
+    assert_eq!("214 days".to_string(), exec("(2026-01-01 - 2025-06-01) as days").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test108() {
+    assert_eq!("2026-12-25 03:30:00 UTC".to_string(), exec("2026-12-25 + 3h30m").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test109() {
+    assert_eq!("2027-01-01".to_string(), exec("2026-12-25 + 1w").expect("Failed to run"));
 }
 
+#[test]
+fn integration_test110() {
+    assert_eq!("true".to_string(), exec("now + 3h30m - now == 210 minutes as s && today <= now").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test111() {
+    assert_eq!("1d 12h".to_string(), exec("3d / 2").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test112() {
+    assert_eq!("-1h 30m".to_string(), exec("30m15s - 2h + 2h - 2h15s").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test113() {
+    assert_eq!("2026-02-28 12:00:00 UTC".to_string(), exec("2026-03-01T00:00 - 12 h").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test114() {
+    assert!(exec("2026-02-30").is_err());
+}
+
+#[test]
+fn integration_test115() {
+    assert!(exec("2026-12-25 + 2026-12-25").is_err());
+}
+
+#[test]
+fn integration_test116() {
+    assert!(exec("today < 3d").is_err());
+}
+
+#[test]
+fn integration_test117() {
+    assert_eq!("[5, 7, 9]".to_string(), exec("[1, 2, 3] + [4, 5, 6]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test118() {
+    assert_eq!("[2, 4, 6]".to_string(), exec("2 * [1, 2, 3]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test119() {
+    assert_eq!("3".to_string(), run("xs = [1, 2, 3]; xs[-1]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test120() {
+    assert_eq!("[[2, 3], [1, 2], [3]]".to_string(), run("xs = [1, 2, 3]; [xs[1:], xs[:2], xs[-1:10]]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test121() {
+    assert_eq!("[3, 6, 6, 0]".to_string(), exec("[len([1, 2, 3]), sum([1, 2, 3]), product([1, 2, 3]), sum([])]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test122() {
+    assert_eq!("[-1, 1/2, 2, 3]".to_string(), exec("sort([3, 1/2, -1, 2])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test123() {
+    assert_eq!("-1".to_string(), exec("min([3, -1, 2])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test124() {
+    assert!(exec("[1, 2] + [1, 2, 3]").is_err());
+}
+
+#[test]
+fn integration_test125() {
+    assert!(exec("[1, 2, 3][3]").is_err());
+}
+
+#[test]
+fn integration_test126() {
+    assert!(exec("sort([1, true])").is_err());
+}
+
+#[test]
+fn integration_test127() {
+    assert_eq!("[1, 2]".to_string(), exec("[1.2, 2.4] as int").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test128() {
+    assert_eq!("-2".to_string(), exec("det([[1, 2], [3, 4]])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test129() {
+    assert_eq!("[ -2     1]\n[3/2  -1/2]".to_string(), exec("inv([[1, 2], [3, 4]])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test130() {
+    assert_eq!("[19  22]\n[43  50]".to_string(), exec("[[1, 2], [3, 4]] @ [[5, 6], [7, 8]]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test131() {
+    assert_eq!("true".to_string(), exec("[[1, 2], [3, 4]] * [[5, 6], [7, 8]] == [[1, 2], [3, 4]] @ [[5, 6], [7, 8]]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test132() {
+    assert_eq!("[1, 2]".to_string(), exec("solve([[2, 1], [1, 3]], [4, 7])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test133() {
+    assert_eq!("[1, 2]".to_string(), exec("[rank([[1, 2], [2, 4]]), rank([[1.5, 0], [0, 2]])]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test134() {
+    assert_eq!("[1  3]\n[2  4]".to_string(), exec("transpose([[1, 2], [3, 4]])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test135() {
+    assert_eq!("[5, 11]".to_string(), exec("[[1, 2], [3, 4]] @ [1, 2]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test136() {
+    assert!(exec("inv([[1, 2], [2, 4]])").is_err());
+}
+
+#[test]
+fn integration_test137() {
+    assert!(exec("[[1, 2, 3], [4, 5, 6]] @ [[1, 2], [3, 4]]").is_err());
+}
+
+#[test]
+fn integration_test138() {
+    assert_eq!("-2".to_string(), exec("det([[1.0, 2.0], [3.0, 4.0]]) as int").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test139() {
+    assert_eq!("5/2".to_string(), exec("mean([1, 2, 3, 4])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test140() {
+    assert_eq!("3".to_string(), exec("median([5, 1, 3])").expect("Failed to run"));
+    assert_eq!("5/2".to_string(), exec("median([4, 1, 3, 2])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test141() {
+    assert_eq!("2".to_string(), exec("mode([3, 2, 1, 2, 3])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test142() {
+    assert_eq!("32/7".to_string(), exec("variance([2, 4, 4, 4, 5, 5, 7, 9])").expect("Failed to run"));
+    assert_eq!("2".to_string(), exec("stddev([1, 3, 5]) as int").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test143() {
+    assert_eq!("25".to_string(), exec("percentile([10, 20, 30, 40, 50], 37.5)").expect("Failed to run"));
+    assert!(exec("percentile([1, 2], 101)").is_err());
+}
+
+#[test]
+fn integration_test144() {
+    assert_eq!("-1".to_string(), exec("corr([1, 2, 3], [6, 4, 2])").expect("Failed to run"));
+    assert!(exec("corr([1, 2, 3], [1, 1, 1])").is_err());
+}
+
+#[test]
+fn integration_test145() {
+    assert_eq!("[2, 1]".to_string(), exec("linreg([0, 1, 2], [1, 3, 5])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test146() {
+    assert_eq!("120".to_string(), exec("factorial(5)").expect("Failed to run"));
+    assert_eq!("1".to_string(), exec("factorial(0)").expect("Failed to run"));
+    assert!(exec("factorial(-1)").is_err());
+    assert!(exec("factorial(100000)").is_err());
+}
+
+#[test]
+fn integration_test147() {
+    assert_eq!("10".to_string(), exec("nCr(5, 2)").expect("Failed to run"));
+    assert_eq!("20".to_string(), exec("nPr(5, 2)").expect("Failed to run"));
+    assert_eq!("0".to_string(), exec("nCr(2, 5)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test148() {
+    let why = exec("mean([])").expect_err("Empty mean should fail").to_string();
+    assert!(why.starts_with("RuntimeError"));
+    assert!(why.ends_with("cannot take the mean of an empty list"));
+    assert!(exec("median([])").is_err());
+    assert!(exec("stddev([])").is_err());
+}
+
+#[test]
+fn integration_test149() {
+    assert_eq!("3*x**2 + cos(x)".to_string(), exec("d/dx (x**3 + sin(x))").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test150() {
+    assert_eq!("2*x*y".to_string(), exec("diff(x**2*y, x)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test151() {
+    assert_eq!("sin(x) + x*cos(x)".to_string(), exec("diff(x*sin(x), x)").expect("Failed to run"));
+    assert_eq!("-1/x**2".to_string(), exec("diff(1/x, x)").expect("Failed to run"));
+    assert_eq!("2*e**(2*x)".to_string(), exec("diff(e**(2*x), x)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test152() {
+    assert_eq!("2*x + y + 3".to_string(), exec("simplify(x + 1 + x + 2*y - y + 2)").expect("Failed to run"));
+    assert_eq!("x".to_string(), exec("simplify(x**2 * y / (x*y) + 0*z)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test153() {
+    assert_eq!("6*x".to_string(), run("let a = 3; diff(a*x**2, x)").expect("Failed to run"));
+    assert_eq!("6".to_string(), run("f = d/dx (x**3); d/dx (diff(f, x))").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test154() {
+    assert!(exec("diff(floor(x), x)").is_err());
+    assert!(exec("diff(x**2, 2)").is_err());
+    assert!(exec("diff(x % 2, x)").is_err());
+}
+
+#[test]
+fn integration_test155() {
+    assert_eq!("[-2, 2]".to_string(), exec("solve(x**2 - 4 = 0, x)").expect("Failed to run"));
+    assert_eq!("[-1/2]".to_string(), exec("solve(2*x + 1 = 0, x)").expect("Failed to run"));
+    assert_eq!("[1]".to_string(), exec("solve(x**2 - 2*x + 1 = 0, x)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test156() {
+    assert_eq!("[-sqrt(2), sqrt(2)]".to_string(), exec("solve(x**2 = 2, x)").expect("Failed to run"));
+    assert_eq!("[-1-2i, -1+2i]".to_string(), exec("solve(x**2 + 2*x + 5 = 0, x)").expect("Failed to run"));
+    assert_eq!("[-b/a]".to_string(), exec("solve(a*x + b = 0, x)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test157() {
+    assert_eq!(
+        "[0.7390851332151607]\nnote: solved numerically, only looking for `x` in -100..100".to_string(),
+        exec("solve(cos(x) = x, x)").expect("Failed to run")
+    );
+    assert!(exec("solve(x**3 - 2*x**2 - x + 2 = 0, x)").expect("Failed to run").starts_with("[-1, 1, 2]\nnote: "));
+    assert!(exec("solve(exp(x) = -1, x)").expect("Failed to run").starts_with("[]\nnote: "));
+}
+
+#[test]
+fn integration_test158() {
+    assert_eq!("[1, 2]".to_string(), run("let b = 4; solve([[2, 1], [1, 3]], [b, 7])").expect("Failed to run"));
+    assert!(exec("solve(x + 1 = x, x)").is_ok());
+    assert!(exec("solve(x = x, x)").is_err());
+    assert!(exec("solve(sin(x) = y, x)").is_err());
+    assert!(exec("x**2 = 4").is_err());
+}
+
+#[test]
+fn integration_test159() {
+    let plot = plot::plot("sin(x), x**2", "-5", "5").expect("Failed to run");
+    assert_eq!(vec!["sin(x)", "x**2"], plot.curves.iter().map(|curve| curve.label.as_str()).collect::<Vec<_>>());
+    assert!(plot.curves.iter().all(|curve| curve.segments.len() == 1));
+    assert_eq!((-5.0, 25.0), plot.curves[1].segments[0][0]);
+    assert!(plot.y.0 < -1.0 && plot.y.1 > 25.0);
+}
+
+#[test]
+fn integration_test160() {
+    let segments = |src| plot::plot(src, "-5", "5").expect("Failed to run").curves[0].segments.len();
+    assert_eq!(5, segments("tan(x)"));
+    assert_eq!(2, segments("1/x"));
+    assert_eq!(2, segments("1/(x-1)**2"));
+    assert_eq!(10, segments("floor(x)"));
+    assert_eq!(1, segments("atan(1000*x)"));
+    assert_eq!(1, segments("x**3"));
+}
+
+#[test]
+fn integration_test161() {
+    let plot = plot::plot("sqrt(x), diff(x**3, x)", "-pi", "2*pi").expect("Failed to run");
+    assert_eq!((-std::f64::consts::PI, 2.0 * std::f64::consts::PI), plot.x);
+    let (x, _) = plot.curves[0].segments[0][0];
+    assert!((0.0..0.01).contains(&x));
+    assert_eq!(3.0 * std::f64::consts::PI.powi(2), plot.curves[1].segments[0][0].1);
+}
+
+#[test]
+fn integration_test162() {
+    assert!(plot::plot("y + 1", "-5", "5").is_err());
+    assert!(plot::plot("[x]", "-5", "5").is_err());
+    assert!(plot::plot("x", "5", "-5").is_err());
+    assert!(plot::plot("x", "0", "i").is_err());
+    assert!(plot::plot("x, x, x, x, x, x, x, x, x", "-5", "5").is_err());
+}
+
+#[test]
+fn integration_test163() {
+    let why = exec("10.1 + 1").expect_err("Mixing types should fail");
+    assert_eq!(
+        "TypeError: cannot apply operator `add` on types `float` and `integer`
+  |
+1 | 10.1 + 1
+  | ----^^^- `integer`
+  | |
+  | `float`
+  = note: integers and floats don't mix, so that exact results stay exact
+  = help: use `1.0` instead of `1` to add to a float",
+        why.render("10.1 + 1")
+    );
+}
+
+#[test]
+fn integration_test164() {
+    let src = "let n = 3; 2.5 * n";
+    let why = error::render_all(&run(src).expect_err("Mixing types should fail"), src);
+    assert!(why.contains("\n1 | let n = 3; 2.5 * n\n  |            ---^^^- `integer`\n"));
+    assert!(why.ends_with("help: convert with `as float`, or evaluate with `--lenient`"));
+}
+
+#[test]
+fn integration_test165() {
+    assert_eq!(
+        "SyntaxError: unexpected end of file\n  |\n1 | 1 +\n  |    ^",
+        exec("1 +").expect_err("Incomplete input should fail").render("1 +")
+    );
+    let src = "1 +\n2 * true";
+    let why = error::render_all(&run(src).expect_err("Mixing types should fail"), src);
+    assert!(why.contains("\n2 | 2 * true\n  | -^^^---- `boolean`\n  | |\n  | `integer`"));
+}
+
+#[test]
+fn integration_test166() {
+    let errors = run("1 + ; 2 * ; 3").expect_err("Both statements should fail");
+    assert_eq!(
+        vec![error::Pos::new(4, 5), error::Pos::new(10, 11)],
+        errors.iter().map(|why| why.position()).collect::<Vec<_>>()
+    );
+    let errors = run("[1, 2 +, 3 *]; let = 4; f(1,, 2)").expect_err("Every statement should fail");
+    assert_eq!(4, errors.len());
+}
+
+#[test]
+fn integration_test167() {
+    // No extra syntax error for the missing operator
+    let errors = run("2 × 3 + √2").expect_err("Unknown characters should fail");
+    assert_eq!(2, errors.len());
+    assert!(errors.iter().all(|why| why.to_string().starts_with("LexError")));
+    assert_eq!(
+        "LexError: unrecognized character `×`\n  |\n1 | 2 × 3 + √2\n  |   ^",
+        errors[0].render("2 × 3 + √2")
+    );
+}
+
+#[test]
+fn integration_test168() {
+    assert_eq!("3".to_string(), run("1 + 2;").expect("Failed to run"));
+    let errors = run("let x = 1; x + ; y").expect_err("Syntax errors come before evaluation");
+    assert_eq!(1, errors.len());
+    let errors = run("1; y + 1; 2 * true").expect_err("Evaluation stops at the first error");
+    assert_eq!(1, errors.len());
+    assert!(errors[0].to_string().ends_with("no variable `y` found"));
+}
+
+#[test]
+fn integration_test169() {
+    assert_eq!("10".to_string(), exec("if 1 < 2 then 10 else 20").expect("Failed to run"));
+    assert_eq!("3".to_string(), run("let x = -3; if x < 0 then -x else x").expect("Failed to run"));
+    assert_eq!("2".to_string(), exec("1 + if true then 1 else 2").expect("Failed to run"));
+    assert_eq!("1".to_string(), exec("if true then 1 else 2 + 10").expect("Failed to run"));
+    assert_eq!("2".to_string(), exec("if true then if false then 1 else 2 else 3").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test170() {
+    assert_eq!(
+        "2432902008176640000".to_string(),
+        run("fact(n) = if n <= 1 then 1 else n * fact(n - 1); fact(20)").expect("Failed to run")
+    );
+    assert_eq!("[1, 0, 1]".to_string(), run("sign(x) = if x < 0 then -1 else if x == 0 then 0 else 1; [sign(-5), sign(0), sign(2)] * [-1, 1, 1]").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test171() {
+    assert_eq!("false".to_string(), exec("false && 1/0 == 1").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("true || nothing").expect("Failed to run"));
+    assert_eq!("1".to_string(), exec("if true then 1 else 1/0").expect("Failed to run"));
+    assert!(exec("true && nothing").is_err());
+    assert!(exec("1 && false").is_err());
+}
+
+#[test]
+fn integration_test172() {
+    assert!(exec("if 1 then 2 else 3").is_err());
+    assert!(exec("if true then 1").is_err());
+    let why = exec("if 1 = 1 then 2 else 3").expect_err("An equation is not a condition");
+    assert!(why.render("if 1 = 1 then 2 else 3").ends_with("help: use `==` to compare two values"));
+}
+
+#[test]
+fn integration_test173() {
+    assert_eq!("\"hello, world\"".to_string(), exec(r#""hello" + ", " + "world""#).expect("Failed to run"));
+    assert_eq!("\"a \\\"b\\\"\\n\"".to_string(), exec(r#""a \"b\"\n""#).expect("Failed to run"));
+    assert_eq!("5".to_string(), exec(r#"len("héllo")"#).expect("Failed to run"));
+    assert_eq!("\"ABC\"".to_string(), exec(r#"upper("abc")"#).expect("Failed to run"));
+    assert_eq!("\"àbc\"".to_string(), exec(r#"lower("ÀBC")"#).expect("Failed to run"));
+    assert!(exec(r#""a" + 1"#).is_err());
+}
+
+#[test]
+fn integration_test174() {
+    assert_eq!("\"ababab\"".to_string(), exec(r#"repeat("ab", 3)"#).expect("Failed to run"));
+    assert!(exec(r#"repeat("a", -1)"#).is_err());
+    assert!(exec(r#"repeat("a", 10**12)"#).is_err());
+    assert_eq!(
+        "[\"a\", \"b\", \"\", \"c\"]".to_string(),
+        exec(r#"split("a,b,,c", ",")"#).expect("Failed to run")
+    );
+    assert_eq!("[\"one\", \"two\"]".to_string(), exec(r#"split("  one   two ")"#).expect("Failed to run"));
+    assert_eq!("[\"a\", \"b\"]".to_string(), exec(r#"split("ab", "")"#).expect("Failed to run"));
+    assert_eq!("\"1-2.5-x\"".to_string(), exec(r#"join([1, 2.5, "x"], "-")"#).expect("Failed to run"));
+    assert_eq!("\"abc\"".to_string(), exec(r#"join(split("a b c"))"#).expect("Failed to run"));
+}
+
+#[test]
+fn integration_test175() {
+    assert_eq!("true".to_string(), exec(r#""abc" < "abd""#).expect("Failed to run"));
+    assert_eq!("false".to_string(), exec(r#""a" >= "b""#).expect("Failed to run"));
+    assert_eq!("true".to_string(), exec(r#""a" == "a" && "a" != "b""#).expect("Failed to run"));
+    assert_eq!("[\"a\", \"b\", \"c\"]".to_string(), exec(r#"sort(["b", "c", "a"])"#).expect("Failed to run"));
+    assert_eq!("\"HI\"".to_string(), run(r#"let s = "hi"; if s == "hi" then upper(s) else s"#).expect("Failed to run"));
+}
+
+#[test]
+fn integration_test176() {
+    assert_eq!("42".to_string(), exec(r#""42" as int"#).expect("Failed to run"));
+    assert_eq!("3.5".to_string(), exec(r#"" 3.5 " as float"#).expect("Failed to run"));
+    assert_eq!("[1, 2]".to_string(), exec(r#"["1", "2"] as int"#).expect("Failed to run"));
+    assert!(exec(r#""abc" as int"#).is_err());
+    let errors = run(r#""bad \q" + "open"#).expect_err("Both strings are malformed");
+    assert_eq!(2, errors.len());
+}
+
+#[test]
+fn integration_test177() {
+    assert_eq!("280".to_string(), exec("0xff + 0b1010 + 0o17").expect("Failed to run"));
+    assert_eq!("2000000".to_string(), exec("1_000_000 * 2").expect("Failed to run"));
+    assert_eq!("1000.75".to_string(), exec("1_000.5 + 0.25").expect("Failed to run"));
+    assert_eq!("0.00001".to_string(), exec("1e-5").expect("Failed to run"));
+    assert_eq!("0.75".to_string(), exec(".5 + .25").expect("Failed to run"));
+    assert_eq!("1.5i".to_string(), exec("3 * .5i").expect("Failed to run"));
+    assert_eq!("3 m**2".to_string(), exec("3 m**0b10").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test178() {
+    let errors = run("0b102 + 1__0 + 1e").expect_err("Every literal is malformed");
+    assert_eq!(3, errors.len());
+    assert_eq!(
+        "LexError: invalid digit `2` in binary literal\n  |\n1 | 0b102 + 1__0 + 1e\n  |     ^",
+        errors[0].render("0b102 + 1__0 + 1e")
+    );
+    let why = exec("0xff + 1.0").expect_err("Integers and floats don't mix");
+    assert!(why.render("0xff + 1.0").ends_with("help: convert with `as float`, or evaluate with `--lenient`"));
+}
+
+#[test]
+fn integration_test179() {
+    assert_eq!("0xff".to_string(), exec("255 as hex").expect("Failed to run"));
+    assert_eq!("-0xff".to_string(), exec("-255 as hex").expect("Failed to run"));
+    assert_eq!("0b1010".to_string(), exec("10 as bin").expect("Failed to run"));
+    assert_eq!("[0o7, 0o10]".to_string(), exec("[7, 8] as oct").expect("Failed to run"));
+    assert_eq!("0xff".to_string(), run("let x = 255; x as hex").expect("Failed to run"));
+    assert!(exec("2.5 as hex").is_err());
+    let why = exec("(255 as hex) + 1").expect_err("Conversions only come last");
+    assert!(why.render("(255 as hex) + 1").ends_with("help: put it at the very end, as in `(x + 1) as hex`"));
+}
+
+#[test]
+fn integration_test180() {
+    assert_eq!("1.2345e4".to_string(), exec("12345 as sci").expect("Failed to run"));
+    assert_eq!("1e400".to_string(), exec("10**400 as sci").expect("Failed to run"));
+    assert_eq!("1.2345e3".to_string(), exec("1234.5 as sci").expect("Failed to run"));
+    assert_eq!("1.00e6".to_string(), exec("999999 as sci round to 3 sig figs").expect("Failed to run"));
+    assert_eq!("3.14e0".to_string(), exec("pi as sci round to 3 sig figs").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test181() {
+    assert_eq!("3.14".to_string(), run("pi round to 3 sig figs").expect("Failed to run"));
+    assert_eq!("0.333".to_string(), run("1/3 round to 3 sig figs").expect("Failed to run"));
+    assert_eq!("12000".to_string(), run("12345 round to 2 sig figs").expect("Failed to run"));
+    assert_eq!("96.6 km/h".to_string(), run("60 mph as km/h round to 3 sig figs").expect("Failed to run"));
+    assert_eq!("1.41i".to_string(), run("sqrt(-2) round to 3 sig figs").expect("Failed to run"));
+    assert!(run("pi round to 0 sig figs").is_err());
+}
+
+#[test]
+fn integration_test182() {
+    let options = Options {
+        format: options::Format {
+            precision: Some(4),
+            ..options::Format::default()
+        },
+        ..Options::default()
+    };
+    assert_eq!("0.3".to_string(), run_with("0.1 + 0.2", options.clone()).expect("Failed to run"));
+    assert_eq!("[1.235  2]".to_string(), run_with("[[1.23456, 2.0]]", options.clone()).expect("Failed to run"));
+    assert_eq!("3.142".to_string(), exec_with("pi", options).expect("Failed to run"));
+    assert_eq!("0.30000000000000004".to_string(), exec("0.1 + 0.2").expect("Failed to run"));
+}
+
+/// Run on a thread with the stack the bot evaluates with, failing if it panics, for input
+/// nested as deep as the limits allow
+#[cfg(test)]
+fn on_eval_stack<F: FnOnce() + Send + 'static>(work: F) {
+    std::thread::Builder::new()
+        .stack_size(options::STACK_SIZE)
+        .spawn(work)
+        .expect("Failed to spawn")
+        .join()
+        .expect("Evaluation panicked");
+}
+
+#[test]
+fn integration_test183() {
+    on_eval_stack(|| {
+        let limit = options::Options::default().max_depth;
+        let deep = format!("{}1{}", "(".repeat(limit - 1), ")".repeat(limit - 1));
+        assert_eq!("1".to_string(), run(&deep).expect("Failed to run"));
+
+        let deeper = format!("({})", deep);
+        let errors = run(&deeper).expect_err("Nesting past the limit should fail");
+        assert_eq!(1, errors.len());
+        assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+        assert!(errors[0].to_string().contains(&format!("limit of {}", limit)));
+        let errors = run(&format!("{}1", "-".repeat(5000))).expect_err("Nesting past the limit should fail");
+        assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+    });
+}
+
+#[test]
+fn integration_test184() {
+    let options = Options {
+        max_steps: 1000,
+        ..Options::default()
+    };
+    let src = "f(n) = if n < 2 then n else f(n - 1) + f(n - 2); f(20)";
+    assert_eq!("6765".to_string(), run(src).expect("Failed to run"));
+    let errors = run_with(src, options.clone()).expect_err("Running out of steps should fail");
+    assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+    assert!(errors[0].to_string().contains("limit of 1000 steps"));
+
+    let options = Options {
+        max_depth: 50,
+        ..Options::default()
+    };
+    let chain = format!("1{}", " + 1".repeat(60));
+    assert_eq!("61".to_string(), run(&chain).expect("Failed to run"));
+    let errors = run_with(&chain, options).expect_err("Nesting past the limit should fail");
+    assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+}
+
+#[test]
+fn integration_test185() {
+    // Recursion is stopped by the nesting limit, with the stack the bot has
+    on_eval_stack(|| {
+        let errors = run("f(x) = f(x); f(1)").expect_err("Endless recursion should fail");
+        assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+        assert!(errors[0].to_string().contains("nested deeper than the limit of 500"));
+    });
+    let errors = run("10 ** 2000").expect_err("Huge integers should fail");
+    assert!(errors[0].to_string().starts_with("LimitError"));
+    assert!(run("repeat(\"ab\", 6000)").is_err());
+    let errors = run("split(repeat(\"ab\", 600), \"\")").expect_err("Long output should fail");
+    assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+    assert!(errors[0].to_string().contains("limit of 1016 characters"));
+}
+
+#[test]
+fn integration_test186() {
+    let options = Options {
+        deadline: Some(std::time::Instant::now()),
+        ..Options::default()
+    };
+    let src = "f(n) = if n < 2 then n else f(n - 1) + f(n - 2); f(20)";
+    let errors = run_with(src, options.clone()).expect_err("Running out of time should fail");
+    assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+    assert!(errors[0].to_string().contains("ran out of time"));
+
+    let why = plot::plot_with("x ** 2", "-1", "1", options).expect_err("Running out of time should fail");
+    assert_eq!(error::ErrorType::LimitError, why.error_type());
+
+    let options = Options {
+        max_steps: 10,
+        ..Options::default()
+    };
+    let why = plot::plot_with("x ** 2", "-1", "1", options).expect_err("Running out of steps should fail");
+    assert_eq!(error::ErrorType::LimitError, why.error_type());
+    assert!(plot::plot_with("x ** 2", "-1", "1", Options::default()).is_ok());
+}
+
+#[test]
+fn integration_test187() {
+    assert!(exec("5 % 0").is_err());
+    assert!(exec("5 // 0").is_err());
+    assert!(run("5 % (2 - 2); 1").is_err());
+
+    // Token streams put together by hand may lack the trailing EOF
+    let mut parser = parser::Parser::new(Vec::new());
+    parser.config();
+    assert!(parser.expr(0).is_err());
+
+    let tokens = lexer::Lexer::new("1 +").tokenize().expect("Failed to lex");
+    let mut parser = parser::Parser::new(tokens[..tokens.len() - 1].to_vec());
+    parser.config();
+    let why = parser.expr(0).expect_err("A missing operand should fail");
+    assert_eq!(error::ErrorType::SyntaxError, why.error_type());
+}
+
+#[test]
+fn integration_test188() {
+    let explained = explain("1 + 2 * 3 - 4", Options::default()).expect("Failed to explain");
+    assert_eq!(
+        "((1 + (2 * 3)) - 4): integer\n  (1 + (2 * 3)): integer\n    1: integer\n    (2 * 3): integer\n      2: integer\n      3: integer\n  4: integer",
+        explained
+    );
+
+    let parsed = |src| explain(src, Options::default()).expect("Failed to explain").lines().next().map(str::to_string);
+    assert_eq!(Some("((-2) ** 2): integer".to_string()), parsed("-2 ** 2"));
+    assert_eq!(Some("(((a < b) && (!c)) || d): ?".to_string()), parsed("a < b && !c || d"));
+    assert_eq!(Some("((5 * km) + ((300 * m) as m)): quantity".to_string()), parsed("5 km + 300 m as m"));
+    assert_eq!(Some("[1, 2.5][1:]: list".to_string()), parsed("[1, 2.5][1:]"));
+    assert_eq!(Some("(if true then \"a\\n\" else 1i): string".to_string()), parsed("if true then \"a\\n\" else 1i"));
+    assert_eq!(Some("(0xff as hex): integer".to_string()), parsed("0xff as hex"));
+}
+
+#[test]
+fn integration_test189() {
+    let explained = explain("let r = 2.5; f(x) = x * r; f(2.0)", Options::default()).expect("Failed to explain");
+    let lines: Vec<&str> = explained.lines().collect();
+    assert_eq!(
+        vec![
+            "let r = 2.5",
+            "  2.5: float",
+            "f = (|x| (x * r))",
+            "  (|x| (x * r)): function",
+            "f(2.0): float",
+            "  2.0: float",
+        ],
+        lines
+    );
+
+    // Parts without a value of their own, or failing, still show how they were parsed
+    let explained = explain("diff(x**2, x) + 1/0", Options::default()).expect("Failed to explain");
+    assert!(explained.starts_with("(diff((x ** 2), x) + (1 / 0)): ?\n  diff((x ** 2), x): expression\n    (x ** 2): ?"));
+    assert!(explain("1 +", Options::default()).is_err());
+
+    let options = Options {
+        max_output: 20,
+        ..Options::default()
+    };
+    let errors = explain("1 + 2 * 3", options).expect_err("Long explanations should fail");
+    assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+}
+
+#[test]
+fn integration_test190() {
+    let steps = |src| trace(src, Options::default()).expect("Failed to trace");
+    assert_eq!(vec!["2 + 3 * 4 ** 2", "2 + 3 * 16", "2 + 48", "50"], steps("2 + 3 * 4 ** 2"));
+    assert_eq!(vec!["(1 + 2) * 3", "3 * 3", "9"], steps("(1 + 2) * 3"));
+    assert_eq!(vec!["10 - (1 - 2)", "10 - -1", "11"], steps("10 - (1 - 2)"));
+    assert_eq!(vec!["-(2 - 5)", "-(-3)", "3"], steps("-(2 - 5)"));
+    assert_eq!(vec!["1 / 2 + 1", "(1/2) + 1", "3/2"], steps("1 / 2 + 1"));
+    assert_eq!(
+        vec!["if 1 < 2 then sqrt(16.0) else 0", "if true then sqrt(16.0) else 0", "if true then 4 else 0", "4"],
+        steps("if 1 < 2 then sqrt(16.0) else 0")
+    );
+    assert_eq!(vec!["42"], steps("42"));
+}
+
+#[test]
+fn integration_test191() {
+    let steps = |src| trace(src, Options::default()).expect("Failed to trace");
+    // Only the last statement is traced, not the insides of the functions it calls
+    assert_eq!(vec!["f(3) + 1", "10 + 1", "11"], steps("f(x) = x ** 2 + 1; f(3) + 1"));
+    assert_eq!(vec!["(255 + 1) as hex", "256 as hex", "0x100"], steps("(255 + 1) as hex"));
+    assert_eq!(vec!["[[1, 2], [3, 4]][0]", "[1, 2]"], steps("[[1, 2], [3, 4]][0]"));
+
+    assert!(trace("1 / 0", Options::default()).is_err());
+    let long = format!("[{}]", vec!["-1"; trace::MAX_STEPS + 1].join(", "));
+    let errors = trace(&long, Options::default()).expect_err("Long traces should fail");
+    assert_eq!(error::ErrorType::LimitError, errors[0].error_type());
+}
+
+#[test]
+fn integration_test192() {
+    // Sorting with NaN used to break the total order `sort_by` relies on
+    let nans = "nan = 0.0/0.0; sort([31.0, nan, nan, 42.0, 1.0, nan, -3.0, 7.5, nan, 2.0, 0.5, nan, 9.0, 12.0])";
+    assert_eq!(
+        "[-3, 0.5, 1, 2, 7.5, 9, 12, 31, 42, NaN, NaN, NaN, NaN, NaN]".to_string(),
+        run(nans).expect("Failed to run")
+    );
+    assert_eq!("[3 cm, 2 m, 1 km]".to_string(), exec("sort([2 m, 1 km, 3 cm])").expect("Failed to run"));
+    assert!(exec(r#"sort([1, "a", 2])"#).is_err());
+    assert!(exec("sort([1.0, 2])").is_err());
+    assert_eq!("[2 s, 3h]".to_string(), exec("sort([3h, 2 s])").expect("Failed to run"));
+    assert!(exec("sort([3h, 2 m])").is_err());
+}
+
+#[test]
+fn integration_test193() {
+    assert_eq!("NaN".to_string(), run("nan = 0.0/0.0; median([31.0, nan, nan, 42.0, 1.0, nan, -3.0])").expect("Failed to run"));
+    assert_eq!("NaN".to_string(), run("nan = 0.0/0.0; percentile([1.0, nan, 2.0], 50)").expect("Failed to run"));
+
+    // The median is the 50th percentile, exact for exact values
+    for xs in &["[4, 1, 3, 2]", "[5, 1, 3]", "[1/2, 1/3]", "[1.0, 2.0]"] {
+        let median = exec(&format!("median({})", xs)).expect("Failed to run");
+        let percentile = exec(&format!("percentile({}, 50)", xs)).expect("Failed to run");
+        assert_eq!(median, percentile);
+    }
+    assert_eq!("5/2".to_string(), exec("percentile([1, 2, 3, 4], 50)").expect("Failed to run"));
+    assert_eq!("50/3".to_string(), exec("percentile([10, 20, 30], 100/3)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test194() {
+    // Each `diff` of `x**x` roughly triples its size, building expressions counts against the limits
+    let nested = format!("{}x**x{}", "diff(".repeat(12), ", x)".repeat(12));
+    let why = exec(&nested).expect_err("Growing expressions should fail");
+    assert_eq!(error::ErrorType::LimitError, why.error_type());
+
+    let options = Options {
+        max_steps: 20,
+        ..Options::default()
+    };
+    let why = exec_with("diff(sin(x) * x**3, x)", options).expect_err("Long symbolic work should fail");
+    assert_eq!(error::ErrorType::LimitError, why.error_type());
+
+    let options = Options {
+        deadline: Some(std::time::Instant::now()),
+        ..Options::default()
+    };
+    let why = exec_with("simplify(x + x)", options).expect_err("Symbolic work past the deadline should fail");
+    assert_eq!(error::ErrorType::LimitError, why.error_type());
+    assert_eq!("x**x*(ln(x) + 1)".to_string(), exec("diff(x**x, x)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test195() {
+    // Simplifying fails on a division by zero wherever evaluating it would
+    for src in &["0/0", "0*(1/0)", "x/(1 - 1)", "0**-1"] {
+        let why = exec(&format!("simplify({})", src)).expect_err("Dividing by zero should fail");
+        assert_eq!(error::ErrorType::RuntimeError, why.error_type());
+    }
+    assert!(exec("0*(1/0)").is_err());
+    assert_eq!("0".to_string(), exec("simplify(0*x)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test196() {
+    // Float samples are divided by a float count, not an integer one
+    assert_eq!("1.5".to_string(), exec("mean([1.0, 2.0])").expect("Failed to run"));
+    assert_eq!("0.5".to_string(), exec("variance([1.0, 2.0])").expect("Failed to run"));
+    assert_eq!("5/3".to_string(), exec("variance([1, 2, 3, 4])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test197() {
+    // Logical operators don't widen, the types stay as written
+    let why = exec_with("true && 1", lenient()).expect_err("A number is no boolean");
+    assert!(why.to_string().contains("`boolean` and `integer`"));
+    assert_eq!("true".to_string(), exec_with("true || 1", lenient()).expect("Failed to run"));
+
+    // Builtins that compare or add up numbers widen them as operators do
+    assert_eq!("1".to_string(), exec_with("min(1, 2.0)", lenient()).expect("Failed to run"));
+    assert_eq!("2.5".to_string(), exec_with("max([1, 2.5, 2])", lenient()).expect("Failed to run"));
+    assert_eq!("3.5".to_string(), exec_with("sum([1, 2.5])", lenient()).expect("Failed to run"));
+    assert_eq!("[1, 2.5, 3]".to_string(), exec_with("sort([2.5, 1, 3])", lenient()).expect("Failed to run"));
+    assert_eq!("1.5".to_string(), exec_with("mean([1, 2.0])", lenient()).expect("Failed to run"));
+    assert_eq!(
+        exec_with("median([2.5, 1, 4])", lenient()).expect("Failed to run"),
+        exec_with("percentile([2.5, 1, 4], 50)", lenient()).expect("Failed to run")
+    );
+    assert!(exec("min(1, 2.0)").is_err());
+    assert!(exec("sum([1, 2.5])").is_err());
+}
+
+#[test]
+fn integration_test198() {
+    // The operands are reported in the order they were written
+    let why = exec("min(1, 2.0)").expect_err("Mixing types should fail");
+    assert_eq!(error::Pos::new(4, 10), why.position());
+    assert!(why.to_string().contains("`integer` and `float`"));
+    assert!(why.render("min(1, 2.0)").contains("\n1 | min(1, 2.0)\n  |     -^^--- `float`\n  |     |\n  |     `integer`"));
+
+    let why = exec("max([3, 1.5])").expect_err("Mixing types should fail");
+    assert!(why.to_string().contains("`integer` and `float`"));
+}
+
+#[test]
+fn integration_test199() {
+    // A lone `m` is metres, wherever it is, and decimals take duration suffixes
+    for src in &["now + 30m", "30m + now", "(now + 1h) + 30m", "let x = now; x + 30m"] {
+        let why = run(src).expect_err("Metres can't be added to a time").remove(0);
+        assert!(why.render(src).ends_with("help: `m` is metres, write `30 min` for minutes"));
+    }
+    assert_eq!("true".to_string(), exec("now + 30 min - now == 30 min as s").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("30 min + now - now == 30 min as s").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("(now + 1h) + 30 min - now == 1.5h").expect("Failed to run"));
+    assert_eq!("2026-12-24 23:30:00 UTC".to_string(), exec("2026-12-25 - 30 min").expect("Failed to run"));
+    assert_eq!("1h 30m".to_string(), exec("1.5h").expect("Failed to run"));
+    assert_eq!("2026-12-25 01:30:00 UTC".to_string(), exec("1.5h + 2026-12-25").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("1.5h == 1h30m").expect("Failed to run"));
+    assert_eq!("2 m".to_string(), exec("2m").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test200() {
+    // Integers round to significant figures like floats, but only decimal output rounds
+    assert_eq!("120000".to_string(), run("123456 round to 2 sig figs").expect("Failed to run"));
+    assert_eq!("-100".to_string(), run("-95 round to 1 sig figs").expect("Failed to run"));
+    assert_eq!("[100, 7]".to_string(), run("[123, 7] round to 1 sig figs").expect("Failed to run"));
+    assert_eq!("0xff".to_string(), run("255 as hex round to 1 sig figs").expect("Failed to run"));
+    let why = exec("2.5 as hex").expect_err("Floats have no hex form");
+    assert_eq!(error::ErrorType::TypeError, why.error_type());
+}
+
+#[test]
+fn integration_test201() {
+    // Numeric roots come with a note when the list may be missing some
+    let roots = exec("solve(sin(x) = 0, x)").expect("Failed to run");
+    assert!(roots.starts_with("[-15.707963267948966, "));
+    assert!(roots.ends_with("\nnote: solved numerically, these are the 10 roots nearest 0 in -100..100"));
+    assert!(exec("solve(x**3 = 1000000, x)").expect("Failed to run").contains("\nnote: "));
+    assert_eq!(
+        "[1.2599210498948732]\nnote: solved numerically, only looking for `x` in -100..100".to_string(),
+        exec("solve(x**3 - 2 = 0, x)").expect("Failed to run")
+    );
+    assert_eq!("[-2, 2]".to_string(), exec("solve(x**2 - 4 = 0, x)").expect("Failed to run"));
+    assert_eq!("3".to_string(), run("let r = solve(x**2 = 4, x); len(r) + 1").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test202() {
+    // Errors point into the whole input, bounds included
+    let src = "x from 0 to [1]";
+    let why = plot::plot_src(src, Options::default()).expect_err("A list isn't a bound");
+    assert_eq!(error::Pos::new(12, 15), why.position());
+    assert!(error::render_all(&[why], src).contains("\n1 | x from 0 to [1]\n  |             ^^^"));
+
+    let why = plot::plot_src("x from 5 to -5", Options::default()).expect_err("The range is empty");
+    assert_eq!(error::Pos::new(7, 14), why.position());
+    let why = plot::plot_src("y + 1", Options::default()).expect_err("`y` has no value");
+    assert_eq!(error::Pos::new(0, 1), why.position());
+    assert_eq!((-10.0, 10.0), plot::plot_src("x", Options::default()).expect("Failed to run").x);
+}
+
+#[test]
+fn integration_test203() {
+    // Arguments are evaluated in the caller's mode, only the values passed in are widened
+    let why = exec("sin(pi/2)").expect_err("Strict mode keeps integers and floats apart");
+    assert!(why.render("sin(pi/2)").ends_with("help: use `2.0` instead of `2` to divide with a float"));
+    assert_eq!("1".to_string(), exec("sin(pi/2.0)").expect("Failed to run"));
+    assert!(exec("sqrt(10.1 + 1)").is_err());
+    assert!(run("f(x) = x + 1; sqrt(f(10.1))").is_err());
+    assert_eq!("3".to_string(), exec("log(8, 2.0)").expect("Failed to run"));
+    assert_eq!("2".to_string(), exec("log(100)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test204() {
+    // Casts to a type bind as tightly as they always have, conversions to units loosely
+    let parsed = |src| explain(src, Options::default()).expect("Failed to explain").lines().next().map(str::to_string);
+    assert_eq!(Some("(pi * ((8 as float) ** 2)): ?".to_string()), parsed("pi * 8 as float ** 2"));
+    assert_eq!(Some("(10 / (4 as float)): ?".to_string()), parsed("10 / 4 as float"));
+    assert_eq!(Some("((((3 * kg) * (9.81 * m)) / (s ** 2)) as N): quantity".to_string()), parsed("3 kg * 9.81 m/s**2 as N"));
+
+    let lenient = Options {
+        mode: options::Mode::Lenient,
+        ..Options::default()
+    };
+    assert_eq!("201.06192982974676".to_string(), run_with("pi * 8 as float ** 2", lenient.clone()).expect("Failed to run"));
+    assert_eq!("2.5".to_string(), run_with("10 / 4 as float", lenient).expect("Failed to run"));
+    assert_eq!("2.5".to_string(), exec("10.0 / 4 as float").expect("Failed to run"));
+    assert_eq!("29.43 N".to_string(), exec("3 kg * 9.81 m/s**2 as N").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test205() {
+    // Small entries are judged against the size of the matrix, not a fixed threshold
+    assert_eq!("true".to_string(), exec("abs(det([[1e-11, 0.0], [0.0, 1e-11]]) - 1e-22) < 1e-30").expect("Failed to run"));
+    assert_eq!("2".to_string(), exec("rank([[1e-11, 0.0], [0.0, 1e-11]])").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("inv([[1e-11, 0.0], [0.0, 1e-11]])[0][0] == 1e11").expect("Failed to run"));
+    assert_eq!("1".to_string(), exec("rank([[1e-11, 2e-11], [2e-11, 4e-11]])").expect("Failed to run"));
+    assert!(exec("inv([[1.0, 2.0], [2.0, 4.0]])").is_err());
+}
+
+#[test]
+fn integration_test206() {
+    // Durations compare from either side, and out of range ones are written briefly
+    assert_eq!("true".to_string(), exec("1h < 3 days").expect("Failed to run"));
+    assert_eq!("false".to_string(), exec("3 days < 1h").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("3 days >= 1h").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("90 s <= 1h30m").expect("Failed to run"));
+    assert_eq!("true".to_string(), exec("2026-01-01 > 2025-12-31T23:00").expect("Failed to run"));
+    assert!(exec("2 m < 1h").is_err());
+
+    let why = exec("1e300 * 1h").expect_err("Too long for a duration");
+    assert_eq!("RuntimeError: col 0-10: duration `3.600e303 s` is out of range", why.to_string());
+}
+
+#[test]
+fn integration_test207() {
+    // Every statistic keeps integers and floats apart in strict mode, and widens under --lenient
+    for src in &["stddev([1, 2.5])", "variance([1, 2.5])", "mean([1, 2.5])", "corr([1, 2, 3], [2.0, 4.0, 7.0])", "linreg([1, 2.0], [1, 2])"] {
+        let why = exec(src).expect_err("Mixing types should fail");
+        assert_eq!(error::ErrorType::TypeError, why.error_type());
+    }
+    assert_eq!("0.7071067811865476".to_string(), exec("stddev([1, 2])").expect("Failed to run"));
+
+    let lenient = Options {
+        mode: options::Mode::Lenient,
+        ..Options::default()
+    };
+    assert_eq!("1.0606601717798212".to_string(), run_with("stddev([1, 2.5])", lenient.clone()).expect("Failed to run"));
+    assert_eq!("[1, 0]".to_string(), run_with("linreg([1, 2.0], [1, 2])", lenient).expect("Failed to run"));
+}
+
+#[test]
+fn integration_test208() {
+    // Counting functions keep to the digit limit in the options
+    let options = Options {
+        max_digits: 10,
+        ..Options::default()
+    };
+    let why = exec_with("factorial(20)", options.clone()).expect_err("20! has 19 digits");
+    assert_eq!("LimitError: col 0-13: result is larger than the limit of 10 digits", why.to_string());
+    assert!(exec_with("nPr(30, 15)", options.clone()).is_err());
+    assert!(exec_with("nCr(100, 50)", options.clone()).is_err());
+    assert_eq!("3628800".to_string(), exec_with("factorial(10)", options).expect("Failed to run"));
+    assert_eq!("2432902008176640000".to_string(), exec("factorial(20)").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test209() {
+    // Solving numerically pays for every sample and step from the step and time budgets
+    let options = Options {
+        max_steps: 5000,
+        ..Options::default()
+    };
+    let why = exec_with("solve(sin(x) = 0.5, x)", options).expect_err("The scan takes more than 5000 steps");
+    assert_eq!(error::ErrorType::LimitError, why.error_type());
+
+    let options = Options {
+        deadline: Some(std::time::Instant::now()),
+        ..Options::default()
+    };
+    let why = exec_with("solve(x**3 - 2 = 0, x)", options).expect_err("Solving past the deadline should fail");
+    assert_eq!(error::ErrorType::LimitError, why.error_type());
+    assert!(exec("solve(sin(x) = 0.5, x)").is_ok());
+}
+
+#[test]
+fn integration_test210() {
+    // Matrix elimination stops once the deadline passes instead of running on unseen
+    let options = Options {
+        deadline: Some(std::time::Instant::now()),
+        ..Options::default()
+    };
+    for src in &["det([[1, 2], [3, 4]])", "inv([[1, 2], [3, 4]])", "rank([[1, 2], [3, 4]])", "solve([[1, 2], [3, 4]], [5, 6])"] {
+        let why = exec_with(src, options.clone()).expect_err("Eliminating past the deadline should fail");
+        assert_eq!(error::ErrorType::LimitError, why.error_type());
+    }
+    assert_eq!("-2".to_string(), exec("det([[1, 2], [3, 4]])").expect("Failed to run"));
+}
+
+#[test]
+fn integration_test211() {
+    // A negated operand's span starts at the `-`
+    let why = exec("(-1) ** (1/2)").expect_err("An integer to a rational power should fail");
+    assert_eq!("TypeError: col 1-12: cannot apply operator `power` on types `integer` and `rational`", why.to_string());
+    let why = exec("-\"a\"").expect_err("Strings cannot be negated");
+    assert_eq!("TypeError: col 0-4: cannot make type `string` negative", why.to_string());
+}
+
+/// Values for fuzzing, alone they are all valid input
+#[cfg(test)]
+const FUZZ_VALUES: &[&str] = &[
+    "0", "1", "2", "-1", "2.5", "0.0", "1e308", "0xff", "0b101", "1_000", ".5", "3i", "10**400",
+    "x", "y", "n", "f", "pi", "e", "i", "true", "false", "now", "today", "2026-01-01", "3h",
+    "90s", "km", "m", "s", "kg", "\"ab\"", "\"\"", "\"1.5\"",
+];
+
+#[cfg(test)]
+const FUZZ_FUNCTIONS: &[&str] = &[
+    "sin", "sqrt", "ln", "log", "abs", "min", "max", "floor", "round", "gcd", "lcm", "len", "sum",
+    "sort", "det", "inv", "transpose", "rank", "solve", "mean", "median", "mode", "variance",
+    "percentile", "corr", "linreg", "factorial", "nCr", "upper", "repeat", "split", "join", "diff",
+    "simplify", "d/dx", "f",
+];
+
+#[cfg(test)]
+const FUZZ_OPERATORS: &[&str] = &[
+    "+", "-", "*", "/", "//", "%", "**", "@", "&", "|", "^", "<<", ">>", "<", ">", "<=", ">=",
+    "==", "!=", "&&", "||", "=",
+];
+
+/// Targets of `as`
+#[cfg(test)]
+const FUZZ_TARGETS: &[&str] = &["km", "s", "days", "mph", "hex", "sci", "int", "float", "string"];
+
+/// The rest of the language, meaningful only next to other pieces
+#[cfg(test)]
+const FUZZ_PUNCTUATION: &[&str] = &[
+    "let", "if", "then", "else", "as", "~", "!", "(", ")", "[", "]", ",", ":", ";",
+    "round to 3 sig figs",
+];
+
+#[cfg(test)]
+use proptest::prelude::*;
+
+/// Evaluate every way there is, failing if any of them panics
+#[cfg(test)]
+fn fuzz_eval(src: String) {
+    on_eval_stack(move || {
+        let _ = exec(&src);
+        let _ = run(&src);
+        let _ = explain(&src, Options::default());
+        let _ = trace(&src, Options::default());
+    });
+}
+
+/// Any piece of input
+#[cfg(test)]
+fn fuzz_token() -> impl Strategy<Value = &'static str> {
+    prop_oneof![
+        prop::sample::select(FUZZ_VALUES),
+        prop::sample::select(FUZZ_FUNCTIONS),
+        prop::sample::select(FUZZ_OPERATORS),
+        prop::sample::select(FUZZ_TARGETS),
+        prop::sample::select(FUZZ_PUNCTUATION),
+    ]
+}
+
+/// Well formed expressions, so fuzzing gets past the parser into evaluation
+#[cfg(test)]
+fn fuzz_expr() -> impl Strategy<Value = String> {
+    let leaf = prop::sample::select(FUZZ_VALUES).prop_map(str::to_string);
+    leaf.prop_recursive(4, 32, 3, |inner| {
+        prop_oneof![
+            (inner.clone(), prop::sample::select(FUZZ_OPERATORS), inner.clone())
+                .prop_map(|(left, op, right)| format!("({} {} {})", left, op, right)),
+            (prop::sample::select(FUZZ_FUNCTIONS), prop::collection::vec(inner.clone(), 0..3))
+                .prop_map(|(func, args)| format!("{}({})", func, args.join(", "))),
+            prop::collection::vec(inner.clone(), 0..4).prop_map(|items| format!("[{}]", items.join(", "))),
+            (inner.clone(), inner.clone(), inner.clone())
+                .prop_map(|(cond, then, other)| format!("(if {} then {} else {})", cond, then, other)),
+            (inner.clone(), inner.clone()).prop_map(|(list, index)| format!("{}[{}]", list, index)),
+            (inner.clone(), prop::sample::select(FUZZ_TARGETS))
+                .prop_map(|(value, target)| format!("({} as {})", value, target)),
+            inner.clone().prop_map(|value| format!("-{}", value)),
+            (inner.clone(), inner).prop_map(|(body, arg)| format!("f(x) = {}; let y = {}; f(y)", body, arg)),
+        ]
+    })
+}
+
+#[cfg(test)]
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1000))]
+
+    /// Any run of tokens evaluates to a value or an error, without panicking
+    #[test]
+    fn fuzz_tokens(tokens in prop::collection::vec(fuzz_token(), 0..24), spaced in any::<bool>()) {
+        fuzz_eval(tokens.join(if spaced { " " } else { "" }));
+    }
+
+    /// As does any well formed expression, whatever the types
+    #[test]
+    fn fuzz_expressions(src in fuzz_expr()) {
+        fuzz_eval(src);
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Instant;
+
+use crate::core::eval::exec::{DEFAULT_MAX_DEPTH, DEFAULT_MAX_DIGITS, DEFAULT_MAX_OUTPUT, DEFAULT_MAX_STEPS};
+
+/// How operands of different types are combined
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Mode {
+    /// `10.1 + 1` is a TypeError
+    Strict,
+    /// Integers widen to floats, and booleans to integers, when mixed
+    Lenient,
+}
+
+/// Base integers are written in
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Radix {
+    Decimal,
+    Hex, // 0xff
+    Oct, // 0o17
+    Bin, // 0b1010
+}
+
+/// How floats are written
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Notation {
+    /// `1234.5`
+    Plain,
+    /// `1.2345e3`
+    Scientific,
+}
+
+/// Stack for a thread evaluating, debug builds take over 10 KB for each level of nesting
+pub(crate) const STACK_SIZE: usize = 32 * 1024 * 1024;
+
+/// Most significant figures asked for, more than a float holds
+pub(crate) const MAX_PRECISION: usize = 17;
+
+/// How the result is written out
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct Format {
+    pub(crate) radix: Radix,
+    pub(crate) notation: Notation,
+    /// Significant figures numbers are rounded to, integers written in hex, octal or binary stay exact
+    pub(crate) precision: Option<usize>,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            radix: Radix::Decimal,
+            notation: Notation::Plain,
+            precision: None,
+        }
+    }
+}
+
+/// Whether `as target` only changes how the result is written, as `as hex` does
+pub(crate) fn is_conversion(target: &str) -> bool {
+    matches!(target, "hex" | "oct" | "bin" | "sci")
+}
+
+impl Format {
+    /// Apply an output conversion such as `as hex`
+    pub(crate) fn convert(&mut self, target: &str) {
+        match target {
+            "hex" => self.radix = Radix::Hex,
+            "oct" => self.radix = Radix::Oct,
+            "bin" => self.radix = Radix::Bin,
+            "sci" => self.notation = Notation::Scientific,
+            _ => {}
+        }
+    }
+}
+
+/// Settings for a single evaluation
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub(crate) mode: Mode,
+    pub(crate) max_digits: usize,
+    pub(crate) format: Format,
+    /// Expressions evaluated before giving up, across the whole input
+    pub(crate) max_steps: usize,
+    /// Expressions parsed or evaluated inside one another, including through function calls
+    pub(crate) max_depth: usize,
+    /// Longest result written out, in characters
+    pub(crate) max_output: usize,
+    /// When evaluation stops, shared by every executer made with these options
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mode: Mode::Strict,
+            max_digits: DEFAULT_MAX_DIGITS,
+            format: Format::default(),
+            max_steps: DEFAULT_MAX_STEPS,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_output: DEFAULT_MAX_OUTPUT,
+            deadline: None,
+        }
+    }
+}
@@ -1,21 +1,88 @@
 use crate::core::eval::ast;
 use crate::core::eval::error::*;
+use crate::core::eval::exec::DEFAULT_MAX_DEPTH;
 use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Binding power of a call, tighter than any operator
+pub(crate) const CALL_BP: u16 = 80;
+
+/// Binding power of a number followed by a name, as in `5 km`: tighter than
+/// `*` so `9.81 m/s` is `(9.81 m)/s`, looser than `**` so `5 m**2` squares the unit
+const JUXTAPOSE_BP: u16 = 62;
+
+/// Binding power of `as` converting to a unit or an output form such as `hex`: below `*` and
+/// `/` so `3 kg * 9.81 m/s**2 as N` converts the whole product
+const CONVERT_BP: u16 = 55;
 
 pub(crate) struct Parser<'a> {
     tokens: Vec<ast::Token<'a>>,
     pos: usize,
+    depth: usize, // Of `expr` calls being parsed
+    max_depth: usize, // Of brackets, prefix operators and `**`, so `((((...` can't overflow the stack
     infix_op: HashMap<ast::Operator, u16>,
     prefix_op: HashMap<ast::Operator, u16>,
+    errors: Vec<Error>,  // Recovered from, to be reported together
+    skipped: Vec<usize>, // Starts of tokens right after input the lexer skipped
 }
 
 impl<'a> Parser<'a> {
-    pub(crate) fn new(tokens: Vec<ast::Token<'a>>) -> Self {
+    pub(crate) fn new(mut tokens: Vec<ast::Token<'a>>) -> Self {
+        // Lookahead past the end lands on the trailing EOF, so there must be one
+        if tokens.last().map(|tok| tok.tok_type) != Some(ast::TokenType::EOF) {
+            let end = tokens.last().map_or(0, |tok| tok.pos.end);
+            tokens.push(ast::Token::new(ast::TokenType::EOF, "", end, end));
+        }
+
         Parser {
             tokens,
             pos: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
             prefix_op: HashMap::new(),
             infix_op: HashMap::new(),
+            errors: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Nest no deeper than `max_depth`, the same limit evaluating has
+    pub(crate) fn limit_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Leave out syntax errors caused by input the lexer skipped: `2 × 3` is missing an
+    /// operator only because `×` isn't one
+    pub(crate) fn skipping(&mut self, lex_errors: &[Error]) {
+        self.skipped = lex_errors
+            .iter()
+            .filter_map(|why| self.tokens.iter().find(|tok| tok.pos.start >= why.position().end))
+            .map(|tok| tok.pos.start)
+            .collect();
+    }
+
+    /// Keep an error to report with the others, unless it only follows from an earlier one
+    fn report(&mut self, why: Error) {
+        let start = why.position().start;
+        if !self.skipped.contains(&start) && self.errors.iter().all(|seen| seen.position().start != start) {
+            self.errors.push(why);
+        }
+    }
+
+    /// Skip ahead to one of `stops` outside of any brackets, to carry on after an error.
+    /// Never skips past the end of a statement.
+    fn recover(&mut self, stops: &[ast::TokenType]) {
+        let mut depth = 0;
+        loop {
+            let tok_type = self.peek().tok_type;
+            match tok_type {
+                ast::TokenType::EOF | ast::TokenType::Semicolon => break,
+                ast::TokenType::LP | ast::TokenType::LB => depth += 1,
+                ast::TokenType::RP | ast::TokenType::RB if depth > 0 => depth -= 1,
+                _ if stops.contains(&tok_type) => break,
+                _ => {}
+            }
+            self.advance();
         }
     }
 
@@ -27,8 +94,28 @@ impl<'a> Parser<'a> {
         self.prefix_op.insert(operator, bp);
     }
 
+    /// Binding power of an infix operator, for writing expressions back out
+    pub(crate) fn infix_power(&self, operator: ast::Operator) -> Option<u16> {
+        self.infix_op.get(&operator).copied()
+    }
+
+    /// Binding power of `as` followed by `target`, tight for the types `float` and `int` so
+    /// `10 / 4 as float` only casts the `4`
+    pub(crate) fn cast_power(&self, target: &str) -> u16 {
+        match target {
+            "float" | "int" => self.infix_op.get(&ast::Operator::As).copied().unwrap_or(CONVERT_BP),
+            _ => CONVERT_BP,
+        }
+    }
+
+    pub(crate) fn prefix_power(&self, operator: ast::Operator) -> Option<u16> {
+        self.prefix_op.get(&operator).copied()
+    }
+
     /// Configure precedence tables
     pub(crate) fn config(&mut self) {
+        self.register_infix(ast::Operator::Equation, 5);
+
         self.register_infix(ast::Operator::LOr, 10);
         self.register_infix(ast::Operator::LAnd, 10);
 
@@ -51,6 +138,7 @@ impl<'a> Parser<'a> {
         self.register_infix(ast::Operator::Sub, 50);
 
         self.register_infix(ast::Operator::Mul, 60);
+        self.register_infix(ast::Operator::MatMul, 60);
         self.register_infix(ast::Operator::Div, 60);
         self.register_infix(ast::Operator::IntDiv, 60);
         self.register_infix(ast::Operator::Mod, 60);
@@ -58,6 +146,7 @@ impl<'a> Parser<'a> {
         // TODO: make powers right assoc
         self.register_infix(ast::Operator::Pow, 65);
 
+        // Casts to a type such as `float`, converting to units binds at `CONVERT_BP`
         self.register_infix(ast::Operator::As, 70);
 
         self.register_prefix(ast::Operator::LNot, 70);
@@ -67,7 +156,29 @@ impl<'a> Parser<'a> {
     }
 
     fn peek(&self) -> ast::Token<'a> {
-        self.tokens[self.pos]
+        self.peek_nth(0)
+    }
+
+    /// Look `n` tokens ahead without advancing, clamped to the trailing EOF
+    fn peek_nth(&self, n: usize) -> ast::Token<'a> {
+        match self.tokens.get(self.pos + n) {
+            Some(tok) => *tok,
+            None => self.tokens[self.tokens.len() - 1],
+        }
+    }
+
+    fn expect(&mut self, tok_type: ast::TokenType) -> Result<ast::Token<'a>, Error> {
+        let next = self.advance();
+        if next.tok_type == tok_type {
+            Ok(next)
+        } else {
+            self.retreat();
+            Err(Error::new(
+                format!("expected {}, found {}", tok_type, next),
+                ErrorType::SyntaxError,
+                next.pos,
+            ))
+        }
     }
 
     fn advance(&mut self) -> ast::Token<'a> {
@@ -76,30 +187,45 @@ impl<'a> Parser<'a> {
                 self.pos += 1;
                 *tok
             }
-            None => dbg!(self.tokens[self.tokens.len() - 1]),
+            None => self.peek(),
         }
     }
 
-    fn bp_infix(&self, op: ast::Token<'a>) -> u16 {
-        match op.tok_type {
-            ast::TokenType::Operator(oper) => self.infix_op[&oper],
-            _ => unreachable!(),
-        }
+    /// Step back over a token that turned out to be unexpected, so error recovery can stop
+    /// at it, as at the `;` of `1 + ; 2`
+    fn retreat(&mut self) {
+        self.pos = self.pos.saturating_sub(1);
     }
 
-    fn bp_prefix(&self, op: &ast::Token<'a>) -> u16 {
-        self.prefix_op[&op.unwrap_op()]
+    fn bp_infix(&self, op: ast::Operator, pos: Pos) -> Result<u16, Error> {
+        self.infix_op.get(&op).copied().ok_or_else(|| {
+            Error::new(
+                format!("cannot use {} as an infix operator", op),
+                ErrorType::SyntaxError,
+                pos,
+            )
+        })
+    }
+
+    fn bp_prefix(&self, op: &ast::Token<'a>) -> Result<u16, Error> {
+        self.prefix_op.get(&op.op()?).copied().ok_or_else(|| {
+            Error::new(
+                format!("cannot use {} as a prefix operator", op),
+                ErrorType::SyntaxError,
+                op.pos,
+            )
+        })
     }
 
     fn item(&mut self) -> Result<ast::Expression<'a>, Error> {
         // Null-Denotation
         if let Ok(prefix) = self.get_operator_prefix() {
-            let binding_power = self.bp_prefix(&prefix);
+            let binding_power = self.bp_prefix(&prefix)?;
             let item = self.expr(binding_power)?;
             return Ok(ast::Expression {
                 pos: Pos::new(prefix.pos.start, item.pos.end),
                 expr: ast::ExpressionKind::PrefixOp(ast::PrefixOp {
-                    op: prefix.unwrap_op(),
+                    op: prefix.op()?,
                     value: Box::new(item),
                 }),
             });
@@ -107,14 +233,34 @@ impl<'a> Parser<'a> {
 
         let next = self.advance();
         match next.tok_type {
-            ast::TokenType::Integer => Ok(ast::Expression {
+            ast::TokenType::Integer => self.juxtapose(ast::Expression {
                 expr: ast::ExpressionKind::Integer(next.value),
                 pos: next.pos,
             }),
-            ast::TokenType::Float => Ok(ast::Expression {
+            ast::TokenType::Float => self.juxtapose(ast::Expression {
                 expr: ast::ExpressionKind::Float(next.value),
                 pos: next.pos,
             }),
+            ast::TokenType::Imaginary => Ok(ast::Expression {
+                expr: ast::ExpressionKind::Imaginary(next.value),
+                pos: next.pos,
+            }),
+            ast::TokenType::Date => Ok(ast::Expression {
+                expr: ast::ExpressionKind::Date(next.value),
+                pos: next.pos,
+            }),
+            ast::TokenType::DateTime => Ok(ast::Expression {
+                expr: ast::ExpressionKind::DateTime(next.value),
+                pos: next.pos,
+            }),
+            ast::TokenType::Duration => Ok(ast::Expression {
+                expr: ast::ExpressionKind::Duration(next.value),
+                pos: next.pos,
+            }),
+            ast::TokenType::Str => Ok(ast::Expression {
+                expr: ast::ExpressionKind::Str(next.value),
+                pos: next.pos,
+            }),
             ast::TokenType::Identifier => Ok(ast::Expression {
                 expr: ast::ExpressionKind::Ident(next.value),
                 pos: next.pos,
@@ -127,26 +273,60 @@ impl<'a> Parser<'a> {
                 expr: ast::ExpressionKind::True,
                 pos: next.pos
             }),
+            ast::TokenType::Operator(ast::Operator::BOr) => {
+                let params = self.params(next.tok_type)?;
+                self.lambda(next.pos.start, params)
+            }
+            ast::TokenType::Operator(ast::Operator::LOr) => self.lambda(next.pos.start, Vec::new()),
+            ast::TokenType::If => self.conditional(next.pos.start),
+            ast::TokenType::LB => {
+                let (elements, close) = self.elements(ast::TokenType::RB)?;
+                Ok(ast::Expression {
+                    expr: ast::ExpressionKind::List(elements),
+                    pos: Pos::new(next.pos.start, close.pos.end),
+                })
+            }
             ast::TokenType::LP => {
                 let expr = self.expr(0)?;
                 let next_tok = self.advance();
                 if ast::TokenType::RP == next_tok.tok_type {
                     return Ok(expr);
                 }
+                self.retreat();
                 return Err(Error::new(
                     format!("expected closing brace, found {}", next_tok),
                     ErrorType::SyntaxError,
                     next_tok.pos,
                 ));
             }
-            _ => Err(Error::new(
-                format!("unexpected {}", next),
-                ErrorType::SyntaxError,
-                next.pos,
-            )),
+            _ => {
+                self.retreat();
+                Err(Error::new(
+                    format!("unexpected {}", next),
+                    ErrorType::SyntaxError,
+                    next.pos,
+                ))
+            }
         }
     }
 
+    /// Read a number directly followed by a name, as in `5 km`, as a product
+    fn juxtapose(&mut self, number: ast::Expression<'a>) -> Result<ast::Expression<'a>, Error> {
+        if self.peek().tok_type != ast::TokenType::Identifier {
+            return Ok(number);
+        }
+
+        let right = self.expr(JUXTAPOSE_BP)?;
+        Ok(ast::Expression {
+            pos: Pos::new(number.pos.start, right.pos.end),
+            expr: ast::ExpressionKind::InfixOp(ast::InfixOp {
+                op: ast::Operator::Mul,
+                left: Box::new(number),
+                right: Box::new(right),
+            }),
+        })
+    }
+
     fn get_operator_prefix(&mut self) -> Result<ast::Token<'a>, Error> {
         let pos = self.pos;
         let potential_op = self.advance();
@@ -169,7 +349,12 @@ impl<'a> Parser<'a> {
     fn get_operator_infix(&mut self) -> Result<ast::Operator, Error> {
         let pos = self.pos;
         let potential_op = self.peek();
-        if let ast::TokenType::Operator(p_op) = potential_op.tok_type {
+        // Assignments are statements, any other `=` joins the sides of an equation
+        let potential_type = match potential_op.tok_type {
+            ast::TokenType::Assign => ast::TokenType::Operator(ast::Operator::Equation),
+            tok_type => tok_type,
+        };
+        if let ast::TokenType::Operator(p_op) = potential_type {
             match self.infix_op.get(&p_op) {
                 // its a valid operator
                 Some(_) => return Ok(p_op),
@@ -190,10 +375,13 @@ impl<'a> Parser<'a> {
         &mut self,
         left: ast::Expression<'a>,
         operator: ast::Operator,
+        binding_power: u16,
     ) -> Result<ast::Expression<'a>, Error> {
-        let binding_power = self.infix_op[&operator];
 
-        let right = self.expr(binding_power)?;
+        let right = match operator {
+            ast::Operator::As => self.cast_target()?,
+            _ => self.expr(binding_power)?,
+        };
 
         Ok(ast::Expression {
             pos: Pos::new(left.pos.start, right.pos.end),
@@ -205,7 +393,171 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// The right of `as`: a type, or a unit such as `km/h` or `m/s**2`
+    fn cast_target(&mut self) -> Result<ast::Expression<'a>, Error> {
+        let mut target = self.unit_power()?;
+
+        while let Some(op) = self.unit_operator() {
+            self.advance(); // Advance operator
+            let right = self.unit_power()?;
+            target = ast::Expression {
+                pos: Pos::new(target.pos.start, right.pos.end),
+                expr: ast::ExpressionKind::InfixOp(ast::InfixOp {
+                    op,
+                    left: Box::new(target),
+                    right: Box::new(right),
+                }),
+            };
+        }
+
+        Ok(target)
+    }
+
+    /// A `*` or `/` joining two units, leaving `x as float * 2` to the caller
+    fn unit_operator(&self) -> Option<ast::Operator> {
+        match (self.peek().tok_type, self.peek_nth(1).tok_type) {
+            (ast::TokenType::Operator(op), ast::TokenType::Identifier)
+                if op == ast::Operator::Mul || op == ast::Operator::Div =>
+            {
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    /// A unit name, optionally raised to a whole power like `s**-2`
+    fn unit_power(&mut self) -> Result<ast::Expression<'a>, Error> {
+        let name = self.expect(ast::TokenType::Identifier)?;
+        let base = ast::Expression {
+            expr: ast::ExpressionKind::Ident(name.value),
+            pos: name.pos,
+        };
+
+        let exponent_follows = matches!(
+            (self.peek_nth(1).tok_type, self.peek_nth(2).tok_type),
+            (ast::TokenType::Integer, _)
+                | (ast::TokenType::Operator(ast::Operator::Sub), ast::TokenType::Integer)
+        );
+        // `8 as float ** 2` squares the float, types take no power
+        let is_type = self.cast_power(name.value) != CONVERT_BP;
+        if self.peek().tok_type != ast::TokenType::Operator(ast::Operator::Pow) || !exponent_follows || is_type {
+            return Ok(base);
+        }
+
+        self.advance(); // Advance `**`
+        let exponent = self.item()?;
+        Ok(ast::Expression {
+            pos: Pos::new(base.pos.start, exponent.pos.end),
+            expr: ast::ExpressionKind::InfixOp(ast::InfixOp {
+                op: ast::Operator::Pow,
+                left: Box::new(base),
+                right: Box::new(exponent),
+            }),
+        })
+    }
+
+    /// Comma separated expressions, up to and including `close`
+    fn elements(
+        &mut self,
+        close: ast::TokenType,
+    ) -> Result<(Vec<ast::Expression<'a>>, ast::Token<'a>), Error> {
+        let mut elements = Vec::new();
+        let mut failed = None;
+        if self.peek().tok_type != close {
+            loop {
+                match self.expr(0) {
+                    Ok(element) => elements.push(element),
+                    // Carry on with the next element, which may have errors of its own
+                    Err(why) => {
+                        match failed {
+                            None => failed = Some(why),
+                            Some(_) => self.report(why),
+                        }
+                        self.recover(&[ast::TokenType::Comma, close]);
+                    }
+                }
+
+                if self.peek().tok_type != ast::TokenType::Comma {
+                    break;
+                }
+                self.advance(); // Advance `,`
+            }
+        }
+
+        let close = self.expect(close);
+        match failed {
+            Some(why) => {
+                if let Err(unclosed) = close {
+                    self.report(unclosed);
+                }
+                Err(why)
+            }
+            None => Ok((elements, close?)),
+        }
+    }
+
+    fn call(&mut self, func: ast::Expression<'a>) -> Result<ast::Expression<'a>, Error> {
+        self.expect(ast::TokenType::LP)?;
+        let (args, close) = self.elements(ast::TokenType::RP)?;
+
+        Ok(ast::Expression {
+            pos: Pos::new(func.pos.start, close.pos.end),
+            expr: ast::ExpressionKind::Call(ast::Call {
+                func: Box::new(func),
+                args,
+            }),
+        })
+    }
+
+    /// `xs[i]`, or a slice such as `xs[1:3]`, `xs[:2]` or `xs[-2:]`
+    fn index(&mut self, value: ast::Expression<'a>) -> Result<ast::Expression<'a>, Error> {
+        self.expect(ast::TokenType::LB)?;
+
+        let start = match self.peek().tok_type {
+            ast::TokenType::Colon => None,
+            _ => Some(Box::new(self.expr(0)?)),
+        };
+
+        let subscript = match start {
+            Some(item) if self.peek().tok_type != ast::TokenType::Colon => {
+                ast::Subscript::Item(item)
+            }
+            start => {
+                self.expect(ast::TokenType::Colon)?;
+                let end = match self.peek().tok_type {
+                    ast::TokenType::RB => None,
+                    _ => Some(Box::new(self.expr(0)?)),
+                };
+                ast::Subscript::Slice(start, end)
+            }
+        };
+
+        let close = self.expect(ast::TokenType::RB)?;
+        Ok(ast::Expression {
+            pos: Pos::new(value.pos.start, close.pos.end),
+            expr: ast::ExpressionKind::Index(ast::Index {
+                value: Box::new(value),
+                subscript,
+            }),
+        })
+    }
+
     pub(crate) fn expr(&mut self, prec: u16) -> Result<ast::Expression<'a>, Error> {
+        if self.depth >= self.max_depth {
+            return Err(Error::new(
+                format!("expression is nested deeper than the limit of {}", self.max_depth),
+                ErrorType::LimitError,
+                self.peek().pos,
+            ));
+        }
+
+        self.depth += 1;
+        let expr = self.nested(prec);
+        self.depth -= 1;
+        expr
+    }
+
+    fn nested(&mut self, prec: u16) -> Result<ast::Expression<'a>, Error> {
         let mut left = self.item()?;
 
         let mut operator;
@@ -215,6 +567,24 @@ impl<'a> Parser<'a> {
                 break;
             }
 
+            if self.peek().tok_type == ast::TokenType::LP {
+                if CALL_BP <= prec {
+                    break;
+                }
+
+                left = self.call(left)?;
+                continue;
+            }
+
+            if self.peek().tok_type == ast::TokenType::LB {
+                if CALL_BP <= prec {
+                    break;
+                }
+
+                left = self.index(left)?;
+                continue;
+            }
+
             match self.get_operator_infix() {
                 Ok(oper) => {
                     operator = oper;
@@ -222,7 +592,10 @@ impl<'a> Parser<'a> {
                 Err(_) => return Ok(left),
             }
 
-            let binding_power = self.infix_op[&operator];
+            let binding_power = match operator {
+                ast::Operator::As => self.cast_power(self.peek_nth(1).value),
+                _ => self.bp_infix(operator, self.peek().pos)?,
+            };
 
             if !(binding_power > prec) {
                 break;
@@ -230,7 +603,7 @@ impl<'a> Parser<'a> {
 
             self.advance(); // Advance operator
 
-            match self.led(left, operator) {
+            match self.led(left, operator, binding_power) {
                 Ok(val) => {
                     left = val;
                 }
@@ -242,4 +615,177 @@ impl<'a> Parser<'a> {
 
         Ok(left)
     }
+
+    /// Comma separated parameter names, up to and including `close`
+    fn params(&mut self, close: ast::TokenType) -> Result<Vec<&'a str>, Error> {
+        let mut params = Vec::new();
+        if self.peek().tok_type != close {
+            loop {
+                params.push(self.expect(ast::TokenType::Identifier)?.value);
+                if self.peek().tok_type != ast::TokenType::Comma {
+                    break;
+                }
+                self.advance(); // Advance `,`
+            }
+        }
+
+        self.expect(close)?;
+        Ok(params)
+    }
+
+    fn lambda(&mut self, start: usize, params: Vec<&'a str>) -> Result<ast::Expression<'a>, Error> {
+        let body = self.expr(0)?;
+        Ok(ast::Expression {
+            pos: Pos::new(start, body.pos.end),
+            expr: ast::ExpressionKind::Lambda(ast::Lambda {
+                params,
+                body: Rc::new(body),
+            }),
+        })
+    }
+
+    /// `if cond then a else b`, after the `if`. Like a lambda body, `b` reaches as far
+    /// right as it can.
+    fn conditional(&mut self, start: usize) -> Result<ast::Expression<'a>, Error> {
+        let cond = self.expr(0)?;
+        self.expect(ast::TokenType::Then)?;
+        let then = self.expr(0)?;
+        self.expect(ast::TokenType::Else)?;
+        let otherwise = self.expr(0)?;
+
+        Ok(ast::Expression {
+            pos: Pos::new(start, otherwise.pos.end),
+            expr: ast::ExpressionKind::If(ast::If {
+                cond: Box::new(cond),
+                then: Box::new(then),
+                otherwise: Box::new(otherwise),
+            }),
+        })
+    }
+
+    /// Check for `name(a, b) =` without consuming anything
+    fn is_function_def(&self) -> bool {
+        if self.peek().tok_type != ast::TokenType::Identifier
+            || self.peek_nth(1).tok_type != ast::TokenType::LP
+        {
+            return false;
+        }
+
+        let mut n = 2;
+        loop {
+            match self.peek_nth(n).tok_type {
+                ast::TokenType::Identifier | ast::TokenType::Comma => n += 1,
+                ast::TokenType::RP => return self.peek_nth(n + 1).tok_type == ast::TokenType::Assign,
+                _ => return false,
+            }
+        }
+    }
+
+    fn assignment(&mut self) -> Result<ast::Assign<'a>, Error> {
+        let name = self.expect(ast::TokenType::Identifier)?;
+        let params = if self.peek().tok_type == ast::TokenType::LP {
+            self.advance();
+            Some(self.params(ast::TokenType::RP)?)
+        } else {
+            None
+        };
+
+        self.expect(ast::TokenType::Assign)?;
+        let mut value = self.expr(0)?;
+
+        // `f(x) = body` is sugar for `f = |x| body`
+        if let Some(params) = params {
+            value = ast::Expression {
+                pos: Pos::new(name.pos.start, value.pos.end),
+                expr: ast::ExpressionKind::Lambda(ast::Lambda {
+                    params,
+                    body: Rc::new(value),
+                }),
+            };
+        }
+
+        Ok(ast::Assign {
+            name: name.value,
+            name_pos: name.pos,
+            value: Box::new(value),
+        })
+    }
+
+    fn statement(&mut self) -> Result<ast::Statement<'a>, Error> {
+        let start = self.peek().pos.start;
+        let stmt = match (self.peek().tok_type, self.peek_nth(1).tok_type) {
+            (ast::TokenType::Let, _) => {
+                self.advance();
+                ast::StatementKind::Let(self.assignment()?)
+            }
+            (ast::TokenType::Identifier, ast::TokenType::Assign) => {
+                ast::StatementKind::Assign(self.assignment()?)
+            }
+            (ast::TokenType::Identifier, ast::TokenType::LP) if self.is_function_def() => {
+                ast::StatementKind::Assign(self.assignment()?)
+            }
+            _ => ast::StatementKind::Expr(self.expr(0)?),
+        };
+
+        let end = match &stmt {
+            ast::StatementKind::Let(assign) | ast::StatementKind::Assign(assign) => {
+                assign.value.pos.end
+            }
+            ast::StatementKind::Expr(expr) => expr.pos.end,
+        };
+
+        Ok(ast::Statement {
+            stmt,
+            pos: Pos::new(start, end),
+        })
+    }
+
+    /// Parse `,` separated expressions until the end of input, such as `sin(x), x**2`
+    pub(crate) fn expressions(&mut self) -> Result<Vec<ast::Expression<'a>>, Error> {
+        Ok(self.elements(ast::TokenType::EOF)?.0)
+    }
+
+    /// Parse `;` separated statements until the end of input, carrying on past syntax
+    /// errors to report every one of them
+    pub(crate) fn program(&mut self) -> Result<Vec<ast::Statement<'a>>, Vec<Error>> {
+        let mut statements = Vec::new();
+
+        loop {
+            match self.statement() {
+                Ok(statement) => {
+                    statements.push(statement);
+
+                    let next = self.peek();
+                    if !matches!(next.tok_type, ast::TokenType::Semicolon | ast::TokenType::EOF) {
+                        self.report(Error::new(
+                            format!("expected `;` or end of file, found {}", next),
+                            ErrorType::SyntaxError,
+                            next.pos,
+                        ));
+                    }
+                }
+                Err(why) => self.report(why),
+            }
+
+            // Pick up again at the next statement
+            self.recover(&[]);
+            if self.peek().tok_type == ast::TokenType::EOF {
+                break;
+            }
+            self.advance(); // Advance `;`
+
+            // Allow a trailing `;`
+            if self.peek().tok_type == ast::TokenType::EOF {
+                break;
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            let mut errors = std::mem::take(&mut self.errors);
+            errors.sort_by_key(|why| why.position().start);
+            Err(errors)
+        }
+    }
 }
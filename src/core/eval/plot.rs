@@ -0,0 +1,306 @@
+use crate::core::eval::ast;
+use crate::core::eval::error::*;
+use crate::core::eval::exec::{big_to_f64, ExecutionExpr, ExecutionExpr::*, Executer};
+use crate::core::eval::options::{Mode, Options};
+use crate::core::eval::{lexer, parser};
+
+use num::traits::ToPrimitive;
+
+/// Points sampled across the range for every curve
+const SAMPLES: usize = 800;
+
+/// Most curves in one plot, one per colour of the legend
+pub(crate) const MAX_CURVES: usize = 8;
+
+/// Halvings of a steep step before it is judged to be a jump rather than a slope
+const BISECTIONS: usize = 40;
+
+/// Share of the plot height a step must cover before it is checked for a jump
+const STEEP: f64 = 0.05;
+
+/// A step that still covers this share of itself after every halving is a jump
+const JUMP: f64 = 0.5;
+
+/// Share of the sampled values kept in view, so `tan(x)` isn't flattened by its poles
+const VISIBLE: f64 = 0.96;
+
+/// Imaginary parts smaller than this are rounding, `sqrt(-1)` still gets left out
+const MAX_IMAGINARY: f64 = 1e-12;
+
+/// One expression, as unbroken runs of `(x, y)` points
+#[derive(Debug)]
+pub(crate) struct Curve {
+    pub(crate) label: String,
+    pub(crate) segments: Vec<Vec<(f64, f64)>>,
+}
+
+/// Curves ready to draw, with the ranges they should be drawn over
+#[derive(Debug)]
+pub(crate) struct Plot {
+    pub(crate) x: (f64, f64),
+    pub(crate) y: (f64, f64),
+    pub(crate) curves: Vec<Curve>,
+}
+
+/// Evaluate a bound of the range, such as `-2*pi`, found `at` a position in the input
+fn bound(src: &str, at: usize, options: &Options) -> Result<f64, Error> {
+    evaluate_bound(src, options).map_err(|why| why.shifted(at))
+}
+
+fn evaluate_bound(src: &str, options: &Options) -> Result<f64, Error> {
+    let tokens = lexer::Lexer::new(src).tokenize()?;
+    let mut parser = parser::Parser::new(tokens);
+    parser.config();
+    parser.limit_depth(options.max_depth);
+    let asts = parser.expressions()?;
+    let ast = match asts.as_slice() {
+        [ast] => ast,
+        asts => {
+            return Err(Error::new(
+                format!("expected one bound, found {}", asts.len()),
+                ErrorType::SyntaxError,
+                Pos::new(0, src.len()),
+            ))
+        }
+    };
+
+    let value = Executer::with_options(options.clone()).eval(ast)?;
+    match to_f64(&value.value) {
+        Some(val) if val.is_finite() => Ok(val),
+        _ => Err(Error::new(
+            format!("expected a real number as a bound, found {}", value.value),
+            ErrorType::TypeError,
+            ast.pos,
+        )),
+    }
+}
+
+/// A real value, or `None` for types that can't be plotted
+fn to_f64(value: &ExecutionExpr) -> Option<f64> {
+    match value {
+        Integer(val) => Some(big_to_f64(val)),
+        Rational(val) => Some(val.to_f64().unwrap_or(f64::NAN)),
+        Float(val) => Some(*val),
+        // Left out where the value isn't real, as with `sqrt(x)` for negative `x`
+        Complex(val) if val.im.abs() <= MAX_IMAGINARY * (1.0 + val.re.abs()) => Some(val.re),
+        Complex(_) => Some(f64::NAN),
+        _ => None,
+    }
+}
+
+/// Sample `,` separated expressions of `x` between two bounds
+pub(crate) fn plot(exprs: &str, from: &str, to: &str) -> Result<Plot, Error> {
+    plot_with(exprs, from, to, Options::default())
+}
+
+/// Plot within the limits of `options`, always mixing types leniently
+pub(crate) fn plot_with(exprs: &str, from: &str, to: &str, options: Options) -> Result<Plot, Error> {
+    plot_parts(exprs, (from, 0), (to, 0), options)
+}
+
+/// Plot `sin(x), x**2 from -5 to 5`, from -10 to 10 when the range is left out, with errors
+/// pointing into `src`
+pub(crate) fn plot_src(src: &str, options: Options) -> Result<Plot, Error> {
+    if let Some(at) = src.rfind(" from ") {
+        let start = at + " from ".len();
+        if let Some(to) = src[start..].find(" to ") {
+            let end = start + to + " to ".len();
+            return plot_parts(&src[..at], (&src[start..start + to], start), (&src[end..], end), options);
+        }
+    }
+
+    plot_parts(src, ("-10", 0), ("10", 0), options)
+}
+
+/// Plot between bounds found at positions in the input
+fn plot_parts(exprs: &str, from: (&str, usize), to: (&str, usize), options: Options) -> Result<Plot, Error> {
+    let options = Options {
+        mode: Mode::Lenient,
+        ..options
+    };
+    let (from, to, range) = (
+        bound(from.0, from.1, &options)?,
+        bound(to.0, to.1, &options)?,
+        Pos::new(from.1, to.1 + to.0.len()),
+    );
+    if from >= to {
+        return Err(Error::new(
+            format!("the range {} to {} is empty", from, to),
+            ErrorType::RuntimeError,
+            range,
+        ));
+    }
+
+    let tokens = lexer::Lexer::new(exprs).tokenize()?;
+    let mut parser = parser::Parser::new(tokens);
+    parser.config();
+    parser.limit_depth(options.max_depth);
+    let asts = parser.expressions()?;
+    if asts.len() > MAX_CURVES {
+        return Err(Error::new(
+            format!("cannot plot more than {} expressions at once", MAX_CURVES),
+            ErrorType::RuntimeError,
+            asts[MAX_CURVES].pos,
+        ));
+    }
+
+    let step = (to - from) / SAMPLES as f64;
+    let mut samples = Vec::with_capacity(asts.len());
+    for ast in &asts {
+        let mut sampler = Sampler::new(ast, &options);
+        let points = (0..=SAMPLES)
+            .map(|i| {
+                let x = from + i as f64 * step;
+                Ok((x, sampler.at(x)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        sampler.check(&points)?;
+        samples.push((sampler, points));
+    }
+
+    let y = view(samples.iter().flat_map(|(_, points)| points.iter().map(|&(_, y)| y)));
+    let curves = samples
+        .into_iter()
+        .zip(&asts)
+        .map(|((mut sampler, points), ast)| {
+            Ok(Curve {
+                label: exprs.get(ast.pos.start..ast.pos.end).unwrap_or_default().trim().to_string(),
+                segments: sampler.segments(&points, y.1 - y.0)?,
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(Plot { x: (from, to), y, curves })
+}
+
+/// Evaluates one expression at different values of `x`
+struct Sampler<'s, 'a> {
+    ast: &'s ast::Expression<'a>,
+    executer: Executer<'a>,
+    // First failure, reported if the expression has no value anywhere
+    error: Option<Error>,
+}
+
+impl<'s, 'a> Sampler<'s, 'a> {
+    fn new(ast: &'s ast::Expression<'a>, options: &Options) -> Self {
+        Sampler {
+            ast,
+            executer: Executer::with_options(options.clone()),
+            error: None,
+        }
+    }
+
+    /// `y` at `x`, NaN where it has no real value
+    fn at(&mut self, x: f64) -> Result<f64, Error> {
+        self.executer.bind("x", Float(x));
+        match self.executer.eval(self.ast) {
+            Ok(value) => match &value.value {
+                Symbolic(expr) => Ok(expr.value_at("x", x)),
+                val => to_f64(val).ok_or_else(|| {
+                    Error::new(
+                        format!("cannot plot type {}", val.display_type()),
+                        ErrorType::TypeError,
+                        self.ast.pos,
+                    )
+                }),
+            },
+            // Running out of steps or time stops the whole plot
+            Err(why) if why.error_type() == ErrorType::LimitError => Err(why),
+            // Such as dividing by zero, which only breaks the curve there
+            Err(why) => {
+                self.error.get_or_insert(why);
+                Ok(f64::NAN)
+            }
+        }
+    }
+
+    /// Fail on expressions without a value anywhere in the range, such as `y + 1`
+    fn check(&mut self, points: &[(f64, f64)]) -> Result<(), Error> {
+        match self.error.take() {
+            Some(why) if points.iter().all(|(_, y)| !y.is_finite()) => Err(why),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `y` jumps between `x0` and `x1`, rather than just rising steeply
+    fn jumps(&mut self, (mut x0, mut y0): (f64, f64), (mut x1, mut y1): (f64, f64)) -> Result<bool, Error> {
+        let step = (y1 - y0).abs();
+        for _ in 0..BISECTIONS {
+            let mid = (x0 + x1) / 2.0;
+            let y = self.at(mid)?;
+            if !y.is_finite() {
+                return Ok(true);
+            }
+
+            // Follow the half holding most of the step, or when `y` overshoots both ends, as
+            // around the pole of `1/x**2`, the half ending further out
+            let left = if (y - y0) * (y1 - y) >= 0.0 {
+                (y - y0).abs() >= (y1 - y).abs()
+            } else {
+                y0.abs() >= y1.abs()
+            };
+            if left {
+                x1 = mid;
+                y1 = y;
+            } else {
+                x0 = mid;
+                y0 = y;
+            }
+        }
+
+        Ok((y1 - y0).abs() >= JUMP * step)
+    }
+
+    /// Split the samples wherever the curve has no value or jumps
+    fn segments(&mut self, points: &[(f64, f64)], height: f64) -> Result<Vec<Vec<(f64, f64)>>, Error> {
+        let mut segments = Vec::new();
+        let mut current: Vec<(f64, f64)> = Vec::new();
+        for &(x, y) in points {
+            let broken = !y.is_finite()
+                || match current.last() {
+                    Some(&last) => (y - last.1).abs() > STEEP * height && self.jumps(last, (x, y))?,
+                    None => false,
+                };
+
+            if broken && !current.is_empty() {
+                segments.push(std::mem::take(&mut current));
+            }
+            if y.is_finite() {
+                current.push((x, y));
+            }
+        }
+        segments.push(current);
+
+        // A lone point can't be drawn as a line
+        segments.retain(|segment| segment.len() > 1);
+        Ok(segments)
+    }
+}
+
+/// `y` range to draw, leaving out the extremes of values that shoot off towards a pole
+fn view<I: Iterator<Item = f64>>(values: I) -> (f64, f64) {
+    let mut values: Vec<f64> = values.filter(|y| y.is_finite()).collect();
+    if values.is_empty() {
+        return (-1.0, 1.0);
+    }
+    // Every value left is finite, so `partial_cmp` always gives an order
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let (min, max) = (values[0], values[values.len() - 1]);
+    let cut = ((1.0 - VISIBLE) / 2.0 * values.len() as f64) as usize;
+    let (lo, hi) = (values[cut], values[values.len() - 1 - cut]);
+
+    // Only crop when the extremes would squash everything else
+    let (lo, hi) = if max - min > 4.0 * (hi - lo) {
+        let margin = (hi - lo) / 2.0;
+        ((lo - margin).max(min), (hi + margin).min(max))
+    } else {
+        (min, max)
+    };
+
+    if hi - lo <= f64::EPSILON * (1.0 + lo.abs()) {
+        return (lo - 1.0, hi + 1.0);
+    }
+    let margin = (hi - lo) * 0.05;
+    (lo - margin, hi + margin)
+}
@@ -0,0 +1,297 @@
+use num::bigint::BigInt;
+use num::rational::BigRational;
+use num::traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::core::eval::builtins::{empty_err, integer, list, number, sorted};
+use crate::core::eval::error::*;
+use crate::core::eval::exec::{
+    max_bits, rational, too_big_err, ExecutionExpr, ExecutionExpr::*, EE,
+};
+use crate::core::eval::options::Options;
+
+/// The elements of a list argument, refusing an empty list
+fn sample(name: &str, pos: Pos, arg: &EE) -> Result<Vec<EE>, Error> {
+    let items = list(arg)?;
+    if items.is_empty() {
+        return Err(empty_err(name, pos));
+    }
+    Ok(items)
+}
+
+/// Values as floats, refusing to mix integers and floats as `mean` does
+fn floats(items: &[EE]) -> Result<Vec<f64>, Error> {
+    total(items)?;
+    items.iter().map(number).collect()
+}
+
+/// Two lists of the same length with at least two values each, of one type between them
+fn pairs(name: &str, pos: Pos, args: &[EE]) -> Result<(Vec<f64>, Vec<f64>), Error> {
+    let (xs, ys) = (sample(name, pos, &args[0])?, sample(name, pos, &args[1])?);
+    floats(&[xs.as_slice(), ys.as_slice()].concat())?;
+    let (xs, ys) = (floats(&xs)?, floats(&ys)?);
+    if xs.len() != ys.len() {
+        return Err(Error::new(
+            format!(
+                "cannot take the {} of lists of lengths {} and {}",
+                name,
+                xs.len(),
+                ys.len()
+            ),
+            ErrorType::RuntimeError,
+            pos,
+        ));
+    }
+    if xs.len() < 2 {
+        return Err(few_err(name, pos));
+    }
+    Ok((xs, ys))
+}
+
+fn few_err(name: &str, pos: Pos) -> Error {
+    Error::new(
+        format!("cannot take the {} of fewer than two values", name),
+        ErrorType::RuntimeError,
+        pos,
+    )
+}
+
+fn int(val: usize, pos: Pos) -> EE {
+    EE::new(Integer(BigInt::from(val)), pos)
+}
+
+/// A count to divide `total` by, a float when it is one so the two divide
+fn divisor(total: &EE, val: usize, pos: Pos) -> EE {
+    match total.value {
+        Float(_) => EE::new(Float(val as f64), pos),
+        _ => int(val, pos),
+    }
+}
+
+/// Add up values, starting from the first so floats never meet an integer zero
+fn total(items: &[EE]) -> Result<EE, Error> {
+    let mut total = items[0].clone();
+    for item in &items[1..] {
+        total = total.add(item)?;
+    }
+    Ok(total)
+}
+
+fn average(items: &[EE], pos: Pos) -> Result<EE, Error> {
+    let total = total(items)?;
+    total.div(&divisor(&total, items.len(), pos))
+}
+
+fn mean_of(xs: &[f64]) -> f64 {
+    xs.iter().sum::<f64>() / xs.len() as f64
+}
+
+/// Sums of the squared deviations of `xs` and `ys`, and of their cross products
+fn deviations(xs: &[f64], ys: &[f64]) -> (f64, f64, f64) {
+    let (mx, my) = (mean_of(xs), mean_of(ys));
+    xs.iter()
+        .zip(ys)
+        .fold((0.0, 0.0, 0.0), |(sxx, syy, sxy), (x, y)| {
+            let (dx, dy) = (x - mx, y - my);
+            (sxx + dx * dx, syy + dy * dy, sxy + dx * dy)
+        })
+}
+
+pub(crate) fn mean(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    Ok(average(&sample("mean", pos, &args[0])?, pos)?.value)
+}
+
+/// Read a number exactly, if it is an integer or rational
+fn exact(value: &ExecutionExpr) -> Option<BigRational> {
+    match value {
+        Integer(val) => Some(BigRational::from_integer(val.clone())),
+        Rational(val) => Some(val.clone()),
+        _ => None,
+    }
+}
+
+/// The value `p` percent of the way through the sorted values, interpolating linearly between
+/// the closest ranks, exactly when `p` and the values are all integers or rationals
+fn quantile(name: &str, pos: Pos, arg: &EE, p: &EE) -> Result<ExecutionExpr, Error> {
+    let items = sorted(sample(name, pos, arg)?)?;
+    let last = items.len() - 1;
+
+    let xs: Option<Vec<BigRational>> = items.iter().map(|item| exact(&item.value)).collect();
+    if let (Some(xs), Some(p)) = (xs, exact(&p.value)) {
+        let rank = p * BigInt::from(last) / BigInt::from(100);
+        let (low, high) = (rank.floor(), rank.ceil());
+        let (low_at, high_at) = (
+            low.to_integer().to_usize().unwrap_or(0),
+            high.to_integer().to_usize().unwrap_or(last),
+        );
+        let (x_low, x_high) = (&xs[low_at], &xs[high_at]);
+        return Ok(rational(x_low + (x_high - x_low) * (&rank - &low)));
+    }
+
+    let xs = items.iter().map(number).collect::<Result<Vec<_>, _>>()?;
+    // NaN sorts last, which would otherwise shift every rank
+    if xs.iter().any(|x| x.is_nan()) {
+        return Ok(Float(f64::NAN));
+    }
+    let rank = number(p)? / 100.0 * last as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    Ok(Float(xs[low] + (xs[high] - xs[low]) * (rank - low as f64)))
+}
+
+/// The middle value, or the mean of the middle two, the same as `percentile(xs, 50)`
+pub(crate) fn median(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    quantile("median", pos, &args[0], &int(50, pos))
+}
+
+/// The most common value, the smallest one on a tie
+pub(crate) fn mode(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let items = sorted(sample("mode", pos, &args[0])?)?;
+
+    let (mut best, mut best_count) = (&items[0], 0);
+    let mut start = 0;
+    for end in 1..=items.len() {
+        if end < items.len() && items[end].value == items[start].value {
+            continue;
+        }
+        if end - start > best_count {
+            best = &items[start];
+            best_count = end - start;
+        }
+        start = end;
+    }
+    Ok(best.value.clone())
+}
+
+/// Sample variance, dividing by one less than the number of values
+pub(crate) fn variance(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let items = sample("variance", pos, &args[0])?;
+    if items.len() < 2 {
+        return Err(few_err("variance", pos));
+    }
+
+    let mean = average(&items, pos)?;
+    let mut squares = Vec::with_capacity(items.len());
+    for item in &items {
+        let diff = item.sub(&mean)?;
+        squares.push(diff.mul(&diff)?);
+    }
+    let total = total(&squares)?;
+    Ok(total.div(&divisor(&total, items.len() - 1, pos))?.value)
+}
+
+pub(crate) fn stddev(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let xs = floats(&sample("stddev", pos, &args[0])?)?;
+    if xs.len() < 2 {
+        return Err(few_err("stddev", pos));
+    }
+
+    let (sxx, _, _) = deviations(&xs, &xs);
+    Ok(Float((sxx / (xs.len() - 1) as f64).sqrt()))
+}
+
+/// `percentile(xs, p)` interpolates linearly between the closest ranks
+pub(crate) fn percentile(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let p = number(&args[1])?;
+    if !(0.0..=100.0).contains(&p) {
+        return Err(Error::new(
+            format!("percentile must be between 0 and 100, found {}", p),
+            ErrorType::RuntimeError,
+            args[1].pos,
+        ));
+    }
+
+    quantile("percentile", pos, &args[0], &args[1])
+}
+
+/// Pearson correlation coefficient of two lists
+pub(crate) fn corr(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let (xs, ys) = pairs("correlation", pos, args)?;
+    let (sxx, syy, sxy) = deviations(&xs, &ys);
+    if sxx == 0.0 || syy == 0.0 {
+        return Err(Error::new(
+            "correlation is undefined for a list of equal values".to_string(),
+            ErrorType::RuntimeError,
+            pos,
+        ));
+    }
+    Ok(Float(sxy / (sxx * syy).sqrt()))
+}
+
+/// Least squares line through the points, as `[slope, intercept]`
+pub(crate) fn linreg(pos: Pos, args: &[EE]) -> Result<ExecutionExpr, Error> {
+    let (xs, ys) = pairs("regression", pos, args)?;
+    let (sxx, _, sxy) = deviations(&xs, &ys);
+    if sxx == 0.0 {
+        return Err(Error::new(
+            "regression is undefined when every x value is equal".to_string(),
+            ErrorType::RuntimeError,
+            pos,
+        ));
+    }
+
+    let slope = sxy / sxx;
+    Ok(List(vec![
+        Float(slope),
+        Float(mean_of(&ys) - slope * mean_of(&xs)),
+    ]))
+}
+
+/// Read a non-negative integer argument
+fn count(arg: &EE, max_digits: usize) -> Result<u64, Error> {
+    let val = integer(arg)?;
+    if val.is_negative() {
+        return Err(Error::new(
+            format!("expected a non-negative integer, found {}", val),
+            ErrorType::RuntimeError,
+            arg.pos,
+        ));
+    }
+    val.to_u64()
+        .ok_or_else(|| too_big_err(max_digits, arg.pos))
+}
+
+/// Multiply the integers in `from..=to`, giving up past the digit limit
+fn falling(from: u64, to: u64, pos: Pos, max_digits: usize) -> Result<BigInt, Error> {
+    let mut total = BigInt::one();
+    for factor in from..=to {
+        total *= factor;
+        if total.bits() > max_bits(max_digits) {
+            return Err(too_big_err(max_digits, pos));
+        }
+    }
+    Ok(total)
+}
+
+pub(crate) fn factorial(pos: Pos, args: &[EE], options: &Options) -> Result<ExecutionExpr, Error> {
+    let max_digits = options.max_digits;
+    Ok(Integer(falling(1, count(&args[0], max_digits)?, pos, max_digits)?))
+}
+
+/// Ways to pick `r` of `n` items when order matters
+pub(crate) fn npr(pos: Pos, args: &[EE], options: &Options) -> Result<ExecutionExpr, Error> {
+    let max_digits = options.max_digits;
+    let (n, r) = (count(&args[0], max_digits)?, count(&args[1], max_digits)?);
+    if r > n {
+        return Ok(Integer(BigInt::zero()));
+    }
+    Ok(Integer(falling(n - r + 1, n, pos, max_digits)?))
+}
+
+/// Ways to pick `r` of `n` items when order doesn't matter
+pub(crate) fn ncr(pos: Pos, args: &[EE], options: &Options) -> Result<ExecutionExpr, Error> {
+    let max_digits = options.max_digits;
+    let (n, r) = (count(&args[0], max_digits)?, count(&args[1], max_digits)?);
+    if r > n {
+        return Ok(Integer(BigInt::zero()));
+    }
+
+    let r = r.min(n - r);
+    let mut total = BigInt::one();
+    for i in 0..r {
+        // Each partial product is itself a binomial coefficient, so this divides exactly
+        total = total * (n - i) / (i + 1);
+        if total.bits() > max_bits(max_digits) {
+            return Err(too_big_err(max_digits, pos));
+        }
+    }
+    Ok(Integer(total))
+}
@@ -0,0 +1,892 @@
+use std::fmt;
+use std::time::Instant;
+
+use num::bigint::BigInt;
+use num::rational::BigRational;
+use num::traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::core::eval::ast::{self, ExpressionKind};
+use crate::core::eval::error::*;
+use crate::core::eval::exec::{max_bits, out_of_time_err, too_many_steps_err, DEFAULT_MAX_DIGITS};
+use crate::core::eval::lexer;
+use crate::core::eval::options::Options;
+
+/// An expression over free variables, as built by `diff` and `simplify`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Number(BigRational),
+    Float(f64),
+    Symbol(String),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// Largest factor tried when moving squares out of a root
+const MAX_SQUARE_FACTOR: u64 = 1000;
+
+/// Looks up the value of a bound variable, `None` leaves it as a symbol
+pub(crate) type Resolve<'r> = dyn Fn(&str) -> Option<Expr> + 'r;
+
+/// Limits on building expressions, which can grow fast, as repeated `diff` of `x**x` does
+pub(crate) struct Budget {
+    pub(crate) steps: usize, // Shared with evaluating, each part built counts as a step
+    max_steps: usize,
+    max_size: usize, // Parts in a single expression, each takes at least a character to show
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub(crate) fn new(options: &Options, steps: usize) -> Self {
+        Budget {
+            steps,
+            max_steps: options.max_steps,
+            max_size: options.max_output,
+            deadline: options.deadline,
+        }
+    }
+
+    /// Count the parts of a newly built expression, failing once it or the work so far is too
+    /// large, or time runs out
+    fn spend(&mut self, expr: Expr, pos: Pos) -> Result<Expr, Error> {
+        let size = expr.size();
+        if size > self.max_size {
+            self.steps += size;
+            return Err(Error::new(
+                format!("expression is larger than the limit of {} parts", self.max_size),
+                ErrorType::LimitError,
+                pos,
+            ));
+        }
+
+        self.charge(size, pos)?;
+        Ok(expr)
+    }
+
+    /// Count `steps` of work, such as evaluating an expression of that many parts, failing once
+    /// the work so far is too much or time runs out
+    pub(crate) fn charge(&mut self, steps: usize, pos: Pos) -> Result<(), Error> {
+        self.steps += steps;
+        if self.steps > self.max_steps {
+            return Err(too_many_steps_err(self.max_steps, pos));
+        }
+        if matches!(self.deadline, Some(end) if Instant::now() >= end) {
+            return Err(out_of_time_err(pos));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn num(val: i64) -> Expr {
+    Expr::Number(BigRational::from_integer(BigInt::from(val)))
+}
+
+impl Expr {
+    pub(crate) fn is_number(&self) -> bool {
+        matches!(self, Expr::Number(_) | Expr::Float(_))
+    }
+
+    pub(crate) fn is(&self, val: i64) -> bool {
+        match self {
+            Expr::Number(num) => *num == BigRational::from_integer(BigInt::from(val)),
+            Expr::Float(num) => *num == val as f64,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn is_negative(&self) -> bool {
+        match self {
+            Expr::Number(val) => val.is_negative(),
+            Expr::Float(val) => *val < 0.0,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn to_f64(&self) -> f64 {
+        match self {
+            Expr::Number(val) => val.to_f64().unwrap_or(f64::NAN),
+            Expr::Float(val) => *val,
+            _ => f64::NAN,
+        }
+    }
+
+    /// Number of parts the expression is made of
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            Expr::Number(_) | Expr::Float(_) | Expr::Symbol(_) => 1,
+            Expr::Neg(val) => 1 + val.size(),
+            Expr::Add(left, right)
+            | Expr::Sub(left, right)
+            | Expr::Mul(left, right)
+            | Expr::Div(left, right)
+            | Expr::Pow(left, right) => 1 + left.size() + right.size(),
+            Expr::Call(_, args) => 1 + args.iter().map(Expr::size).sum::<usize>(),
+        }
+    }
+
+    pub(crate) fn depends_on(&self, var: &str) -> bool {
+        match self {
+            Expr::Number(_) | Expr::Float(_) => false,
+            Expr::Symbol(name) => name == var,
+            Expr::Neg(val) => val.depends_on(var),
+            Expr::Add(left, right)
+            | Expr::Sub(left, right)
+            | Expr::Mul(left, right)
+            | Expr::Div(left, right)
+            | Expr::Pow(left, right) => left.depends_on(var) || right.depends_on(var),
+            Expr::Call(_, args) => args.iter().any(|arg| arg.depends_on(var)),
+        }
+    }
+
+    /// Evaluate with `var` set to `x`, NaN if any other variable is left
+    pub(crate) fn value_at(&self, var: &str, x: f64) -> f64 {
+        match self {
+            Expr::Number(_) | Expr::Float(_) => self.to_f64(),
+            Expr::Symbol(name) if name == var => x,
+            Expr::Symbol(_) => f64::NAN,
+            Expr::Neg(val) => -val.value_at(var, x),
+            Expr::Add(left, right) => left.value_at(var, x) + right.value_at(var, x),
+            Expr::Sub(left, right) => left.value_at(var, x) - right.value_at(var, x),
+            Expr::Mul(left, right) => left.value_at(var, x) * right.value_at(var, x),
+            Expr::Div(left, right) => left.value_at(var, x) / right.value_at(var, x),
+            Expr::Pow(left, right) => left.value_at(var, x).powf(right.value_at(var, x)),
+            Expr::Call(name, args) => match (name.as_str(), args.as_slice()) {
+                ("log", [val]) => val.value_at(var, x).log10(),
+                ("log", [val, base]) => val.value_at(var, x).log(base.value_at(var, x)),
+                (_, [val]) => float_call(name, val.value_at(var, x)).unwrap_or(f64::NAN),
+                _ => f64::NAN,
+            },
+        }
+    }
+
+    /// Binding power when printed, following the parser's precedence table
+    fn precedence(&self) -> u16 {
+        match self {
+            Expr::Number(val) if !val.is_integer() => 60,
+            Expr::Number(val) if val.is_negative() => 70,
+            Expr::Float(val) if *val < 0.0 => 70,
+            Expr::Add(..) | Expr::Sub(..) => 50,
+            Expr::Mul(..) | Expr::Div(..) => 60,
+            Expr::Pow(..) => 65,
+            Expr::Neg(_) => 70,
+            _ => 100,
+        }
+    }
+
+    /// Prefix `-` binds tighter than `**`, so `-x**2` would read as `(-x)**2`
+    fn leads_with_pow(&self) -> bool {
+        match self {
+            Expr::Pow(..) => true,
+            Expr::Mul(left, _) | Expr::Div(left, _) => left.leads_with_pow(),
+            _ => false,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min: u16) -> fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    fn fmt_binary(f: &mut fmt::Formatter<'_>, left: &Expr, op: &str, right: &Expr, prec: u16) -> fmt::Result {
+        // Every operator is left associative, `**` included
+        left.fmt_operand(f, prec)?;
+        write!(f, "{}", op)?;
+        right.fmt_operand(f, prec + 1)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(val) => write!(f, "{}", val),
+            Expr::Float(val) => write!(f, "{}", val),
+            Expr::Symbol(name) => write!(f, "{}", name),
+            Expr::Neg(val) if val.precedence() >= 60 && !val.leads_with_pow() => write!(f, "-{}", val),
+            Expr::Neg(val) => write!(f, "-({})", val),
+            Expr::Add(left, right) => Expr::fmt_binary(f, left, " + ", right, 50),
+            Expr::Sub(left, right) => Expr::fmt_binary(f, left, " - ", right, 50),
+            Expr::Mul(left, right) => Expr::fmt_binary(f, left, "*", right, 60),
+            Expr::Div(left, right) => Expr::fmt_binary(f, left, "/", right, 60),
+            Expr::Pow(left, right) => Expr::fmt_binary(f, left, "**", right, 65),
+            Expr::Call(name, args) => write!(
+                f,
+                "{}({})",
+                name,
+                args.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+/// Combine two numbers, staying exact unless either is a float
+fn fold<R, F>(left: &Expr, right: &Expr, exact: R, float: F) -> Expr
+where
+    R: Fn(&BigRational, &BigRational) -> BigRational,
+    F: Fn(f64, f64) -> f64,
+{
+    match (left, right) {
+        (Expr::Number(left), Expr::Number(right)) => Expr::Number(exact(left, right)),
+        _ => Expr::Float(float(left.to_f64(), right.to_f64())),
+    }
+}
+
+/// Raise a number to a numeric power, if the result is exact and small
+fn fold_pow(base: &Expr, exp: &Expr) -> Option<Expr> {
+    match (base, exp) {
+        (Expr::Number(base), Expr::Number(exp)) => {
+            if !exp.is_integer() {
+                return None;
+            }
+
+            let exp = exp.to_integer().to_i32()?;
+            let bits = base.numer().bits().max(base.denom().bits()).max(1);
+            if (base.is_zero() && exp < 0) || bits * u64::from(exp.unsigned_abs()) > max_bits(DEFAULT_MAX_DIGITS) {
+                return None;
+            }
+
+            Some(Expr::Number(base.pow(exp)))
+        }
+        _ if base.is_number() && exp.is_number() => {
+            let val = base.to_f64().powf(exp.to_f64());
+            if val.is_finite() {
+                Some(Expr::Float(val))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The positive form of a negated term, so `a + -2*x` is written `a - 2*x`
+fn unsigned(expr: &Expr) -> Option<Expr> {
+    match expr {
+        Expr::Number(_) | Expr::Float(_) if expr.is_negative() => {
+            Some(fold(expr, &num(-1), |left, right| left * right, |left, right| left * right))
+        }
+        Expr::Neg(val) => Some((**val).clone()),
+        Expr::Mul(left, right) => unsigned(left).map(|left| Expr::Mul(Box::new(left), right.clone())),
+        Expr::Div(left, right) => unsigned(left).map(|left| Expr::Div(Box::new(left), right.clone())),
+        _ => None,
+    }
+}
+
+fn flatten_sum(expr: Expr, terms: &mut Vec<Expr>) {
+    match expr {
+        Expr::Add(left, right) => {
+            flatten_sum(*left, terms);
+            flatten_sum(*right, terms);
+        }
+        Expr::Sub(left, right) => {
+            flatten_sum(*left, terms);
+            flatten_sum(neg(*right), terms);
+        }
+        expr => terms.push(expr),
+    }
+}
+
+fn flatten_product(expr: Expr, factors: &mut Vec<Expr>) {
+    match expr {
+        Expr::Mul(left, right) => {
+            flatten_product(*left, factors);
+            flatten_product(*right, factors);
+        }
+        Expr::Div(left, right) => {
+            flatten_product(*left, factors);
+            let mut divisors = Vec::new();
+            flatten_product(*right, &mut divisors);
+            factors.extend(divisors.into_iter().map(reciprocal));
+        }
+        Expr::Neg(val) => {
+            factors.push(num(-1));
+            flatten_product(*val, factors);
+        }
+        expr => factors.push(expr),
+    }
+}
+
+/// `1/x` of a single factor, without simplifying it
+fn reciprocal(factor: Expr) -> Expr {
+    match factor {
+        Expr::Number(val) if !val.is_zero() => Expr::Number(val.recip()),
+        Expr::Float(val) if val != 0.0 => Expr::Float(1.0 / val),
+        Expr::Pow(base, exp) => Expr::Pow(base, Box::new(neg(*exp))),
+        factor => Expr::Pow(Box::new(factor), Box::new(num(-1))),
+    }
+}
+
+/// Split `3*x*y` into `3` and `x*y`
+fn split_coefficient(term: Expr) -> (Expr, Expr) {
+    let mut factors = Vec::new();
+    flatten_product(term, &mut factors);
+
+    let (numbers, rest): (Vec<_>, Vec<_>) = factors.into_iter().partition(Expr::is_number);
+    let coefficient = numbers.iter().fold(num(1), |acc, val| {
+        fold(&acc, val, |left, right| left * right, |left, right| left * right)
+    });
+    (coefficient, product(rest))
+}
+
+/// Add up terms, folding constants and collecting like terms such as `x + 2*x`
+fn sum(terms: Vec<Expr>) -> Expr {
+    let mut flat = Vec::new();
+    for term in terms {
+        flatten_sum(term, &mut flat);
+    }
+
+    let mut constant = num(0);
+    let mut groups: Vec<(Expr, Expr)> = Vec::new(); // Term and its coefficient
+    for term in flat {
+        if term.is_number() {
+            constant = fold(&constant, &term, |left, right| left + right, |left, right| left + right);
+            continue;
+        }
+
+        let (coefficient, term) = split_coefficient(term);
+        match groups.iter_mut().find(|(other, _)| *other == term) {
+            Some((_, total)) => {
+                *total = fold(total, &coefficient, |left, right| left + right, |left, right| left + right)
+            }
+            None => groups.push((term, coefficient)),
+        }
+    }
+
+    let mut result = None;
+    for (term, coefficient) in groups {
+        if !coefficient.is(0) {
+            result = Some(append(result, product(vec![coefficient, term])));
+        }
+    }
+    if !constant.is(0) {
+        result = Some(append(result, constant));
+    }
+
+    result.unwrap_or_else(|| num(0))
+}
+
+fn append(sum: Option<Expr>, term: Expr) -> Expr {
+    match sum {
+        None => term,
+        Some(sum) => match unsigned(&term) {
+            Some(term) => Expr::Sub(Box::new(sum), Box::new(term)),
+            None => Expr::Add(Box::new(sum), Box::new(term)),
+        },
+    }
+}
+
+/// Numbers first, then variables, then function calls, then anything else
+fn factor_order(factor: &Expr) -> (u8, String) {
+    match factor {
+        Expr::Number(_) | Expr::Float(_) => (0, String::new()),
+        Expr::Symbol(name) => (1, name.clone()),
+        Expr::Pow(base, _) => factor_order(base),
+        Expr::Call(..) => (2, factor.to_string()),
+        _ => (3, factor.to_string()),
+    }
+}
+
+fn chain(mut factors: Vec<Expr>) -> Expr {
+    factors.sort_by_key(factor_order);
+    let mut factors = factors.into_iter();
+    let first = factors.next().unwrap_or_else(|| num(1));
+    factors.fold(first, |acc, factor| Expr::Mul(Box::new(acc), Box::new(factor)))
+}
+
+/// Multiply factors, folding numbers and adding up the powers of equal bases
+fn product(factors: Vec<Expr>) -> Expr {
+    let mut flat = Vec::new();
+    for factor in factors {
+        flatten_product(factor, &mut flat);
+    }
+
+    let mut coefficient = num(1);
+    let mut powers: Vec<(Expr, Expr)> = Vec::new();
+    for factor in flat {
+        if factor.is_number() {
+            coefficient = fold(&coefficient, &factor, |left, right| left * right, |left, right| left * right);
+            continue;
+        }
+
+        let (base, exp) = match factor {
+            Expr::Pow(base, exp) => (*base, *exp),
+            factor => (factor, num(1)),
+        };
+        match powers.iter_mut().find(|(other, _)| *other == base) {
+            Some((_, total)) => *total = add(total.clone(), exp),
+            None => powers.push((base, exp)),
+        }
+    }
+
+    let mut top = Vec::new();
+    let mut bottom = Vec::new();
+    for (base, exp) in powers {
+        if let Some(exp) = unsigned(&exp) {
+            bottom.push(pow(base, exp));
+            continue;
+        }
+
+        match pow(base, exp) {
+            factor if factor.is_number() => {
+                coefficient = fold(&coefficient, &factor, |left, right| left * right, |left, right| left * right)
+            }
+            factor => top.push(factor),
+        }
+    }
+
+    // `0/0` is kept for `defined` to refuse, as evaluating it would
+    if coefficient.is(0) && !bottom.iter().any(is_zero) {
+        return num(0);
+    }
+    if top.is_empty() && bottom.is_empty() {
+        return coefficient;
+    }
+
+    // The coefficient leads, its denominator joins the divisors: `3*x/2`
+    let lead = match &coefficient {
+        Expr::Number(val) => {
+            if !val.denom().is_one() {
+                bottom.push(Expr::Number(BigRational::from_integer(val.denom().clone())));
+            }
+            Expr::Number(BigRational::from_integer(val.numer().clone()))
+        }
+        _ => coefficient,
+    };
+
+    let negate = lead.is(-1);
+    let mut result = chain(top);
+    if !lead.is(1) && !negate {
+        result = match result {
+            one if one.is(1) => lead,
+            result => product_with_lead(lead, result),
+        };
+    }
+    if !bottom.is_empty() {
+        result = Expr::Div(Box::new(result), Box::new(chain(bottom)));
+    }
+
+    if negate {
+        Expr::Neg(Box::new(result))
+    } else {
+        result
+    }
+}
+
+/// An exact zero, or a product with one in it
+fn is_zero(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(val) => val.is_zero(),
+        Expr::Mul(left, right) => is_zero(left) || is_zero(right),
+        _ => false,
+    }
+}
+
+/// The dividend of the first division by an exact zero, as `1` in `x + 1/0` or `0**-1`
+fn zero_division(expr: &Expr) -> Option<Expr> {
+    match expr {
+        Expr::Div(left, right) if is_zero(right) => Some((**left).clone()),
+        Expr::Pow(base, exp) if is_zero(base) && exp.is_negative() => Some(num(1)),
+        Expr::Number(_) | Expr::Float(_) | Expr::Symbol(_) => None,
+        Expr::Neg(val) => zero_division(val),
+        Expr::Add(left, right)
+        | Expr::Sub(left, right)
+        | Expr::Mul(left, right)
+        | Expr::Div(left, right)
+        | Expr::Pow(left, right) => zero_division(left).or_else(|| zero_division(right)),
+        Expr::Call(_, args) => args.iter().find_map(zero_division),
+    }
+}
+
+/// Refuse an expression that divides by zero, which simplifying would otherwise hide, as
+/// `0*(1/0)` is `0`
+fn defined(expr: Expr, pos: Pos) -> Result<Expr, Error> {
+    match zero_division(&expr) {
+        Some(dividend) => Err(Error::new(
+            format!("failed to divide `{}` by zero", dividend),
+            ErrorType::RuntimeError,
+            pos,
+        )),
+        None => Ok(expr),
+    }
+}
+
+/// Put a number in front of an already ordered product
+fn product_with_lead(lead: Expr, product: Expr) -> Expr {
+    match product {
+        Expr::Mul(left, right) => Expr::Mul(Box::new(product_with_lead(lead, *left)), right),
+        factor => Expr::Mul(Box::new(lead), Box::new(factor)),
+    }
+}
+
+pub(crate) fn add(left: Expr, right: Expr) -> Expr {
+    sum(vec![left, right])
+}
+
+pub(crate) fn sub(left: Expr, right: Expr) -> Expr {
+    sum(vec![left, neg(right)])
+}
+
+pub(crate) fn mul(left: Expr, right: Expr) -> Expr {
+    product(vec![left, right])
+}
+
+pub(crate) fn div(left: Expr, right: Expr) -> Expr {
+    product(vec![left, pow(right, num(-1))])
+}
+
+pub(crate) fn neg(val: Expr) -> Expr {
+    match val {
+        Expr::Add(..) | Expr::Sub(..) => {
+            let mut terms = Vec::new();
+            flatten_sum(val, &mut terms);
+            sum(terms.into_iter().map(neg).collect())
+        }
+        val => product(vec![num(-1), val]),
+    }
+}
+
+pub(crate) fn pow(base: Expr, exp: Expr) -> Expr {
+    if let Some(val) = fold_pow(&base, &exp) {
+        return val;
+    }
+    if exp.is(0) || base.is(1) {
+        return num(1);
+    }
+    if exp.is(1) {
+        return base;
+    }
+    if base.is(0) && exp.is_number() && !exp.is_negative() {
+        return num(0);
+    }
+
+    // Only whole powers distribute, `(x**2)**(1/2)` is not `x` for negative `x`
+    let whole = matches!(&exp, Expr::Number(val) if val.is_integer());
+    match base {
+        Expr::Pow(base, inner) if whole => pow(*base, mul(*inner, exp)),
+        Expr::Mul(..) | Expr::Div(..) | Expr::Neg(_) if whole => {
+            let mut factors = Vec::new();
+            flatten_product(base, &mut factors);
+            product(factors.into_iter().map(|factor| pow(factor, exp.clone())).collect())
+        }
+        base => Expr::Pow(Box::new(base), Box::new(exp)),
+    }
+}
+
+/// Evaluate a function of a float, giving up on results that aren't finite
+fn float_call(name: &str, val: f64) -> Option<f64> {
+    let val = match name {
+        "sin" => val.sin(),
+        "cos" => val.cos(),
+        "tan" => val.tan(),
+        "asin" => val.asin(),
+        "acos" => val.acos(),
+        "atan" => val.atan(),
+        "exp" => val.exp(),
+        "ln" => val.ln(),
+        "sqrt" => val.sqrt(),
+        "cbrt" => val.cbrt(),
+        "abs" => val.abs(),
+        _ => return None,
+    };
+
+    Some(val).filter(|val| val.is_finite())
+}
+
+/// The square root of a rational that is a perfect square, such as `9/4`
+fn exact_sqrt(val: &BigRational) -> Option<BigRational> {
+    if val.is_negative() {
+        return None;
+    }
+
+    let (numer, denom) = (val.numer().sqrt(), val.denom().sqrt());
+    if &(&numer * &numer) == val.numer() && &(&denom * &denom) == val.denom() {
+        Some(BigRational::new(numer, denom))
+    } else {
+        None
+    }
+}
+
+/// Move square factors out of a root, so `sqrt(8)` is `2*sqrt(2)` and
+/// `sqrt(1/2)` is `sqrt(2)/2`
+fn split_sqrt(val: &BigRational) -> Option<Expr> {
+    let mut inside = (val.numer() * val.denom()).to_u64()?;
+    let mut outside = 1u64;
+    let mut k = 2u64;
+    while k <= MAX_SQUARE_FACTOR && k * k <= inside {
+        while inside % (k * k) == 0 {
+            inside /= k * k;
+            outside *= k;
+        }
+        k += 1;
+    }
+
+    if outside == 1 && val.denom().is_one() {
+        return None;
+    }
+
+    let outside = BigRational::new(BigInt::from(outside), val.denom().clone());
+    let root = Expr::Call("sqrt".to_string(), vec![Expr::Number(BigRational::from_integer(BigInt::from(inside)))]);
+    Some(mul(Expr::Number(outside), root))
+}
+
+/// Call a function, folding exact values such as `sin(0)` and `ln(e)`
+pub(crate) fn apply(name: &str, args: Vec<Expr>) -> Expr {
+    match (name, args.as_slice()) {
+        ("sqrt", [Expr::Number(val)]) => {
+            if let Some(root) = exact_sqrt(val) {
+                return Expr::Number(root);
+            }
+            if let Some(root) = split_sqrt(val) {
+                return root;
+            }
+        }
+        ("exp", [val]) | ("cos", [val]) if val.is(0) => return num(1),
+        ("sin", [val]) | ("tan", [val]) | ("asin", [val]) | ("atan", [val]) if val.is(0) => return num(0),
+        ("sqrt", [val]) | ("cbrt", [val]) | ("abs", [val]) if val.is(0) || val.is(1) => return val.clone(),
+        ("ln", [val]) if val.is(1) => return num(0),
+        ("ln", [Expr::Symbol(base)]) if base == "e" => return num(1),
+        ("ln", [Expr::Call(inner, args)]) if inner == "exp" && args.len() == 1 => return args[0].clone(),
+        ("exp", [Expr::Call(inner, args)]) if inner == "ln" && args.len() == 1 => return args[0].clone(),
+        (_, [Expr::Float(val)]) => {
+            if let Some(val) = float_call(name, *val) {
+                return Expr::Float(val);
+            }
+        }
+        _ => {}
+    }
+
+    Expr::Call(name.to_string(), args)
+}
+
+/// `d/dx` of an expression, simplified
+pub(crate) fn derivative(expr: &Expr, var: &str, pos: Pos, budget: &mut Budget) -> Result<Expr, Error> {
+    let mut d = |expr: &Expr| derivative(expr, var, pos, budget);
+
+    let result = match expr {
+        Expr::Number(_) | Expr::Float(_) => num(0),
+        Expr::Symbol(name) => num(if name == var { 1 } else { 0 }),
+        Expr::Neg(val) => neg(d(val)?),
+        Expr::Add(left, right) => add(d(left)?, d(right)?),
+        Expr::Sub(left, right) => sub(d(left)?, d(right)?),
+        Expr::Mul(left, right) => add(
+            mul(d(left)?, (**right).clone()),
+            mul((**left).clone(), d(right)?),
+        ),
+        Expr::Div(left, right) => div(
+            sub(
+                mul(d(left)?, (**right).clone()),
+                mul((**left).clone(), d(right)?),
+            ),
+            pow((**right).clone(), num(2)),
+        ),
+        Expr::Pow(base, exp) if !exp.depends_on(var) => mul(
+            mul((**exp).clone(), pow((**base).clone(), sub((**exp).clone(), num(1)))),
+            d(base)?,
+        ),
+        // d(u**v) = u**v * (v'*ln(u) + v*u'/u)
+        Expr::Pow(base, exp) => mul(
+            expr.clone(),
+            add(
+                mul(d(exp)?, apply("ln", vec![(**base).clone()])),
+                div(mul((**exp).clone(), d(base)?), (**base).clone()),
+            ),
+        ),
+        Expr::Call(name, args) => {
+            let (u, du) = match args.as_slice() {
+                [u] => (u.clone(), d(u)?),
+                [u, base] if name == "log" => {
+                    let ln = |val: &Expr| apply("ln", vec![val.clone()]);
+                    return d(&div(ln(u), ln(base)));
+                }
+                _ => return Err(differentiate_err(name, pos)),
+            };
+
+            let square = || pow(u.clone(), num(2));
+            match name.as_str() {
+                "sin" => mul(apply("cos", vec![u]), du),
+                "cos" => neg(mul(apply("sin", vec![u]), du)),
+                "tan" => div(du, pow(apply("cos", vec![u]), num(2))),
+                "asin" => div(du, apply("sqrt", vec![sub(num(1), square())])),
+                "acos" => neg(div(du, apply("sqrt", vec![sub(num(1), square())]))),
+                "atan" => div(du, add(num(1), square())),
+                "exp" => mul(apply("exp", vec![u]), du),
+                "ln" => div(du, u),
+                "log" => div(du, mul(u, apply("ln", vec![num(10)]))),
+                "sqrt" => div(du, mul(num(2), apply("sqrt", vec![u]))),
+                "cbrt" => div(du, mul(num(3), pow(apply("cbrt", vec![u]), num(2)))),
+                "abs" => div(mul(u.clone(), du), apply("abs", vec![u])),
+                _ => return Err(differentiate_err(name, pos)),
+            }
+        }
+    };
+    budget.spend(defined(result, pos)?, pos)
+}
+
+fn differentiate_err(name: &str, pos: Pos) -> Error {
+    Error::new(
+        format!("cannot differentiate function `{}`", name),
+        ErrorType::RuntimeError,
+        pos,
+    )
+}
+
+pub(crate) fn arity_err(name: &str, expected: usize, found: usize, pos: Pos) -> Error {
+    Error::new(
+        format!(
+            "function `{}` takes {} argument(s) but {} were supplied",
+            name, expected, found
+        ),
+        ErrorType::TypeError,
+        pos,
+    )
+}
+
+/// `diff` and `simplify` read their arguments as expressions, not values
+pub(crate) fn is_form(name: &str) -> bool {
+    name == "diff" || name == "simplify"
+}
+
+/// Read `d/dx (u)` as the variable `x` and the expression `u`
+pub(crate) fn leibniz<'e, 'a>(infix: &'e ast::InfixOp<'a>) -> Option<(&'a str, &'e ast::Expression<'a>)> {
+    if infix.op != ast::Operator::Div || infix.left.expr != ExpressionKind::Ident("d") {
+        return None;
+    }
+
+    match &infix.right.expr {
+        ExpressionKind::Call(call) if call.args.len() == 1 => match call.func.expr {
+            ExpressionKind::Ident(name) if name.len() > 1 && name.starts_with('d') => {
+                Some((&name[1..], &call.args[0]))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn differentiate(
+    body: &ast::Expression<'_>,
+    var: &str,
+    pos: Pos,
+    resolve: &Resolve<'_>,
+    free: &[&str],
+    budget: &mut Budget,
+) -> Result<Expr, Error> {
+    // The variable stays free even if it has a value
+    let mut free = free.to_vec();
+    free.push(var);
+    let body = from_ast(body, resolve, &free, budget)?;
+    derivative(&body, var, pos, budget)
+}
+
+/// Build a simplified expression, reading unbound and `free` names as variables
+pub(crate) fn from_ast(
+    ast: &ast::Expression<'_>,
+    resolve: &Resolve<'_>,
+    free: &[&str],
+    budget: &mut Budget,
+) -> Result<Expr, Error> {
+    let unsupported = |ast: &ast::Expression<'_>| {
+        Error::new(
+            format!("cannot use {} in a symbolic expression", ast.expr),
+            ErrorType::TypeError,
+            ast.pos,
+        )
+    };
+
+    let expr = match &ast.expr {
+        ExpressionKind::Integer(val) => match lexer::parse_integer(val) {
+            Ok(val) => Expr::Number(BigRational::from_integer(val)),
+            Err(why) => {
+                return Err(Error::new(
+                    format!("error converting `{}` to integer: {}", val, why),
+                    ErrorType::RuntimeError,
+                    ast.pos,
+                ))
+            }
+        },
+        ExpressionKind::Float(val) => match lexer::parse_float(val) {
+            Ok(val) => Expr::Float(val),
+            Err(why) => {
+                return Err(Error::new(
+                    format!("error converting `{}` to float: {}", val, why),
+                    ErrorType::RuntimeError,
+                    ast.pos,
+                ))
+            }
+        },
+        ExpressionKind::Ident(name) if free.contains(name) => Expr::Symbol(name.to_string()),
+        ExpressionKind::Ident(name) => resolve(name).unwrap_or_else(|| Expr::Symbol(name.to_string())),
+
+        ExpressionKind::PrefixOp(prefix) => match prefix.op {
+            ast::Operator::Sub => neg(from_ast(&prefix.value, resolve, free, budget)?),
+            ast::Operator::Add => apply("abs", vec![from_ast(&prefix.value, resolve, free, budget)?]),
+            _ => return Err(unsupported(ast)),
+        },
+
+        ExpressionKind::InfixOp(infix) => {
+            if let Some((var, body)) = leibniz(infix) {
+                return differentiate(body, var, ast.pos, resolve, free, budget);
+            }
+
+            let left = from_ast(&infix.left, resolve, free, budget)?;
+            let right = from_ast(&infix.right, resolve, free, budget)?;
+            match infix.op {
+                ast::Operator::Add => add(left, right),
+                ast::Operator::Sub => sub(left, right),
+                ast::Operator::Mul => mul(left, right),
+                ast::Operator::Div => div(left, right),
+                ast::Operator::Pow => pow(left, right),
+                _ => return Err(unsupported(ast)),
+            }
+        }
+
+        ExpressionKind::Call(call) => {
+            let name = match call.func.expr {
+                ExpressionKind::Ident(name) => name,
+                _ => return Err(unsupported(&call.func)),
+            };
+
+            match name {
+                "diff" => {
+                    if call.args.len() != 2 {
+                        return Err(arity_err(name, 2, call.args.len(), ast.pos));
+                    }
+
+                    let var = match call.args[1].expr {
+                        ExpressionKind::Ident(var) => var,
+                        _ => {
+                            return Err(Error::new(
+                                format!("expected a variable to differentiate by, found {}", call.args[1].expr),
+                                ErrorType::TypeError,
+                                call.args[1].pos,
+                            ))
+                        }
+                    };
+                    differentiate(&call.args[0], var, ast.pos, resolve, free, budget)?
+                }
+                "simplify" => {
+                    if call.args.len() != 1 {
+                        return Err(arity_err(name, 1, call.args.len(), ast.pos));
+                    }
+                    from_ast(&call.args[0], resolve, free, budget)?
+                }
+                _ => {
+                    let mut args = Vec::with_capacity(call.args.len());
+                    for arg in &call.args {
+                        args.push(from_ast(arg, resolve, free, budget)?);
+                    }
+                    apply(name, args)
+                }
+            }
+        }
+
+        _ => return Err(unsupported(ast)),
+    };
+    budget.spend(defined(expr, ast.pos)?, ast.pos)
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::core::eval::ast::{self, ExpressionKind, Subscript};
+use crate::core::eval::parser::{Parser, CALL_BP};
+
+/// Most steps a trace shows
+pub(crate) const MAX_STEPS: usize = 200;
+
+/// Binding power of parts that never need brackets, such as numbers and lists
+const ATOM: u16 = u16::MAX;
+
+/// A part of the expression that was reduced, by address, and its value written inline
+pub(crate) type Reduction = (usize, String);
+
+/// Address identifying a part of the expression, the same for as long as it is borrowed
+pub(crate) fn node(ast: &ast::Expression<'_>) -> usize {
+    ast as *const _ as usize
+}
+
+/// `ast` as written, then again after each reduction in turn
+pub(crate) fn rewrites(ast: &ast::Expression<'_>, reductions: &[Reduction]) -> Vec<String> {
+    let mut powers = Parser::new(Vec::new());
+    powers.config();
+    let mut writer = Writer {
+        powers,
+        reduced: HashMap::new(),
+    };
+
+    let mut steps = vec![writer.write(ast).0];
+    for (node, value) in reductions {
+        writer.reduced.insert(*node, value);
+        let step = writer.write(ast).0;
+        // Reducing `-1` to `-1` rewrites nothing
+        if steps.last() != Some(&step) {
+            steps.push(step);
+        }
+    }
+    steps
+}
+
+/// Writes expressions back out with as few brackets as keep their meaning
+struct Writer<'r> {
+    powers: Parser<'static>, // Only for its precedence tables
+    reduced: HashMap<usize, &'r str>,
+}
+
+impl Writer<'_> {
+    /// Write `ast` with its reduced parts as their values, returning how tightly the text binds
+    fn write(&self, ast: &ast::Expression<'_>) -> (String, u16) {
+        if let Some(value) = self.reduced.get(&node(ast)) {
+            return (value.to_string(), self.value_power(value));
+        }
+
+        match &ast.expr {
+            ExpressionKind::InfixOp(infix) => {
+                let power = match (infix.op, &infix.right.expr) {
+                    (ast::Operator::As, ExpressionKind::Ident(target)) => self.powers.cast_power(target),
+                    (ast::Operator::As, _) => self.powers.cast_power(""),
+                    (op, _) => self.powers.infix_power(op).unwrap_or(0),
+                };
+                // Operators group to the left, so only the right side is bracketed at the same power
+                let left = self.operand(&infix.left, power);
+                let right = self.operand(&infix.right, power + 1);
+                (format!("{} {} {}", left, infix.op.symbol(), right), power)
+            }
+            ExpressionKind::PrefixOp(prefix) => {
+                let power = self.powers.prefix_power(prefix.op).unwrap_or(0);
+                // `-(-2)` rather than `--2`
+                (format!("{}{}", prefix.op.symbol(), self.operand(&prefix.value, power + 1)), power)
+            }
+            ExpressionKind::Call(call) => (
+                format!("{}({})", self.operand(&call.func, CALL_BP), self.list(&call.args)),
+                CALL_BP,
+            ),
+            ExpressionKind::If(cond) => (
+                format!(
+                    "if {} then {} else {}",
+                    self.write(&cond.cond).0,
+                    self.write(&cond.then).0,
+                    self.write(&cond.otherwise).0
+                ),
+                0,
+            ),
+            ExpressionKind::Lambda(lambda) => {
+                (format!("|{}| {}", lambda.params.join(", "), self.write(&lambda.body).0), 0)
+            }
+            ExpressionKind::List(items) => (format!("[{}]", self.list(items)), ATOM),
+            ExpressionKind::Index(index) => {
+                let value = self.operand(&index.value, CALL_BP);
+                let subscript = match &index.subscript {
+                    Subscript::Item(item) => self.write(item).0,
+                    Subscript::Slice(start, end) => format!(
+                        "{}:{}",
+                        start.as_ref().map(|start| self.write(start).0).unwrap_or_default(),
+                        end.as_ref().map(|end| self.write(end).0).unwrap_or_default()
+                    ),
+                };
+                (format!("{}[{}]", value, subscript), CALL_BP)
+            }
+            // Numbers, names and the like are written as they were typed
+            _ => (ast.to_string(), ATOM),
+        }
+    }
+
+    /// Write a part, in brackets unless it binds at least as tightly as `power`
+    fn operand(&self, ast: &ast::Expression<'_>, power: u16) -> String {
+        match self.write(ast) {
+            (text, bound) if bound >= power => text,
+            (text, _) => format!("({})", text),
+        }
+    }
+
+    fn list(&self, items: &[ast::Expression<'_>]) -> String {
+        items.iter().map(|item| self.write(item).0).collect::<Vec<_>>().join(", ")
+    }
+
+    /// How tightly a value binds: `-3` as a negation, `1/2`, `5 km` and `1+2i` loosely
+    fn value_power(&self, value: &str) -> u16 {
+        let plain = |text: &str| text.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '_');
+        if value.starts_with('"') || value.starts_with('[') || plain(value) {
+            ATOM
+        } else if value.starts_with('-') && plain(&value[1..]) {
+            self.powers.prefix_power(ast::Operator::Sub).unwrap_or(0)
+        } else {
+            0
+        }
+    }
+}
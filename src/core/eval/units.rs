@@ -0,0 +1,339 @@
+use std::fmt;
+
+use num::traits::ToPrimitive;
+
+use crate::core::eval::error::*;
+use crate::core::eval::lexer;
+use crate::core::eval::{ast, ast::ExpressionKind};
+
+/// Exponents of the SI base units, in the order of `BASE_UNITS`
+pub(crate) type Dim = [i8; 7];
+
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+pub(crate) const DIMENSIONLESS: Dim = [0, 0, 0, 0, 0, 0, 0];
+
+pub(crate) const LENGTH: Dim = [1, 0, 0, 0, 0, 0, 0];
+const MASS: Dim = [0, 1, 0, 0, 0, 0, 0];
+pub(crate) const TIME: Dim = [0, 0, 1, 0, 0, 0, 0];
+const CURRENT: Dim = [0, 0, 0, 1, 0, 0, 0];
+const TEMPERATURE: Dim = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: Dim = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: Dim = [0, 0, 0, 0, 0, 0, 1];
+
+const AREA: Dim = [2, 0, 0, 0, 0, 0, 0];
+const VOLUME: Dim = [3, 0, 0, 0, 0, 0, 0];
+const SPEED: Dim = [1, 0, -1, 0, 0, 0, 0];
+const FREQUENCY: Dim = [0, 0, -1, 0, 0, 0, 0];
+const FORCE: Dim = [1, 1, -2, 0, 0, 0, 0];
+const PRESSURE: Dim = [-1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dim = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dim = [2, 1, -3, 0, 0, 0, 0];
+const CHARGE: Dim = [0, 0, 1, 1, 0, 0, 0];
+const VOLTAGE: Dim = [2, 1, -3, -1, 0, 0, 0];
+const RESISTANCE: Dim = [2, 1, -3, -2, 0, 0, 0];
+
+/// Size in SI base units and dimension of a named unit
+pub(crate) fn lookup(name: &str) -> Option<(f64, Dim)> {
+    Some(match name {
+        "m" => (1.0, LENGTH),
+        "km" => (1e3, LENGTH),
+        "cm" => (1e-2, LENGTH),
+        "mm" => (1e-3, LENGTH),
+        "um" => (1e-6, LENGTH),
+        "nm" => (1e-9, LENGTH),
+        "in" => (0.0254, LENGTH),
+        "ft" => (0.3048, LENGTH),
+        "yd" => (0.9144, LENGTH),
+        "mi" => (1609.344, LENGTH),
+        "nmi" => (1852.0, LENGTH),
+
+        "kg" => (1.0, MASS),
+        "g" => (1e-3, MASS),
+        "mg" => (1e-6, MASS),
+        "tonne" => (1e3, MASS),
+        "lb" => (0.45359237, MASS),
+        "oz" => (0.028349523125, MASS),
+
+        "s" | "sec" | "second" | "seconds" => (1.0, TIME),
+        "ms" => (1e-3, TIME),
+        "us" => (1e-6, TIME),
+        "ns" => (1e-9, TIME),
+        "min" | "minute" | "minutes" => (60.0, TIME),
+        "h" | "hr" | "hour" | "hours" => (3600.0, TIME),
+        "day" | "days" => (86400.0, TIME),
+        "week" | "weeks" => (604800.0, TIME),
+        "yr" | "year" | "years" => (31557600.0, TIME), // Julian year
+
+        "A" => (1.0, CURRENT),
+        "mA" => (1e-3, CURRENT),
+        "K" => (1.0, TEMPERATURE),
+        "mol" => (1.0, AMOUNT),
+        "cd" => (1.0, LUMINOSITY),
+
+        "ha" => (1e4, AREA),
+        "acre" => (4046.8564224, AREA),
+        "L" | "l" => (1e-3, VOLUME),
+        "mL" | "ml" => (1e-6, VOLUME),
+        "gal" => (0.003785411784, VOLUME),
+
+        "mph" => (0.44704, SPEED),
+        "kn" => (1852.0 / 3600.0, SPEED),
+
+        "Hz" => (1.0, FREQUENCY),
+        "kHz" => (1e3, FREQUENCY),
+        "MHz" => (1e6, FREQUENCY),
+        "GHz" => (1e9, FREQUENCY),
+
+        "N" => (1.0, FORCE),
+        "kN" => (1e3, FORCE),
+        "lbf" => (4.4482216152605, FORCE),
+
+        "Pa" => (1.0, PRESSURE),
+        "kPa" => (1e3, PRESSURE),
+        "bar" => (1e5, PRESSURE),
+        "atm" => (101325.0, PRESSURE),
+        "psi" => (6894.757293168, PRESSURE),
+
+        "J" => (1.0, ENERGY),
+        "kJ" => (1e3, ENERGY),
+        "cal" => (4.184, ENERGY),
+        "kcal" => (4184.0, ENERGY),
+        "eV" => (1.602176634e-19, ENERGY),
+        "Wh" => (3600.0, ENERGY),
+        "kWh" => (3.6e6, ENERGY),
+
+        "W" => (1.0, POWER),
+        "kW" => (1e3, POWER),
+        "MW" => (1e6, POWER),
+        "hp" => (745.699872, POWER),
+
+        "C" => (1.0, CHARGE),
+        "V" => (1.0, VOLTAGE),
+        "ohm" => (1.0, RESISTANCE),
+        _ => return None,
+    })
+}
+
+/// Spell out a dimension in SI base units, such as `kg*m/s**2`
+pub(crate) fn dim_name(dim: &Dim) -> String {
+    let power = |(unit, exp): (&&str, i8)| match exp {
+        1 => unit.to_string(),
+        _ => format!("{}**{}", unit, exp),
+    };
+
+    let numer: Vec<String> = BASE_UNITS
+        .iter()
+        .zip(dim.iter().copied())
+        .filter(|(_, exp)| *exp > 0)
+        .map(power)
+        .collect();
+    let denom: Vec<String> = BASE_UNITS
+        .iter()
+        .zip(dim.iter().map(|exp| -exp))
+        .filter(|(_, exp)| *exp > 0)
+        .map(power)
+        .collect();
+
+    let numer = match numer.len() {
+        0 => "1".to_string(),
+        _ => numer.join("*"),
+    };
+    match denom.len() {
+        0 => numer,
+        1 => format!("{}/{}", numer, denom[0]),
+        _ => format!("{}/({})", numer, denom.join("*")),
+    }
+}
+
+/// Dimension of a product, or of a quotient when `invert` is set
+fn combine(mut dim: Dim, other: &Dim, invert: bool) -> Dim {
+    for (exp, other) in dim.iter_mut().zip(other.iter()) {
+        *exp = if invert { *exp - other } else { *exp + other };
+    }
+    dim
+}
+
+/// The unit a quantity is shown in
+#[derive(Debug, Clone)]
+pub(crate) struct Unit {
+    pub(crate) name: String,
+    pub(crate) factor: f64, // Size in SI base units
+}
+
+/// A number with a physical dimension, stored in SI base units
+#[derive(Debug, Clone)]
+pub(crate) struct Quantity {
+    pub(crate) value: f64,
+    pub(crate) dim: Dim,
+    pub(crate) unit: Option<Unit>, // None to show in SI base units
+}
+
+impl Quantity {
+    /// A plain number, as a quantity without dimension
+    pub(crate) fn scalar(value: f64) -> Self {
+        Quantity {
+            value,
+            dim: DIMENSIONLESS,
+            unit: None,
+        }
+    }
+
+    /// One of a named unit, such as `km`
+    pub(crate) fn of_unit(name: &str) -> Option<Self> {
+        let (factor, dim) = lookup(name)?;
+        Some(Quantity {
+            value: factor,
+            dim,
+            unit: Some(Unit {
+                name: name.to_string(),
+                factor,
+            }),
+        })
+    }
+
+    /// The number and unit it is written with
+    pub(crate) fn shown(&self) -> (f64, String) {
+        match &self.unit {
+            Some(unit) => (self.value / unit.factor, unit.name.clone()),
+            None => (self.value, dim_name(&self.dim)),
+        }
+    }
+
+    pub(crate) fn with_value(&self, value: f64) -> Self {
+        Quantity {
+            value,
+            dim: self.dim,
+            unit: self.unit.clone(),
+        }
+    }
+
+    /// Multiply, or divide when `invert` is set, adding up the dimensions
+    pub(crate) fn product(&self, other: &Self, invert: bool) -> Self {
+        let dim = combine(self.dim, &other.dim, invert);
+
+        // Scaling keeps the unit, `5 * km` is still in `km`
+        let unit = match (self.dim == DIMENSIONLESS, other.dim == DIMENSIONLESS) {
+            (false, true) => self.unit.clone(),
+            (true, false) if !invert => other.unit.clone(),
+            _ => None,
+        };
+
+        Quantity {
+            value: if invert {
+                self.value / other.value
+            } else {
+                self.value * other.value
+            },
+            dim,
+            unit,
+        }
+    }
+
+    pub(crate) fn powi(&self, exp: i8) -> Self {
+        let mut dim = self.dim;
+        for val in dim.iter_mut() {
+            *val = val.saturating_mul(exp);
+        }
+
+        Quantity {
+            value: self.value.powi(i32::from(exp)),
+            dim,
+            unit: None,
+        }
+    }
+}
+
+/// Quantities are equal when they measure the same amount, whatever the unit
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.dim == other.dim && self.value == other.value
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (value, unit) = self.shown();
+        write!(f, "{} {}", value, unit)
+    }
+}
+
+fn unit_exponent(expr: &ast::Expression<'_>) -> Option<i8> {
+    match &expr.expr {
+        ExpressionKind::Integer(val) => lexer::parse_integer(val).ok()?.to_i8(),
+        ExpressionKind::PrefixOp(prefix) if prefix.op == ast::Operator::Sub => {
+            unit_exponent(&prefix.value).map(|val: i8| -val)
+        }
+        _ => None,
+    }
+}
+
+/// Resolve the target of an `as` conversion, such as `km/h` or `m/s**2`
+pub(crate) fn target(expr: &ast::Expression<'_>) -> Result<(Unit, Dim), Error> {
+    match &expr.expr {
+        ExpressionKind::Ident(name) => match lookup(name) {
+            Some((factor, dim)) => Ok((
+                Unit {
+                    name: name.to_string(),
+                    factor,
+                },
+                dim,
+            )),
+            None => Err(Error::new(
+                format!("unknown type or unit `{}`", name),
+                ErrorType::TypeError,
+                expr.pos,
+            )),
+        },
+        ExpressionKind::InfixOp(infix)
+            if infix.op == ast::Operator::Mul || infix.op == ast::Operator::Div =>
+        {
+            let (left, left_dim) = target(&infix.left)?;
+            let (right, right_dim) = target(&infix.right)?;
+            let invert = infix.op == ast::Operator::Div;
+
+            Ok((
+                Unit {
+                    name: format!("{}{}{}", left.name, if invert { "/" } else { "*" }, right.name),
+                    factor: if invert {
+                        left.factor / right.factor
+                    } else {
+                        left.factor * right.factor
+                    },
+                },
+                combine(left_dim, &right_dim, invert),
+            ))
+        }
+        ExpressionKind::InfixOp(infix) if infix.op == ast::Operator::Pow => {
+            let exp = match unit_exponent(&infix.right) {
+                Some(exp) => exp,
+                None => {
+                    return Err(Error::new(
+                        format!("invalid unit exponent `{}`", infix.right.expr),
+                        ErrorType::TypeError,
+                        infix.right.pos,
+                    ))
+                }
+            };
+
+            let (base, base_dim) = target(&infix.left)?;
+            let mut dim = base_dim;
+            for val in dim.iter_mut() {
+                *val = val.saturating_mul(exp);
+            }
+
+            Ok((
+                Unit {
+                    name: format!("{}**{}", base.name, exp),
+                    factor: base.factor.powi(i32::from(exp)),
+                },
+                dim,
+            ))
+        }
+        _ => Err(Error::new(
+            format!("invalid type for `as` type operand `{}`", expr.expr),
+            ErrorType::TypeError,
+            expr.pos,
+        )),
+    }
+}
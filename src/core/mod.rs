@@ -1,6 +1,7 @@
 pub mod eval;
 pub mod game;
 pub mod handler;
+pub mod plot;
 pub mod shardmanager_container;
 pub mod utils;
 pub mod db;
@@ -0,0 +1,166 @@
+use cairo::{Context, FontSlant, FontWeight, Format, ImageSurface};
+
+use crate::core::eval::plot::{Plot, MAX_CURVES};
+
+const WIDTH: i32 = 800;
+const HEIGHT: i32 = 500;
+
+// Space around the plot area, left and bottom hold the tick labels
+const LEFT: f64 = 70.;
+const RIGHT: f64 = 20.;
+const TOP: f64 = 20.;
+const BOTTOM: f64 = 40.;
+
+/// Rough number of gridlines along each axis
+const TICKS: f64 = 8.;
+
+/// One colour per curve, in the order the expressions were given
+const PALETTE: [(f64, f64, f64); MAX_CURVES] = [
+    (0.12, 0.47, 0.71),
+    (0.84, 0.15, 0.16),
+    (0.17, 0.63, 0.17),
+    (1.00, 0.50, 0.05),
+    (0.58, 0.40, 0.74),
+    (0.55, 0.34, 0.29),
+    (0.89, 0.47, 0.76),
+    (0.09, 0.75, 0.81),
+];
+
+/// Maps plot coordinates to pixels
+struct Frame {
+    x: (f64, f64),
+    y: (f64, f64),
+}
+
+impl Frame {
+    fn px(&self, x: f64) -> f64 {
+        LEFT + (x - self.x.0) / (self.x.1 - self.x.0) * (WIDTH as f64 - LEFT - RIGHT)
+    }
+
+    fn py(&self, y: f64) -> f64 {
+        let py = HEIGHT as f64 - BOTTOM - (y - self.y.0) / (self.y.1 - self.y.0) * (HEIGHT as f64 - TOP - BOTTOM);
+        // Points far off towards a pole would overflow cairo's fixed point coordinates
+        py.max(-10. * HEIGHT as f64).min(11. * HEIGHT as f64)
+    }
+}
+
+/// Evenly spaced round values across `lo..=hi`, and the decimals needed to print them
+fn ticks(lo: f64, hi: f64) -> (Vec<f64>, usize) {
+    let rough = (hi - lo) / TICKS;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1., 2., 5., 10.]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10. * magnitude);
+
+    let decimals = (-step.log10().floor()).max(0.) as usize;
+    let ticks = ((lo / step).ceil() as i64..=(hi / step).floor() as i64)
+        .map(|i| i as f64 * step)
+        .collect();
+    (ticks, decimals)
+}
+
+/// Draw the curves with axes, gridlines and a legend, saving them as a PNG to `path`
+pub fn render(plot: &Plot, path: &str) -> Result<(), String> {
+    let surface = ImageSurface::create(Format::ARgb32, WIDTH, HEIGHT)
+        .map_err(|why| format!("Couldn't create surface: {:?}", why))?;
+    let ctx = Context::new(&surface);
+    let frame = Frame { x: plot.x, y: plot.y };
+    let (left, right) = (LEFT, WIDTH as f64 - RIGHT);
+    let (top, bottom) = (TOP, HEIGHT as f64 - BOTTOM);
+
+    ctx.set_source_rgb(1., 1., 1.);
+    ctx.paint();
+
+    ctx.select_font_face("sans-serif", FontSlant::Normal, FontWeight::Normal);
+    ctx.set_font_size(12.);
+
+    // Gridlines and their labels
+    ctx.set_line_width(1.);
+    let (xs, x_decimals) = ticks(plot.x.0, plot.x.1);
+    for x in xs {
+        let px = frame.px(x).round() + 0.5;
+        ctx.set_source_rgb(0.9, 0.9, 0.9);
+        ctx.move_to(px, top);
+        ctx.line_to(px, bottom);
+        ctx.stroke();
+
+        let label = format!("{:.*}", x_decimals, x);
+        let extents = ctx.text_extents(&label);
+        ctx.set_source_rgb(0.3, 0.3, 0.3);
+        ctx.move_to(px - extents.width / 2., bottom + 18.);
+        ctx.show_text(&label);
+    }
+
+    let (ys, y_decimals) = ticks(plot.y.0, plot.y.1);
+    for y in ys {
+        let py = frame.py(y).round() + 0.5;
+        ctx.set_source_rgb(0.9, 0.9, 0.9);
+        ctx.move_to(left, py);
+        ctx.line_to(right, py);
+        ctx.stroke();
+
+        let label = format!("{:.*}", y_decimals, y);
+        let extents = ctx.text_extents(&label);
+        ctx.set_source_rgb(0.3, 0.3, 0.3);
+        ctx.move_to(left - extents.width - 8., py + extents.height / 2.);
+        ctx.show_text(&label);
+    }
+
+    // Axes through the origin when it is in view, otherwise along the edges
+    ctx.set_source_rgb(0.2, 0.2, 0.2);
+    ctx.set_line_width(1.5);
+    let axis_x = if plot.x.0 <= 0. && 0. <= plot.x.1 { frame.px(0.) } else { left };
+    let axis_y = if plot.y.0 <= 0. && 0. <= plot.y.1 { frame.py(0.) } else { bottom };
+    ctx.move_to(axis_x, top);
+    ctx.line_to(axis_x, bottom);
+    ctx.move_to(left, axis_y);
+    ctx.line_to(right, axis_y);
+    ctx.stroke();
+
+    // Curves, each unbroken run of points as its own line
+    ctx.save();
+    ctx.rectangle(left, top, right - left, bottom - top);
+    ctx.clip();
+    ctx.set_line_width(2.);
+    for (curve, colour) in plot.curves.iter().zip(PALETTE.iter()) {
+        ctx.set_source_rgb(colour.0, colour.1, colour.2);
+        for segment in &curve.segments {
+            ctx.move_to(frame.px(segment[0].0), frame.py(segment[0].1));
+            for &(x, y) in &segment[1..] {
+                ctx.line_to(frame.px(x), frame.py(y));
+            }
+            ctx.stroke();
+        }
+    }
+    ctx.restore();
+
+    // Legend in the top left corner
+    let widest = plot
+        .curves
+        .iter()
+        .map(|curve| ctx.text_extents(&curve.label).width)
+        .fold(0., f64::max);
+    ctx.set_source_rgba(1., 1., 1., 0.85);
+    ctx.rectangle(left + 10., top + 10., widest + 46., plot.curves.len() as f64 * 18. + 10.);
+    ctx.fill();
+
+    ctx.set_line_width(2.);
+    for (i, (curve, colour)) in plot.curves.iter().zip(PALETTE.iter()).enumerate() {
+        let line = top + 24. + i as f64 * 18.;
+        ctx.set_source_rgb(colour.0, colour.1, colour.2);
+        ctx.move_to(left + 16., line - 4.);
+        ctx.line_to(left + 36., line - 4.);
+        ctx.stroke();
+
+        ctx.set_source_rgb(0.1, 0.1, 0.1);
+        ctx.move_to(left + 44., line);
+        ctx.show_text(&curve.label);
+    }
+
+    let mut file = std::fs::File::create(path).map_err(|why| format!("Couldn't create file: {}", why))?;
+    surface
+        .write_to_png(&mut file)
+        .map_err(|why| format!("Couldn't write to png: {:?}", why))
+}
@@ -28,7 +28,7 @@ struct Misc;
 struct Tech;
 
 #[group]
-#[commands(eval)]
+#[commands(eval, plot)]
 struct Util;
 
 #[group]